@@ -9,12 +9,17 @@ use std::{
     path::Path,
     str::FromStr,
 };
+use tartare_tools::equipments::{
+    equipment_property_mut, generate_object_id, get_prefix, set_stop_point_equipment,
+    stop_point_equipment, APPROPRIATE_ESCORT, APPROPRIATE_SIGNAGE, AUDIBLE_ANNOUNCEMENT,
+    BIKE_ACCEPTED, STOP_POINT_EQUIPMENTS, VISUAL_ANNOUNCEMENT,
+};
 use tartare_tools::report::Report;
 use transit_model::{
     model::Collections,
     objects::{
-        Availability, Comment, CommentType, Coord, Equipment, Geometry, Line, StopPoint,
-        TripProperty, VehicleJourney,
+        Availability, Comment, CommentType, Coord, Geometry, Line, StopPoint, TripProperty,
+        VehicleJourney,
     },
     PrefixConfiguration, Result,
 };
@@ -59,30 +64,7 @@ struct PropertyRule {
 }
 
 const AIR_CONDITIONED: &str = "air_conditioned";
-const APPROPRIATE_ESCORT: &str = "appropriate_escort";
-const APPROPRIATE_SIGNAGE: &str = "appropriate_signage";
-const AUDIBLE_ANNOUNCEMENT: &str = "audible_announcement";
-const BIKE_ACCEPTED: &str = "bike_accepted";
-const BIKE_DEPOT: &str = "bike_depot";
-const ELEVATOR: &str = "elevator";
-const ESCALATOR: &str = "escalator";
-const SHELTERED: &str = "sheltered";
-const VISUAL_ANNOUNCEMENT: &str = "visual_announcement";
 const WHEELCHAIR_ACCESSIBLE: &str = "wheelchair_accessible";
-const WHEELCHAIR_BOARDING: &str = "wheelchair_boarding";
-
-const STOP_POINT_EQUIPMENTS: &[&str] = &[
-    APPROPRIATE_ESCORT,
-    APPROPRIATE_SIGNAGE,
-    AUDIBLE_ANNOUNCEMENT,
-    BIKE_ACCEPTED,
-    BIKE_DEPOT,
-    ELEVATOR,
-    ESCALATOR,
-    SHELTERED,
-    VISUAL_ANNOUNCEMENT,
-    WHEELCHAIR_BOARDING,
-];
 
 const LINE_TRIP_PROPERTIES: &[&str] = &[
     AIR_CONDITIONED,
@@ -259,21 +241,6 @@ fn update_object_id<T>(
     }
 }
 
-fn generate_object_id<T>(
-    collection: &mut CollectionWithId<T>,
-    prefix_conf: &PrefixConfiguration,
-) -> String {
-    let mut inc = 0;
-    let mut available = false;
-    let mut id = String::new();
-    while !available {
-        inc += 1;
-        id = prefix_conf.schedule_prefix(inc.to_string().as_str());
-        available = !collection.contains_id(&id);
-    }
-    id
-}
-
 fn add_comment_on_line(
     p: &PropertyRule,
     collections: &mut Collections,
@@ -966,60 +933,6 @@ lazy_static! {
     };
 }
 
-// To pool
-fn get_prefix(collections: &Collections) -> PrefixConfiguration {
-    collections
-        .datasets
-        .values()
-        // Take any dataset, no reason to chose one in particular
-        .next()
-        .map(|dataset| &dataset.id)
-        .map(|id| id.split(':').collect::<Vec<_>>())
-        .map(|split| {
-            let mut prefix_conf = PrefixConfiguration::default();
-            if split.len() > 1 {
-                // If the original `dataset id` contains ':', the first part
-                // must be the `data_prefix` and we assume the last part is the
-                // `schedule_subprefix`. This should work fine for the following cases:
-                // - `IDFM:a1b2c3d4e5f6g7h8`
-                // - `IDFM:RATP:a1b2c3d4e5f6g7h8`
-                prefix_conf.set_data_prefix(split[0]);
-                let subprefix = split[split.len() - 1];
-                let truncate_index = usize::min(subprefix.len(), 6);
-                prefix_conf.set_schedule_subprefix(&subprefix[0..truncate_index]);
-            }
-            prefix_conf
-        })
-        .unwrap_or_default()
-}
-
-fn get_id_or_create_equipment(
-    equipment: Equipment,
-    collection_equipments: &mut CollectionWithId<Equipment>,
-    prefix_conf: &PrefixConfiguration,
-) -> String {
-    let similar_equipments: Vec<&Equipment> = collection_equipments
-        .values()
-        .filter(|eq| eq.is_similar(&equipment))
-        .collect();
-
-    if !similar_equipments.is_empty() {
-        // Similar equipments may exist, especially after a merge of ntfs (with different prefixes)
-        // We take the first one that matches. Rare case.
-        return similar_equipments[0].id.clone();
-    }
-
-    let equipment_id = generate_object_id(collection_equipments, prefix_conf);
-
-    collection_equipments
-        .push(Equipment {
-            id: equipment_id.clone(),
-            ..equipment
-        })
-        .unwrap();
-    equipment_id
-}
-
 fn update_stop_points_equipments(
     sp_equipments_properties: BTreeMap<String, Vec<PropertyRule>>,
     report: &mut Report<ReportCategory>,
@@ -1029,12 +942,7 @@ fn update_stop_points_equipments(
     let any_prop = "*".to_string();
 
     for (stop_point_id, equipment_properties) in sp_equipments_properties {
-        let mut sp_equipment = collections
-            .stop_points
-            .get(&stop_point_id)
-            .and_then(|sp| sp.equipment_id.as_ref())
-            .and_then(|eq_id| collections.equipments.get(eq_id).cloned())
-            .unwrap_or_default();
+        let mut sp_equipment = stop_point_equipment(collections, &stop_point_id);
 
         for equipment_property in equipment_properties {
             // Checks any existing equipment (for this stop point) to compare old values
@@ -1049,19 +957,12 @@ fn update_stop_points_equipments(
                             continue;
                         }
                     };
-                    let equipment_old_value = match equipment_property.property_name.as_str() {
-                        APPROPRIATE_ESCORT => sp_equipment.appropriate_escort,
-                        APPROPRIATE_SIGNAGE => sp_equipment.appropriate_signage,
-                        AUDIBLE_ANNOUNCEMENT => sp_equipment.audible_announcement,
-                        BIKE_ACCEPTED => sp_equipment.bike_accepted,
-                        BIKE_DEPOT => sp_equipment.bike_depot,
-                        ELEVATOR => sp_equipment.elevator,
-                        ESCALATOR => sp_equipment.escalator,
-                        SHELTERED => sp_equipment.sheltered,
-                        VISUAL_ANNOUNCEMENT => sp_equipment.visual_announcement,
-                        WHEELCHAIR_BOARDING => sp_equipment.wheelchair_boarding,
-                        _ => Availability::InformationNotAvailable,
-                    };
+                    let equipment_old_value = equipment_property_mut(
+                        &mut sp_equipment,
+                        &equipment_property.property_name,
+                    )
+                    .map(|availability| *availability)
+                    .unwrap_or(Availability::InformationNotAvailable);
                     if property_old_value != equipment_old_value {
                         property_old_value_do_not_match(report, &equipment_property);
                         continue;
@@ -1082,28 +983,14 @@ fn update_stop_points_equipments(
                     continue;
                 }
             };
-            match equipment_property.property_name.as_str() {
-                APPROPRIATE_ESCORT => sp_equipment.appropriate_escort = property_value,
-                APPROPRIATE_SIGNAGE => sp_equipment.appropriate_signage = property_value,
-                AUDIBLE_ANNOUNCEMENT => sp_equipment.audible_announcement = property_value,
-                BIKE_ACCEPTED => sp_equipment.bike_accepted = property_value,
-                BIKE_DEPOT => sp_equipment.bike_depot = property_value,
-                ELEVATOR => sp_equipment.elevator = property_value,
-                ESCALATOR => sp_equipment.escalator = property_value,
-                SHELTERED => sp_equipment.sheltered = property_value,
-                VISUAL_ANNOUNCEMENT => sp_equipment.visual_announcement = property_value,
-                WHEELCHAIR_BOARDING => sp_equipment.wheelchair_boarding = property_value,
-                _ => (),
+            if let Some(availability) =
+                equipment_property_mut(&mut sp_equipment, &equipment_property.property_name)
+            {
+                *availability = property_value;
             }
         }
 
-        if let Some(mut sp) = collections.stop_points.get_mut(&stop_point_id) {
-            sp.equipment_id = Some(get_id_or_create_equipment(
-                sp_equipment,
-                &mut collections.equipments,
-                prefix_conf,
-            ));
-        }
+        set_stop_point_equipment(collections, &stop_point_id, sp_equipment, prefix_conf);
     }
 }
 
@@ -1313,38 +1200,4 @@ mod tests {
             report_string.as_str()
         );
     }
-
-    mod get_prefix {
-        use super::*;
-        use pretty_assertions::assert_eq;
-        use transit_model::objects::Dataset;
-
-        fn schedule_prefix_from_dataset_id(dataset_id: &str, id: &str) -> String {
-            let mut collections = Collections::default();
-            collections.datasets = CollectionWithId::from(Dataset {
-                id: dataset_id.to_string(),
-                ..Default::default()
-            });
-            let prefix_conf = get_prefix(&collections);
-            prefix_conf.schedule_prefix(id)
-        }
-
-        #[test]
-        fn no_data_prefix() {
-            let prefixed_id = schedule_prefix_from_dataset_id("a1b2c3d4e5f6g7h8", "foo");
-            assert_eq!("foo", prefixed_id);
-        }
-
-        #[test]
-        fn with_data_prefix() {
-            let prefixed_id = schedule_prefix_from_dataset_id("IDFM:a1b2c3d4e5f6g7h8", "foo");
-            assert_eq!("IDFM:a1b2c3:foo", prefixed_id);
-        }
-
-        #[test]
-        fn with_multiple_data_prefix() {
-            let prefixed_id = schedule_prefix_from_dataset_id("IDFM:RATP:a1b2c3d4e5f6g7h8", "foo");
-            assert_eq!("IDFM:a1b2c3:foo", prefixed_id);
-        }
-    }
 }
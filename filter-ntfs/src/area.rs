@@ -0,0 +1,242 @@
+//! Selection of the vehicle journeys serving a geographic area.
+
+use failure::{bail, format_err, ResultExt};
+use geo::algorithm::contains::Contains;
+use geo::{LineString, MultiPolygon, Point, Polygon};
+use relational_types::IdxSet;
+use serde_json::Value;
+use std::fs::File;
+use std::path::Path;
+use transit_model::{
+    model::Collections,
+    objects::{Coord, VehicleJourney},
+    Model, Result,
+};
+use typed_index_collection::CollectionWithId;
+
+#[derive(Debug, Clone)]
+pub enum Area {
+    BoundingBox {
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+    },
+    Polygon(MultiPolygon<f64>),
+}
+
+fn ring(value: &Value) -> Result<LineString<f64>> {
+    let positions = value
+        .as_array()
+        .ok_or_else(|| format_err!("a polygon ring should be an array of positions"))?;
+    positions
+        .iter()
+        .map(|position| match position.as_array().map(Vec::as_slice) {
+            Some([lon, lat, ..]) => match (lon.as_f64(), lat.as_f64()) {
+                (Some(lon), Some(lat)) => Ok((lon, lat)),
+                _ => bail!("a position should be [longitude, latitude]"),
+            },
+            _ => bail!("a position should be [longitude, latitude]"),
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(LineString::from)
+}
+
+fn polygon(value: &Value) -> Result<Polygon<f64>> {
+    let mut rings = value
+        .as_array()
+        .ok_or_else(|| format_err!("a polygon should be an array of rings"))?
+        .iter()
+        .map(ring)
+        .collect::<Result<Vec<_>>>()?;
+    if rings.is_empty() {
+        bail!("a polygon should have an exterior ring");
+    }
+    let exterior = rings.remove(0);
+    Ok(Polygon::new(exterior, rings))
+}
+
+// The polygons of a GeoJSON object, which can be a geometry, a feature or a
+// feature collection
+fn polygons(value: &Value) -> Result<Vec<Polygon<f64>>> {
+    match value["type"].as_str() {
+        Some("FeatureCollection") => Ok(value["features"]
+            .as_array()
+            .ok_or_else(|| format_err!("a feature collection should have features"))?
+            .iter()
+            .map(polygons)
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect()),
+        Some("Feature") => polygons(&value["geometry"]),
+        Some("Polygon") => Ok(vec![polygon(&value["coordinates"])?]),
+        Some("MultiPolygon") => value["coordinates"]
+            .as_array()
+            .ok_or_else(|| format_err!("a multipolygon should be an array of polygons"))?
+            .iter()
+            .map(polygon)
+            .collect(),
+        geojson_type => bail!(
+            "expected a Polygon or a MultiPolygon, {:?} given",
+            geojson_type
+        ),
+    }
+}
+
+impl Area {
+    /// Parses a bounding box like `min_lon,min_lat,max_lon,max_lat`.
+    pub fn from_bbox(bbox: &str) -> Result<Area> {
+        let error = || {
+            format_err!(
+                "expected bounding box should be \"min_lon,min_lat,max_lon,max_lat\", \"{}\" given",
+                bbox
+            )
+        };
+        let values = bbox
+            .split(',')
+            .map(|value| value.trim().parse::<f64>().map_err(|_| error()))
+            .collect::<Result<Vec<_>>>()?;
+        match values.as_slice() {
+            [min_lon, min_lat, max_lon, max_lat] if min_lon <= max_lon && min_lat <= max_lat => {
+                Ok(Area::BoundingBox {
+                    min_lon: *min_lon,
+                    min_lat: *min_lat,
+                    max_lon: *max_lon,
+                    max_lat: *max_lat,
+                })
+            }
+            _ => Err(error()),
+        }
+    }
+
+    /// Reads the polygons of a GeoJSON file.
+    pub fn from_geojson<P: AsRef<Path>>(path: P) -> Result<Area> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+        let value: Value =
+            serde_json::from_reader(file).with_context(|_| format!("Error reading {:?}", path))?;
+        let polygons = polygons(&value).with_context(|_| format!("Error reading {:?}", path))?;
+        if polygons.is_empty() {
+            bail!("no polygon in {:?}", path);
+        }
+        Ok(Area::Polygon(MultiPolygon(polygons)))
+    }
+
+    pub fn contains(&self, coord: &Coord) -> bool {
+        match self {
+            Area::BoundingBox {
+                min_lon,
+                min_lat,
+                max_lon,
+                max_lat,
+            } => {
+                coord.lon >= *min_lon
+                    && coord.lon <= *max_lon
+                    && coord.lat >= *min_lat
+                    && coord.lat <= *max_lat
+            }
+            Area::Polygon(multi_polygon) => {
+                multi_polygon.contains(&Point::new(coord.lon, coord.lat))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeographicFilter {
+    pub area: Area,
+    /// Cut the trips to their part inside the area (with an extraction) or
+    /// out of the area (with a removal), instead of keeping or removing them
+    /// entirely
+    pub cut_trips: bool,
+}
+
+/// The vehicle journeys serving at least one stop point in the area.
+pub fn vehicle_journeys_in_area(model: &Model, area: &Area) -> IdxSet<VehicleJourney> {
+    model
+        .stop_points
+        .iter()
+        .filter(|(_, stop_point)| area.contains(&stop_point.coord))
+        .flat_map(|(stop_point_idx, _)| {
+            let vehicle_journeys: IdxSet<VehicleJourney> =
+                model.get_corresponding_from_idx(stop_point_idx);
+            vehicle_journeys
+        })
+        .collect()
+}
+
+/// Keeps only the stop times inside (or out of) the area of the vehicle
+/// journeys for which `should_cut` is true, and removes the vehicle journeys
+/// with less than 2 stop times left. The geometries of the cut vehicle
+/// journeys are removed as they no longer match them.
+pub fn cut_vehicle_journeys<F>(
+    collections: &mut Collections,
+    area: &Area,
+    keep_inside: bool,
+    should_cut: F,
+) -> Result<()>
+where
+    F: Fn(&VehicleJourney) -> bool,
+{
+    let stop_points = &collections.stop_points;
+    let mut vehicle_journeys = collections.vehicle_journeys.take();
+    for vehicle_journey in vehicle_journeys.iter_mut() {
+        if !should_cut(vehicle_journey) {
+            continue;
+        }
+        let nb_stop_times = vehicle_journey.stop_times.len();
+        vehicle_journey.stop_times.retain(|stop_time| {
+            area.contains(&stop_points[stop_time.stop_point_idx].coord) == keep_inside
+        });
+        if vehicle_journey.stop_times.len() != nb_stop_times {
+            vehicle_journey.geometry_id = None;
+        }
+    }
+    vehicle_journeys.retain(|vehicle_journey| vehicle_journey.stop_times.len() >= 2);
+    collections.vehicle_journeys = CollectionWithId::new(vehicle_journeys)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coord(lon: f64, lat: f64) -> Coord {
+        Coord { lon, lat }
+    }
+
+    #[test]
+    fn bounding_box() {
+        let area = Area::from_bbox("2.3, 48.8, 2.4, 48.9").unwrap();
+        assert!(area.contains(&coord(2.35, 48.85)));
+        assert!(!area.contains(&coord(2.45, 48.85)));
+        assert!(Area::from_bbox("2.4,48.8,2.3,48.9").is_err());
+        assert!(Area::from_bbox("2.3,48.8,2.4").is_err());
+    }
+
+    #[test]
+    fn geojson_polygons() {
+        let value: Value = serde_json::from_str(
+            r#"{
+                "type": "FeatureCollection",
+                "features": [{
+                    "type": "Feature",
+                    "properties": {},
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [[[0, 0], [2, 0], [2, 2], [0, 2], [0, 0]]]
+                    }
+                }]
+            }"#,
+        )
+        .unwrap();
+        let area = Area::Polygon(MultiPolygon(polygons(&value).unwrap()));
+        assert!(area.contains(&coord(1., 1.)));
+        assert!(!area.contains(&coord(3., 1.)));
+
+        let point: Value =
+            serde_json::from_str(r#"{"type": "Point", "coordinates": [0, 0]}"#).unwrap();
+        assert!(polygons(&point).is_err());
+    }
+}
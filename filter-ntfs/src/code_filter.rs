@@ -0,0 +1,186 @@
+//! Selection of the vehicle journeys from the codes or the object properties
+//! of their lines, routes or stop points, like `line:external_code:X*`.
+
+use failure::{bail, format_err};
+use regex::Regex;
+use relational_types::IdxSet;
+use std::str::FromStr;
+use transit_model::{
+    model::GetCorresponding,
+    objects::{Codes, Properties, VehicleJourney},
+    Model, Result,
+};
+use typed_index_collection::CollectionWithId;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CodeObjectType {
+    Line,
+    Route,
+    StopPoint,
+}
+
+impl FromStr for CodeObjectType {
+    type Err = failure::Error;
+    fn from_str(object_type: &str) -> Result<Self> {
+        match object_type {
+            "line" => Ok(CodeObjectType::Line),
+            "route" => Ok(CodeObjectType::Route),
+            "stop_point" => Ok(CodeObjectType::StopPoint),
+            _ => bail!(
+                "expected object type should be line, route or stop_point, \"{}\" given",
+                object_type
+            ),
+        }
+    }
+}
+
+/// Whether the filter applies to the codes or to the object properties.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Attribute {
+    Code,
+    ObjectProperty,
+}
+
+/// A value matching any value when `None`, otherwise the regex of a
+/// `/regex/` or of a wildcard pattern (`*` for any characters, `?` for any
+/// character) matching the whole value.
+#[derive(Debug, Clone)]
+pub struct ValuePattern(Option<Regex>);
+
+impl ValuePattern {
+    pub fn parse(pattern: Option<&str>) -> Result<Self> {
+        let pattern = match pattern {
+            None => return Ok(ValuePattern(None)),
+            Some(pattern) => pattern,
+        };
+        let regex = if pattern.len() >= 2 && pattern.starts_with('/') && pattern.ends_with('/') {
+            pattern[1..pattern.len() - 1].to_string()
+        } else {
+            let wildcard: String = pattern
+                .chars()
+                .map(|c| match c {
+                    '*' => ".*".to_string(),
+                    '?' => ".".to_string(),
+                    c => regex::escape(&c.to_string()),
+                })
+                .collect();
+            format!("^{}$", wildcard)
+        };
+        let regex = Regex::new(&regex)
+            .map_err(|e| format_err!("invalid pattern \"{}\": {}", pattern, e))?;
+        Ok(ValuePattern(Some(regex)))
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        self.0.as_ref().map_or(true, |regex| regex.is_match(value))
+    }
+}
+
+impl PartialEq for ValuePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_ref().map(Regex::as_str) == other.0.as_ref().map(Regex::as_str)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeFilter {
+    pub object_type: CodeObjectType,
+    pub attribute: Attribute,
+    /// The code system or the object property name
+    pub key: String,
+    pub value: ValuePattern,
+}
+
+impl CodeFilter {
+    /// Parses a filter like `object_type:key[:value]`, the value being any
+    /// value when missing.
+    pub fn parse(attribute: Attribute, filter: &str) -> Result<Self> {
+        let mut parts = filter.splitn(3, ':');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(object_type), Some(key), value) if !key.is_empty() => Ok(CodeFilter {
+                object_type: object_type.parse()?,
+                attribute,
+                key: key.to_string(),
+                value: ValuePattern::parse(value)?,
+            }),
+            _ => bail!(
+                "expected filter should be \"object_type:key[:value]\", \"{}\" given",
+                filter
+            ),
+        }
+    }
+
+    fn matches<T: Codes + Properties>(&self, object: &T) -> bool {
+        let keys_values = match self.attribute {
+            Attribute::Code => object.codes(),
+            Attribute::ObjectProperty => object.properties(),
+        };
+        keys_values
+            .iter()
+            .any(|(key, value)| *key == self.key && self.value.matches(value))
+    }
+
+    // The vehicle journeys of the objects of `collection` matching the filter
+    fn select_in<T>(
+        &self,
+        model: &Model,
+        collection: &CollectionWithId<T>,
+    ) -> IdxSet<VehicleJourney>
+    where
+        T: Codes + Properties,
+        IdxSet<T>: GetCorresponding<VehicleJourney>,
+    {
+        let selected: IdxSet<T> = collection
+            .iter()
+            .filter(|(_, object)| self.matches(*object))
+            .map(|(idx, _)| idx)
+            .collect();
+        model.get_corresponding(&selected)
+    }
+
+    /// The vehicle journeys of the objects matching the filter.
+    pub fn select(&self, model: &Model) -> IdxSet<VehicleJourney> {
+        match self.object_type {
+            CodeObjectType::Line => self.select_in(model, &model.lines),
+            CodeObjectType::Route => self.select_in(model, &model.routes),
+            CodeObjectType::StopPoint => self.select_in(model, &model.stop_points),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_pattern() {
+        let pattern = ValuePattern::parse(Some("X*-?")).unwrap();
+        assert!(pattern.matches("X12-A"));
+        assert!(pattern.matches("X-A"));
+        assert!(!pattern.matches("X12-AB"));
+        assert!(!pattern.matches("AX12-A"));
+    }
+
+    #[test]
+    fn regex_pattern() {
+        let pattern = ValuePattern::parse(Some("/^[0-9]+$/")).unwrap();
+        assert!(pattern.matches("1234"));
+        assert!(!pattern.matches("12a"));
+        assert!(ValuePattern::parse(Some("/[/")).is_err());
+    }
+
+    #[test]
+    fn any_value() {
+        assert!(ValuePattern::parse(None).unwrap().matches("anything"));
+    }
+
+    #[test]
+    fn parse_filter() {
+        let filter = CodeFilter::parse(Attribute::Code, "line:external_code:a:b*").unwrap();
+        assert_eq!(CodeObjectType::Line, filter.object_type);
+        assert_eq!("external_code", filter.key);
+        assert!(filter.value.matches("a:bc"));
+        assert!(CodeFilter::parse(Attribute::Code, "network:external_code").is_err());
+        assert!(CodeFilter::parse(Attribute::Code, "line").is_err());
+    }
+}
@@ -0,0 +1,142 @@
+//! The NTFS written when the filtering removes all the trips and an empty
+//! result is accepted. `transit_model::ntfs::write` needs a dataset to
+//! compute the validity period, and `Model::new` removes the datasets
+//! without trips, so the files are written here, with only their header.
+
+use crate::unused_stops::STOP_COLUMNS;
+use chrono::{DateTime, FixedOffset};
+use failure::ResultExt;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use transit_model::{Result, NTFS_VERSION};
+
+// The mandatory files with their mandatory columns, all the columns of
+// stops.txt for the unused stops to be appended
+const FILES: &[(&str, &[&str])] = &[
+    ("contributors.txt", &["contributor_id", "contributor_name"]),
+    (
+        "datasets.txt",
+        &[
+            "dataset_id",
+            "contributor_id",
+            "dataset_start_date",
+            "dataset_end_date",
+        ],
+    ),
+    ("networks.txt", &["network_id", "network_name"]),
+    (
+        "commercial_modes.txt",
+        &["commercial_mode_id", "commercial_mode_name"],
+    ),
+    ("companies.txt", &["company_id", "company_name"]),
+    (
+        "lines.txt",
+        &["line_id", "line_name", "network_id", "commercial_mode_id"],
+    ),
+    (
+        "physical_modes.txt",
+        &["physical_mode_id", "physical_mode_name"],
+    ),
+    ("routes.txt", &["route_id", "route_name", "line_id"]),
+    (
+        "trips.txt",
+        &[
+            "route_id",
+            "service_id",
+            "trip_id",
+            "physical_mode_id",
+            "dataset_id",
+            "company_id",
+        ],
+    ),
+    (
+        "stop_times.txt",
+        &[
+            "trip_id",
+            "arrival_time",
+            "departure_time",
+            "stop_id",
+            "stop_sequence",
+        ],
+    ),
+    (
+        "calendar.txt",
+        &[
+            "service_id",
+            "monday",
+            "tuesday",
+            "wednesday",
+            "thursday",
+            "friday",
+            "saturday",
+            "sunday",
+            "start_date",
+            "end_date",
+        ],
+    ),
+    ("stops.txt", STOP_COLUMNS),
+];
+
+// Writes `file` in `path` with the `columns` header and the `records`
+fn write_file<'a>(
+    path: &Path,
+    file: &str,
+    columns: &[&str],
+    records: impl IntoIterator<Item = Vec<&'a str>>,
+) -> Result<()> {
+    let path = path.join(file);
+    let mut writer =
+        csv::Writer::from_path(&path).with_context(|_| format!("Error writing {:?}", path))?;
+    writer
+        .write_record(columns)
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    for record in records {
+        writer
+            .write_record(&record)
+            .with_context(|_| format!("Error writing {:?}", path))?;
+    }
+    writer
+        .flush()
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    Ok(())
+}
+
+/// Writes an NTFS without any object in `path`. The feed infos are the
+/// ones of the input, without the validity period.
+pub fn write(
+    path: &Path,
+    feed_infos: &BTreeMap<String, String>,
+    current_datetime: DateTime<FixedOffset>,
+) -> Result<()> {
+    fs::create_dir_all(path).with_context(|_| format!("Error writing {:?}", path))?;
+
+    let mut feed_infos = feed_infos.clone();
+    feed_infos.remove("feed_start_date");
+    feed_infos.remove("feed_end_date");
+    feed_infos.insert(
+        "feed_creation_date".to_string(),
+        current_datetime.format("%Y%m%d").to_string(),
+    );
+    feed_infos.insert(
+        "feed_creation_time".to_string(),
+        current_datetime.format("%T").to_string(),
+    );
+    feed_infos.insert(
+        "feed_creation_datetime".to_string(),
+        current_datetime.to_rfc3339(),
+    );
+    feed_infos.insert("ntfs_version".to_string(), NTFS_VERSION.to_string());
+    write_file(
+        path,
+        "feed_infos.txt",
+        &["feed_info_param", "feed_info_value"],
+        feed_infos
+            .iter()
+            .map(|(param, value)| vec![param.as_str(), value.as_str()]),
+    )?;
+    for (file, columns) in FILES {
+        write_file(path, file, columns, None)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,201 @@
+//! Filter expressions combining the property, code and object property
+//! filters with `and`, `or` and `not`, like `{"and": [{"network_id": "RATP"},
+//! {"not": {"line_code": "M1"}}, {"code": "line:external_code:X*"}]}`.
+
+use crate::code_filter::{Attribute, CodeFilter};
+use crate::filter::{filter_by_property, object_type_of_property, ObjectType};
+use failure::{bail, format_err, ResultExt};
+use relational_types::IdxSet;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+use transit_model::{objects::VehicleJourney, Model, Result};
+
+// An expression as written in the configuration file
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ExpressionConfig {
+    And { and: Vec<ExpressionConfig> },
+    Or { or: Vec<ExpressionConfig> },
+    Not { not: Box<ExpressionConfig> },
+    Code { code: String },
+    ObjectProperty { object_property: String },
+    Property(BTreeMap<String, String>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expression {
+    /// The vehicle journeys selected by a property filter
+    Property {
+        object_type: ObjectType,
+        property: String,
+        value: String,
+    },
+    /// The vehicle journeys selected by a code or an object property filter
+    Code(CodeFilter),
+    /// The vehicle journeys selected by all the expressions
+    And(Vec<Expression>),
+    /// The vehicle journeys selected by any of the expressions
+    Or(Vec<Expression>),
+    /// The vehicle journeys not selected by the expression
+    Not(Box<Expression>),
+}
+
+impl Expression {
+    fn from_config(config: ExpressionConfig) -> Result<Self> {
+        let from_configs = |configs: Vec<ExpressionConfig>| {
+            configs
+                .into_iter()
+                .map(Expression::from_config)
+                .collect::<Result<Vec<_>>>()
+        };
+        match config {
+            ExpressionConfig::And { and } => Ok(Expression::And(from_configs(and)?)),
+            ExpressionConfig::Or { or } => Ok(Expression::Or(from_configs(or)?)),
+            ExpressionConfig::Not { not } => {
+                Ok(Expression::Not(Box::new(Expression::from_config(*not)?)))
+            }
+            ExpressionConfig::Code { code } => {
+                Ok(Expression::Code(CodeFilter::parse(Attribute::Code, &code)?))
+            }
+            ExpressionConfig::ObjectProperty { object_property } => Ok(Expression::Code(
+                CodeFilter::parse(Attribute::ObjectProperty, &object_property)?,
+            )),
+            ExpressionConfig::Property(property_value) => {
+                let mut property_values = property_value.into_iter();
+                match (property_values.next(), property_values.next()) {
+                    (Some((property, value)), None) => {
+                        let object_type = object_type_of_property(&property).ok_or_else(|| {
+                            format_err!("Property '{}' not yet supported.", property)
+                        })?;
+                        Ok(Expression::Property {
+                            object_type,
+                            property,
+                            value,
+                        })
+                    }
+                    _ => bail!("a property filter should be like {{\"property\": \"value\"}}"),
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn from_json(json: &str) -> Result<Self> {
+        let config: ExpressionConfig = serde_json::from_str(json)?;
+        Expression::from_config(config)
+    }
+
+    /// Reads the expression of a JSON file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+        let config: ExpressionConfig =
+            serde_json::from_reader(file).with_context(|_| format!("Error reading {:?}", path))?;
+        let expression = Expression::from_config(config)
+            .with_context(|_| format!("Error reading {:?}", path))?;
+        Ok(expression)
+    }
+
+    /// The vehicle journeys selected by the expression.
+    pub fn evaluate(&self, model: &Model) -> Result<IdxSet<VehicleJourney>> {
+        match self {
+            Expression::Property {
+                object_type,
+                property,
+                value,
+            } => filter_by_property(model, *object_type, property, value),
+            Expression::Code(code_filter) => Ok(code_filter.select(model)),
+            Expression::And(expressions) => {
+                let mut selected_vjs = all_vehicle_journeys(model);
+                for expression in expressions {
+                    let vjs = expression.evaluate(model)?;
+                    selected_vjs.retain(|vj_idx| vjs.contains(vj_idx));
+                }
+                Ok(selected_vjs)
+            }
+            Expression::Or(expressions) => {
+                let mut selected_vjs = IdxSet::new();
+                for expression in expressions {
+                    selected_vjs.extend(expression.evaluate(model)?);
+                }
+                Ok(selected_vjs)
+            }
+            Expression::Not(expression) => {
+                let vjs = expression.evaluate(model)?;
+                Ok(all_vehicle_journeys(model)
+                    .into_iter()
+                    .filter(|vj_idx| !vjs.contains(vj_idx))
+                    .collect())
+            }
+        }
+    }
+}
+
+fn all_vehicle_journeys(model: &Model) -> IdxSet<VehicleJourney> {
+    model
+        .vehicle_journeys
+        .iter()
+        .map(|(vj_idx, _)| vj_idx)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn property(object_type: ObjectType, property: &str, value: &str) -> Expression {
+        Expression::Property {
+            object_type,
+            property: property.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_expression() {
+        let expression = Expression::from_json(
+            r#"{"and": [{"network_id": "RATP"}, {"not": {"or": [{"line_code": "M1"}]}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Expression::And(vec![
+                property(ObjectType::Network, "network_id", "RATP"),
+                Expression::Not(Box::new(Expression::Or(vec![property(
+                    ObjectType::Line,
+                    "line_code",
+                    "M1"
+                )]))),
+            ]),
+            expression
+        );
+    }
+
+    #[test]
+    fn parse_code_expression() {
+        let expression = Expression::from_json(
+            r#"{"or": [{"code": "line:external_code:X*"}, {"not": {"object_property": "route:direction"}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Expression::Or(vec![
+                Expression::Code(
+                    CodeFilter::parse(Attribute::Code, "line:external_code:X*").unwrap()
+                ),
+                Expression::Not(Box::new(Expression::Code(
+                    CodeFilter::parse(Attribute::ObjectProperty, "route:direction").unwrap()
+                ))),
+            ]),
+            expression
+        );
+        assert!(Expression::from_json(r#"{"code": "network:external_code"}"#).is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Expression::from_json(r#"{"unknown_id": "RATP"}"#).is_err());
+        assert!(Expression::from_json(r#"{"network_id": "RATP", "line_code": "M1"}"#).is_err());
+        assert!(Expression::from_json(r#"{"and": {"network_id": "RATP"}}"#).is_err());
+    }
+}
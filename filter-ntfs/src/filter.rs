@@ -2,6 +2,9 @@
 //! It can import and export data from [GTFS](http://gtfs.org/) and
 //! [NTFS](https://github.com/CanalTP/ntfs-specification/blob/master/ntfs_fr.md).
 
+use crate::area::{self, GeographicFilter};
+use crate::expression::Expression;
+use crate::time_window::{self, TimeWindow};
 use failure::{bail, format_err};
 use lazy_static::lazy_static;
 use relational_types::IdxSet;
@@ -19,6 +22,11 @@ pub enum Action {
 pub enum ObjectType {
     Network,
     Line,
+    StopArea,
+    CommercialMode,
+    PhysicalMode,
+    Company,
+    Contributor,
 }
 
 type PropertyValues = HashMap<String, HashSet<String>>;
@@ -27,6 +35,10 @@ type PropertyValues = HashMap<String, HashSet<String>>;
 pub struct Filter {
     action: Action,
     filters: HashMap<ObjectType, PropertyValues>,
+    expressions: Vec<Expression>,
+    geographic_filter: Option<GeographicFilter>,
+    time_window: TimeWindow,
+    allow_empty: bool,
 }
 
 impl Filter {
@@ -34,6 +46,10 @@ impl Filter {
         Filter {
             action,
             filters: HashMap::new(),
+            expressions: Vec::new(),
+            geographic_filter: None,
+            time_window: TimeWindow::default(),
+            allow_empty: false,
         }
     }
 
@@ -49,6 +65,26 @@ impl Filter {
             .or_insert_with(HashSet::new)
             .insert(value.into());
     }
+
+    /// Also selects the vehicle journeys of an expression.
+    pub fn add_expression(&mut self, expression: Expression) {
+        self.expressions.push(expression);
+    }
+
+    /// Also selects the vehicle journeys serving an area.
+    pub fn set_geographic_filter(&mut self, geographic_filter: GeographicFilter) {
+        self.geographic_filter = Some(geographic_filter);
+    }
+
+    /// Also restricts the data to a time window, whatever the action.
+    pub fn set_time_window(&mut self, time_window: TimeWindow) {
+        self.time_window = time_window;
+    }
+
+    /// Accepts a result without vehicle journeys instead of failing.
+    pub fn set_allow_empty(&mut self, allow_empty: bool) {
+        self.allow_empty = allow_empty;
+    }
 }
 
 type FnFilter = Box<dyn Fn(&Model, &str) -> Result<IdxSet<VehicleJourney>> + Send + Sync>;
@@ -96,11 +132,93 @@ lazy_static! {
             }),
         );
         m.insert(ObjectType::Line, line_filters);
+
+        // Stop area filters
+        let mut stop_area_filters: HashMap<&'static str, FnFilter> = HashMap::new();
+        stop_area_filters.insert(
+            "stop_area_id",
+            Box::new(|model, stop_area_id| {
+                model
+                    .stop_areas
+                    .get_idx(&stop_area_id)
+                    .ok_or_else(|| format_err!("Stop area '{}' not found.", stop_area_id))
+                    .map(|stop_area_idx| model.get_corresponding_from_idx(stop_area_idx))
+            }),
+        );
+        m.insert(ObjectType::StopArea, stop_area_filters);
+
+        // Commercial mode filters
+        let mut commercial_mode_filters: HashMap<&'static str, FnFilter> = HashMap::new();
+        commercial_mode_filters.insert(
+            "commercial_mode_id",
+            Box::new(|model, commercial_mode_id| {
+                model
+                    .commercial_modes
+                    .get_idx(&commercial_mode_id)
+                    .ok_or_else(|| {
+                        format_err!("Commercial mode '{}' not found.", commercial_mode_id)
+                    })
+                    .map(|commercial_mode_idx| {
+                        model.get_corresponding_from_idx(commercial_mode_idx)
+                    })
+            }),
+        );
+        m.insert(ObjectType::CommercialMode, commercial_mode_filters);
+
+        // Physical mode filters
+        let mut physical_mode_filters: HashMap<&'static str, FnFilter> = HashMap::new();
+        physical_mode_filters.insert(
+            "physical_mode_id",
+            Box::new(|model, physical_mode_id| {
+                model
+                    .physical_modes
+                    .get_idx(&physical_mode_id)
+                    .ok_or_else(|| format_err!("Physical mode '{}' not found.", physical_mode_id))
+                    .map(|physical_mode_idx| model.get_corresponding_from_idx(physical_mode_idx))
+            }),
+        );
+        m.insert(ObjectType::PhysicalMode, physical_mode_filters);
+
+        // Company filters
+        let mut company_filters: HashMap<&'static str, FnFilter> = HashMap::new();
+        company_filters.insert(
+            "company_id",
+            Box::new(|model, company_id| {
+                model
+                    .companies
+                    .get_idx(&company_id)
+                    .ok_or_else(|| format_err!("Company '{}' not found.", company_id))
+                    .map(|company_idx| model.get_corresponding_from_idx(company_idx))
+            }),
+        );
+        m.insert(ObjectType::Company, company_filters);
+
+        // Contributor filters
+        let mut contributor_filters: HashMap<&'static str, FnFilter> = HashMap::new();
+        contributor_filters.insert(
+            "contributor_id",
+            Box::new(|model, contributor_id| {
+                model
+                    .contributors
+                    .get_idx(&contributor_id)
+                    .ok_or_else(|| format_err!("Contributor '{}' not found.", contributor_id))
+                    .map(|contributor_idx| model.get_corresponding_from_idx(contributor_idx))
+            }),
+        );
+        m.insert(ObjectType::Contributor, contributor_filters);
         m
     };
 }
 
-fn filter_by_property(
+/// The object type with a property filter of this name.
+pub(crate) fn object_type_of_property(property: &str) -> Option<ObjectType> {
+    PROPERTY_FILTERS
+        .iter()
+        .find(|(_, property_filters)| property_filters.contains_key(property))
+        .map(|(object_type, _)| *object_type)
+}
+
+pub(crate) fn filter_by_property(
     model: &Model,
     object_type: ObjectType,
     property: &str,
@@ -119,14 +237,13 @@ fn filter_from_idxset<T: Id<T>>(
     idx_set: IdxSet<T>,
     action: Action,
 ) {
-    let ids: Vec<String> = idx_set
+    let ids: HashSet<String> = idx_set
         .into_iter()
         .map(|idx| collection[idx].id().to_string())
         .collect();
-    let id_refs: Vec<&str> = ids.iter().map(String::as_str).collect();
     collection.retain(|object| match action {
-        Action::Extract => id_refs.contains(&object.id()),
-        Action::Remove => !id_refs.contains(&object.id()),
+        Action::Extract => ids.contains(object.id()),
+        Action::Remove => !ids.contains(object.id()),
     });
 }
 
@@ -134,12 +251,22 @@ fn updated_stop_time_attributes(
     vehicle_journeys: &CollectionWithId<VehicleJourney>,
     attributes_map: &mut HashMap<(String, u32), String>,
 ) {
-    attributes_map.retain(|(vj_id, _), _| vehicle_journeys.contains_id(&vj_id));
+    attributes_map.retain(|(vj_id, sequence), _| {
+        vehicle_journeys
+            .get(&vj_id)
+            .map_or(false, |vehicle_journey| {
+                vehicle_journey
+                    .stop_times
+                    .iter()
+                    .any(|stop_time| stop_time.sequence == *sequence)
+            })
+    });
 }
 
 /// Extract or remove part of the dataset from property filters on an object (Network, Line, etc.)
+/// or from an area, and restrict it to the time window of the filter
 pub fn filter(model: Model, filter: &Filter) -> Result<Model> {
-    let selected_vjs = filter
+    let mut selected_vjs = filter
         .filters
         .iter()
         .flat_map(|(object_type, property_values)| {
@@ -163,13 +290,66 @@ pub fn filter(model: Model, filter: &Filter) -> Result<Model> {
             },
         )?;
 
+    for expression in &filter.expressions {
+        selected_vjs.extend(expression.evaluate(&model)?);
+    }
+
+    // Vehicle journeys of the area to cut when extracting (the ones also
+    // selected by a property are kept entirely)
+    let mut vj_ids_to_cut = HashSet::new();
+    if let Some(geographic_filter) = &filter.geographic_filter {
+        let area_vjs = area::vehicle_journeys_in_area(&model, &geographic_filter.area);
+        match (geographic_filter.cut_trips, filter.action) {
+            // The removal only cuts the remaining vehicle journeys
+            (true, Action::Remove) => {}
+            (true, Action::Extract) => {
+                vj_ids_to_cut = area_vjs
+                    .difference(&selected_vjs)
+                    .map(|&vj_idx| model.vehicle_journeys[vj_idx].id.clone())
+                    .collect();
+                selected_vjs.extend(area_vjs);
+            }
+            (false, _) => selected_vjs.extend(area_vjs),
+        }
+    }
+
     let mut collections = model.into_collections();
 
-    filter_from_idxset(
-        &mut collections.vehicle_journeys,
-        selected_vjs,
-        filter.action,
-    );
+    // Without property, expression or geographic filters, the time
+    // window alone selects the trips
+    if !filter.filters.is_empty()
+        || !filter.expressions.is_empty()
+        || filter.geographic_filter.is_some()
+        || filter.time_window.is_empty()
+    {
+        filter_from_idxset(
+            &mut collections.vehicle_journeys,
+            selected_vjs,
+            filter.action,
+        );
+    }
+    if let Some(geographic_filter) = filter
+        .geographic_filter
+        .as_ref()
+        .filter(|geographic_filter| geographic_filter.cut_trips)
+    {
+        match filter.action {
+            Action::Extract => {
+                area::cut_vehicle_journeys(&mut collections, &geographic_filter.area, true, |vj| {
+                    vj_ids_to_cut.contains(&vj.id)
+                })?
+            }
+            Action::Remove => area::cut_vehicle_journeys(
+                &mut collections,
+                &geographic_filter.area,
+                false,
+                |_| true,
+            )?,
+        }
+    }
+    if !filter.time_window.is_empty() {
+        time_window::restrict(&mut collections, &filter.time_window)?;
+    }
 
     updated_stop_time_attributes(
         &collections.vehicle_journeys,
@@ -184,7 +364,7 @@ pub fn filter(model: Model, filter: &Filter) -> Result<Model> {
         &mut collections.stop_time_comments,
     );
 
-    if collections.vehicle_journeys.is_empty() {
+    if collections.vehicle_journeys.is_empty() && !filter.allow_empty {
         bail!("the data does not contain vehicle journeys anymore.")
     }
 
@@ -1,11 +1,19 @@
-use chrono::{DateTime, FixedOffset};
-use failure::format_err;
-use log::info;
+use chrono::{DateTime, FixedOffset, NaiveDate, Weekday};
+use failure::{bail, format_err};
+use log::{info, warn};
+use std::fs::File;
 use std::path::PathBuf;
 use structopt::{clap::arg_enum, StructOpt};
-use transit_model::Result;
+use transit_model::{objects::Time, Result};
 
+mod area;
+mod code_filter;
+mod empty_ntfs;
+mod expression;
 mod filter;
+mod report;
+mod time_window;
+mod unused_stops;
 
 arg_enum! {
     #[derive(Debug)]
@@ -34,7 +42,7 @@ struct Opt {
     #[structopt(short, long, parse(from_os_str), default_value = ".")]
     input: PathBuf,
 
-    /// Extract or remove networks and / or lines
+    /// Extract or remove the selected objects
     #[structopt(possible_values = &Action::variants(), case_insensitive = true)]
     action: Action,
 
@@ -46,6 +54,92 @@ struct Opt {
     #[structopt(short, long)]
     lines: Vec<String>,
 
+    /// Stop area filters (the trips serving the stop areas are selected)
+    #[structopt(long)]
+    stop_areas: Vec<String>,
+
+    /// Commercial mode filters
+    #[structopt(long)]
+    commercial_modes: Vec<String>,
+
+    /// Physical mode filters
+    #[structopt(long)]
+    physical_modes: Vec<String>,
+
+    /// Company filters
+    #[structopt(long)]
+    companies: Vec<String>,
+
+    /// Contributor filters
+    #[structopt(long)]
+    contributors: Vec<String>,
+
+    /// JSON files of filter expressions combining the property, code and
+    /// object property filters with and, or and not (like {"and":
+    /// [{"network_id": "network1"}, {"not": {"code":
+    /// "line:external_code:X*"}}]})
+    #[structopt(long, parse(from_os_str))]
+    filter_config: Vec<PathBuf>,
+
+    /// Code filters like line:external_code:X* (object type line, route or
+    /// stop_point, code system and value); the value is a wildcard pattern
+    /// (* and ?) or a /regex/, and any value matches when omitted
+    #[structopt(long)]
+    codes: Vec<String>,
+
+    /// Object property filters like route:osm_route_id (object type, property
+    /// name and value), with values like the code filters
+    #[structopt(long)]
+    object_properties: Vec<String>,
+
+    /// Select the trips serving a stop in this bounding box (like
+    /// min_lon,min_lat,max_lon,max_lat)
+    #[structopt(long, conflicts_with = "area")]
+    bbox: Option<String>,
+
+    /// Select the trips serving a stop in the polygons of this GeoJSON file
+    #[structopt(long, parse(from_os_str))]
+    area: Option<PathBuf>,
+
+    /// Cut the trips to their stops inside the bounding box or the area
+    /// (with extract) or out of it (with remove), instead of keeping or
+    /// removing them entirely
+    #[structopt(long)]
+    cut_trips: bool,
+
+    /// Remove the dates before this one (like 2019-01-31)
+    #[structopt(long, parse(try_from_str))]
+    start_date: Option<NaiveDate>,
+
+    /// Remove the dates after this one (like 2019-01-31)
+    #[structopt(long, parse(try_from_str))]
+    end_date: Option<NaiveDate>,
+
+    /// Keep only these days of the week (like mon, tue...)
+    #[structopt(long, parse(try_from_str = time_window::parse_weekday))]
+    weekdays: Vec<Weekday>,
+
+    /// Remove the trips departing before this time (like 08:00)
+    #[structopt(long, parse(try_from_str = time_window::parse_time))]
+    start_time: Option<Time>,
+
+    /// Remove the trips departing after this time (like 20:30)
+    #[structopt(long, parse(try_from_str = time_window::parse_time))]
+    end_time: Option<Time>,
+
+    /// Keep the stop points and stop areas not used anymore by any trip
+    #[structopt(long)]
+    keep_unused_stops: bool,
+
+    /// Accept a result without any trip (an NTFS without any object, apart
+    /// from the unused stops with --keep-unused-stops, is then written)
+    #[structopt(long)]
+    allow_empty: bool,
+
+    /// Write a JSON report of the removed objects to this file
+    #[structopt(long, parse(from_os_str))]
+    report: Option<PathBuf>,
+
     /// Current datetime
     #[structopt(
         short = "x",
@@ -89,9 +183,93 @@ fn run(opt: Opt) -> Result<()> {
     let mut filter = filter::Filter::new(opt.action.into());
     add_filters(&mut filter, filter::ObjectType::Network, opt.networks)?;
     add_filters(&mut filter, filter::ObjectType::Line, opt.lines)?;
+    add_filters(&mut filter, filter::ObjectType::StopArea, opt.stop_areas)?;
+    add_filters(
+        &mut filter,
+        filter::ObjectType::CommercialMode,
+        opt.commercial_modes,
+    )?;
+    add_filters(
+        &mut filter,
+        filter::ObjectType::PhysicalMode,
+        opt.physical_modes,
+    )?;
+    add_filters(&mut filter, filter::ObjectType::Company, opt.companies)?;
+    add_filters(
+        &mut filter,
+        filter::ObjectType::Contributor,
+        opt.contributors,
+    )?;
+    for path in opt.filter_config {
+        filter.add_expression(expression::Expression::from_path(path)?);
+    }
+    for code in opt.codes {
+        filter.add_expression(expression::Expression::Code(
+            code_filter::CodeFilter::parse(code_filter::Attribute::Code, &code)?,
+        ));
+    }
+    for object_property in opt.object_properties {
+        filter.add_expression(expression::Expression::Code(
+            code_filter::CodeFilter::parse(
+                code_filter::Attribute::ObjectProperty,
+                &object_property,
+            )?,
+        ));
+    }
+    let area = match (opt.bbox, opt.area) {
+        (Some(bbox), _) => Some(area::Area::from_bbox(&bbox)?),
+        (None, Some(path)) => Some(area::Area::from_geojson(path)?),
+        (None, None) => None,
+    };
+    match area {
+        Some(area) => filter.set_geographic_filter(area::GeographicFilter {
+            area,
+            cut_trips: opt.cut_trips,
+        }),
+        None if opt.cut_trips => bail!("--cut-trips needs a --bbox or an --area"),
+        None => {}
+    }
+    filter.set_time_window(time_window::TimeWindow {
+        start_date: opt.start_date,
+        end_date: opt.end_date,
+        weekdays: opt.weekdays.into_iter().collect(),
+        start_time: opt.start_time,
+        end_time: opt.end_time,
+    });
 
+    filter.set_allow_empty(opt.allow_empty);
+
+    let ids_before = report::collection_ids(&model);
+    let feed_infos = model.feed_infos.clone();
+    let mut unused_stops = if opt.keep_unused_stops {
+        Some(unused_stops::UnusedStops::new(&model))
+    } else {
+        None
+    };
     let model = filter::filter(model, &filter)?;
-    transit_model::ntfs::write(&model, opt.output, opt.current_datetime)?;
+
+    let mut ids_after = report::collection_ids(&model);
+    if let Some(unused_stops) = &mut unused_stops {
+        unused_stops.retain_unused(&model);
+        unused_stops.add_ids(&mut ids_after);
+    }
+    let removal_report = report::RemovalReport::new(&ids_before, &ids_after);
+    removal_report.log();
+
+    if model.vehicle_journeys.is_empty() {
+        warn!("The data does not contain vehicle journeys anymore, an empty NTFS is written.");
+        empty_ntfs::write(&opt.output, &feed_infos, opt.current_datetime)?;
+    } else {
+        transit_model::ntfs::write(&model, &opt.output, opt.current_datetime)?;
+    }
+    if let Some(unused_stops) = &unused_stops {
+        unused_stops.write(&opt.output)?;
+    }
+    if let Some(report_path) = opt.report {
+        let file = File::create(&report_path)
+            .map_err(|e| format_err!("Error writing {:?}: {}", report_path, e))?;
+        serde_json::to_writer_pretty(file, &removal_report)?;
+    }
 
     Ok(())
 }
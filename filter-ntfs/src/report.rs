@@ -0,0 +1,129 @@
+//! Report of the objects removed by the filtering, either filtered out or
+//! not used anymore.
+
+use log::info;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use transit_model::model::Collections;
+use typed_index_collection::{CollectionWithId, Id};
+
+pub type CollectionIds = BTreeMap<&'static str, BTreeSet<String>>;
+
+fn ids<T: Id<T>>(collection: &CollectionWithId<T>) -> BTreeSet<String> {
+    collection
+        .values()
+        .map(|object| object.id().to_string())
+        .collect()
+}
+
+/// The ids of the objects of the reported collections. The transfers, which
+/// have no id, are identified by their stops like `stop1->stop2`.
+pub fn collection_ids(collections: &Collections) -> CollectionIds {
+    let mut collection_ids = CollectionIds::new();
+    collection_ids.insert("calendars", ids(&collections.calendars));
+    collection_ids.insert("comments", ids(&collections.comments));
+    collection_ids.insert("commercial_modes", ids(&collections.commercial_modes));
+    collection_ids.insert("companies", ids(&collections.companies));
+    collection_ids.insert("contributors", ids(&collections.contributors));
+    collection_ids.insert("datasets", ids(&collections.datasets));
+    collection_ids.insert("equipments", ids(&collections.equipments));
+    collection_ids.insert("geometries", ids(&collections.geometries));
+    collection_ids.insert("lines", ids(&collections.lines));
+    collection_ids.insert("networks", ids(&collections.networks));
+    collection_ids.insert("physical_modes", ids(&collections.physical_modes));
+    collection_ids.insert("routes", ids(&collections.routes));
+    collection_ids.insert("stop_areas", ids(&collections.stop_areas));
+    collection_ids.insert("stop_points", ids(&collections.stop_points));
+    collection_ids.insert("ticket_uses", ids(&collections.ticket_uses));
+    collection_ids.insert("tickets", ids(&collections.tickets));
+    collection_ids.insert(
+        "transfers",
+        collections
+            .transfers
+            .values()
+            .map(|transfer| format!("{}->{}", transfer.from_stop_id, transfer.to_stop_id))
+            .collect(),
+    );
+    collection_ids.insert("vehicle_journeys", ids(&collections.vehicle_journeys));
+    collection_ids
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct RemovedObjects {
+    pub count: usize,
+    pub ids: BTreeSet<String>,
+}
+
+/// The removed objects of each collection with removed objects.
+#[derive(Debug, Default, Serialize)]
+pub struct RemovalReport {
+    removed: BTreeMap<&'static str, RemovedObjects>,
+}
+
+impl RemovalReport {
+    pub fn new(before: &CollectionIds, after: &CollectionIds) -> Self {
+        let removed = before
+            .iter()
+            .filter_map(|(&collection, ids_before)| {
+                let ids: BTreeSet<String> = match after.get(collection) {
+                    Some(ids_after) => ids_before.difference(ids_after).cloned().collect(),
+                    None => ids_before.clone(),
+                };
+                if ids.is_empty() {
+                    None
+                } else {
+                    Some((
+                        collection,
+                        RemovedObjects {
+                            count: ids.len(),
+                            ids,
+                        },
+                    ))
+                }
+            })
+            .collect();
+        RemovalReport { removed }
+    }
+
+    #[cfg(test)]
+    pub fn removed(&self, collection: &str) -> Option<&RemovedObjects> {
+        self.removed.get(collection)
+    }
+
+    pub fn log(&self) {
+        for (collection, removed_objects) in &self.removed {
+            info!("{} {} removed", removed_objects.count, collection);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collection_ids(v: &[(&'static str, &[&str])]) -> CollectionIds {
+        v.iter()
+            .map(|&(collection, ids)| (collection, ids.iter().map(|id| id.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn removed_objects() {
+        let before = collection_ids(&[
+            ("lines", &["line1", "line2"]),
+            ("routes", &["route1"]),
+            ("transfers", &["sp1->sp2"]),
+        ]);
+        let after = collection_ids(&[("lines", &["line1"]), ("routes", &["route1"])]);
+        let report = RemovalReport::new(&before, &after);
+        assert_eq!(
+            Some(&RemovedObjects {
+                count: 1,
+                ids: vec!["line2".to_string()].into_iter().collect(),
+            }),
+            report.removed("lines")
+        );
+        assert_eq!(None, report.removed("routes"));
+        assert_eq!(1, report.removed("transfers").unwrap().count);
+    }
+}
@@ -0,0 +1,209 @@
+//! Restriction of an NTFS to a period, to some days of the week or to a time
+//! of the day.
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use failure::format_err;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use transit_model::{model::Collections, objects::Time, Result};
+use typed_index_collection::CollectionWithId;
+
+#[derive(Debug, Default, Clone)]
+pub struct TimeWindow {
+    /// First date kept (included)
+    pub start_date: Option<NaiveDate>,
+    /// Last date kept (included)
+    pub end_date: Option<NaiveDate>,
+    /// Days of the week kept, all of them if empty
+    pub weekdays: HashSet<Weekday>,
+    /// The trips departing before this time are removed
+    pub start_time: Option<Time>,
+    /// The trips departing after this time are removed
+    pub end_time: Option<Time>,
+}
+
+impl TimeWindow {
+    pub fn is_empty(&self) -> bool {
+        self.start_date.is_none()
+            && self.end_date.is_none()
+            && self.weekdays.is_empty()
+            && self.start_time.is_none()
+            && self.end_time.is_none()
+    }
+
+    fn contains_date(&self, date: NaiveDate) -> bool {
+        self.start_date
+            .map_or(true, |start_date| date >= start_date)
+            && self.end_date.map_or(true, |end_date| date <= end_date)
+            && (self.weekdays.is_empty() || self.weekdays.contains(&date.weekday()))
+    }
+
+    fn contains_time(&self, time: Time) -> bool {
+        self.start_time
+            .map_or(true, |start_time| time >= start_time)
+            && self.end_time.map_or(true, |end_time| time <= end_time)
+    }
+}
+
+/// Parses a time of the day like `08:30` or `25:10:00` (after midnight for
+/// the trips of the previous day).
+pub fn parse_time(time: &str) -> Result<Time> {
+    let error = || format_err!("expected time should be \"HH:MM[:SS]\", \"{}\" given", time);
+    let parts = time
+        .split(':')
+        .map(|part| part.parse::<u32>().map_err(|_| error()))
+        .collect::<Result<Vec<_>>>()?;
+    match parts.as_slice() {
+        [hours, minutes] if *minutes < 60 => Ok(Time::new(*hours, *minutes, 0)),
+        [hours, minutes, seconds] if *minutes < 60 && *seconds < 60 => {
+            Ok(Time::new(*hours, *minutes, *seconds))
+        }
+        _ => Err(error()),
+    }
+}
+
+/// Parses a day of the week like `mon` or `monday`.
+pub fn parse_weekday(weekday: &str) -> Result<Weekday> {
+    weekday
+        .parse()
+        .map_err(|_| format_err!("unknown day of the week \"{}\"", weekday))
+}
+
+/// Removes the calendar dates out of the time window and the vehicle
+/// journeys not running anymore or departing out of the time window, and
+/// restricts the validity periods of the datasets.
+///
+/// A dataset whose period does not overlap the time window gets the period
+/// during which its vehicle journeys still run, or is removed if none of
+/// them runs anymore.
+pub fn restrict(collections: &mut Collections, time_window: &TimeWindow) -> Result<()> {
+    let mut calendars = collections.calendars.take();
+    for calendar in calendars.iter_mut() {
+        calendar
+            .dates
+            .retain(|&date| time_window.contains_date(date));
+    }
+    collections.calendars = CollectionWithId::new(calendars)?;
+
+    let calendars = &collections.calendars;
+    collections.vehicle_journeys.retain(|vehicle_journey| {
+        let is_running = calendars
+            .get(&vehicle_journey.service_id)
+            .map_or(false, |calendar| !calendar.dates.is_empty());
+        let departs_in_window = vehicle_journey
+            .stop_times
+            .first()
+            .map_or(false, |stop_time| {
+                time_window.contains_time(stop_time.departure_time)
+            });
+        is_running && departs_in_window
+    });
+
+    // First and last dates the vehicle journeys of each dataset still run
+    let mut running_periods: HashMap<&str, (NaiveDate, NaiveDate)> = HashMap::new();
+    for vehicle_journey in collections.vehicle_journeys.values() {
+        let dates = match collections.calendars.get(&vehicle_journey.service_id) {
+            Some(calendar) => &calendar.dates,
+            None => continue,
+        };
+        if let (Some(&first), Some(&last)) = (dates.iter().next(), dates.iter().next_back()) {
+            let period = running_periods
+                .entry(&vehicle_journey.dataset_id)
+                .or_insert((first, last));
+            *period = (cmp::min(period.0, first), cmp::max(period.1, last));
+        }
+    }
+    let datasets = collections
+        .datasets
+        .take()
+        .into_iter()
+        .filter_map(|mut dataset| {
+            if let Some(start_date) = time_window.start_date {
+                dataset.start_date = cmp::max(dataset.start_date, start_date);
+            }
+            if let Some(end_date) = time_window.end_date {
+                dataset.end_date = cmp::min(dataset.end_date, end_date);
+            }
+            if dataset.start_date > dataset.end_date {
+                let &(first, last) = running_periods.get(dataset.id.as_str())?;
+                dataset.start_date = first;
+                dataset.end_date = last;
+            }
+            Some(dataset)
+        })
+        .collect();
+    collections.datasets = CollectionWithId::new(datasets)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_times() {
+        assert_eq!(Time::new(8, 30, 0), parse_time("08:30").unwrap());
+        assert_eq!(Time::new(25, 10, 5), parse_time("25:10:05").unwrap());
+        assert!(parse_time("8h30").is_err());
+        assert!(parse_time("08:75").is_err());
+    }
+
+    #[test]
+    fn parse_weekdays() {
+        assert_eq!(Weekday::Mon, parse_weekday("mon").unwrap());
+        assert_eq!(Weekday::Sun, parse_weekday("Sunday").unwrap());
+        assert!(parse_weekday("lundi").is_err());
+    }
+
+    #[test]
+    fn dates_in_window() {
+        let time_window = TimeWindow {
+            start_date: Some(NaiveDate::from_ymd(2019, 1, 2)),
+            weekdays: vec![Weekday::Wed, Weekday::Thu].into_iter().collect(),
+            ..Default::default()
+        };
+        // Tuesday
+        assert!(!time_window.contains_date(NaiveDate::from_ymd(2019, 1, 1)));
+        // Wednesday
+        assert!(time_window.contains_date(NaiveDate::from_ymd(2019, 1, 2)));
+        // Friday
+        assert!(!time_window.contains_date(NaiveDate::from_ymd(2019, 1, 4)));
+    }
+
+    fn input_collections() -> Collections {
+        transit_model::ntfs::read("tests/fixtures/input_filters")
+            .unwrap()
+            .into_collections()
+    }
+
+    #[test]
+    fn window_out_of_dataset_period() {
+        let mut collections = input_collections();
+        // The trips of dataset1 run after the end of its period
+        collections.datasets.get_mut("dataset1").unwrap().end_date =
+            NaiveDate::from_ymd(2019, 1, 4);
+        let time_window = TimeWindow {
+            start_date: Some(NaiveDate::from_ymd(2019, 1, 7)),
+            end_date: Some(NaiveDate::from_ymd(2019, 1, 11)),
+            ..Default::default()
+        };
+        restrict(&mut collections, &time_window).unwrap();
+        for dataset in collections.datasets.values() {
+            assert!(dataset.start_date <= dataset.end_date);
+        }
+        // The dates trip1 still runs
+        let dataset = collections.datasets.get("dataset1").unwrap();
+        assert_eq!(NaiveDate::from_ymd(2019, 1, 7), dataset.start_date);
+        assert_eq!(NaiveDate::from_ymd(2019, 1, 11), dataset.end_date);
+
+        // No trip runs after the periods of the datasets
+        let mut collections = input_collections();
+        let time_window = TimeWindow {
+            start_date: Some(NaiveDate::from_ymd(2019, 3, 1)),
+            ..Default::default()
+        };
+        restrict(&mut collections, &time_window).unwrap();
+        assert!(collections.vehicle_journeys.is_empty());
+        assert!(collections.datasets.is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+//! The stop points and stop areas not used anymore after the filtering.
+//! `Model::new` removes them, so they are appended to the written NTFS with
+//! their codes, object properties and comments, and with the geometries,
+//! equipments and levels they reference.
+
+use crate::report::CollectionIds;
+use failure::ResultExt;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs::OpenOptions;
+use std::path::Path;
+use transit_model::{
+    model::Collections,
+    objects::{
+        Comment, CommentLinksT, CommentType, Equipment, Geometry, KeysValues, Level, ObjectType,
+        StopArea, StopPoint, StopType,
+    },
+    Model, Result,
+};
+use typed_index_collection::{CollectionWithId, Id};
+
+// The records below are serialized like the ones written by
+// `transit_model::ntfs::write`, with the same columns in the same order
+
+#[derive(Serialize)]
+struct Stop<'a> {
+    stop_id: &'a str,
+    stop_name: &'a str,
+    stop_code: Option<&'a str>,
+    visible: u8,
+    fare_zone_id: Option<&'a str>,
+    stop_lon: f64,
+    stop_lat: f64,
+    location_type: u8,
+    parent_station: Option<&'a str>,
+    stop_timezone: Option<String>,
+    geometry_id: Option<&'a str>,
+    equipment_id: Option<&'a str>,
+    level_id: Option<&'a str>,
+    platform_code: Option<&'a str>,
+}
+
+/// The columns of `stops.txt`.
+pub const STOP_COLUMNS: &[&str] = &[
+    "stop_id",
+    "stop_name",
+    "stop_code",
+    "visible",
+    "fare_zone_id",
+    "stop_lon",
+    "stop_lat",
+    "location_type",
+    "parent_station",
+    "stop_timezone",
+    "geometry_id",
+    "equipment_id",
+    "level_id",
+    "platform_code",
+];
+
+#[derive(Serialize)]
+struct Code<'a> {
+    object_type: ObjectType,
+    object_id: &'a str,
+    object_system: &'a str,
+    object_code: &'a str,
+}
+
+#[derive(Serialize)]
+struct ObjectProperty<'a> {
+    object_type: ObjectType,
+    object_id: &'a str,
+    object_property_name: &'a str,
+    object_property_value: &'a str,
+}
+
+#[derive(Serialize)]
+struct CommentLink<'a> {
+    object_id: &'a str,
+    object_type: ObjectType,
+    comment_id: &'a str,
+}
+
+#[derive(Debug, Default)]
+pub struct UnusedStops {
+    stop_areas: Vec<StopArea>,
+    stop_points: Vec<StopPoint>,
+    comments: Vec<Comment>,
+    geometries: Vec<Geometry>,
+    equipments: Vec<Equipment>,
+    levels: Vec<Level>,
+}
+
+// The objects of `collection` whose id is in `ids`
+fn referenced<T: Id<T>>(
+    collection: &CollectionWithId<T>,
+    ids: &BTreeSet<&str>,
+    clone: impl Fn(&T) -> T,
+) -> Vec<T> {
+    ids.iter()
+        .filter_map(|id| collection.get(id))
+        .map(clone)
+        .collect()
+}
+
+// `Comment` and `Geometry` do not implement `Clone`
+fn clone_comment(comment: &Comment) -> Comment {
+    Comment {
+        id: comment.id.clone(),
+        comment_type: match comment.comment_type {
+            CommentType::Information => CommentType::Information,
+            CommentType::OnDemandTransport => CommentType::OnDemandTransport,
+        },
+        label: comment.label.clone(),
+        name: comment.name.clone(),
+        url: comment.url.clone(),
+    }
+}
+
+fn clone_geometry(geometry: &Geometry) -> Geometry {
+    Geometry {
+        id: geometry.id.clone(),
+        geometry: geometry.geometry.clone(),
+    }
+}
+
+// Appends the records to `file` in `path`, writing the header first if the
+// file does not exist yet or is empty (like `comment_links.txt` without any
+// link)
+fn append<T: Serialize>(
+    path: &Path,
+    file: &str,
+    records: impl IntoIterator<Item = T>,
+) -> Result<()> {
+    let mut records = records.into_iter().peekable();
+    if records.peek().is_none() {
+        return Ok(());
+    }
+    let path = path.join(file);
+    let has_headers = path.metadata().map_or(true, |metadata| metadata.len() == 0);
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(has_headers)
+        .from_writer(file);
+    for record in records {
+        writer
+            .serialize(record)
+            .with_context(|_| format!("Error writing {:?}", path))?;
+    }
+    writer
+        .flush()
+        .with_context(|_| format!("Error writing {:?}", path))?;
+    Ok(())
+}
+
+// An unused stop, with the attributes written in other files than stops.txt
+struct StopAttributes<'a> {
+    object_type: ObjectType,
+    id: &'a str,
+    codes: &'a KeysValues,
+    object_properties: &'a KeysValues,
+    comment_links: &'a CommentLinksT,
+}
+
+// The comments, geometries, equipments and levels referenced by the stops
+#[derive(Default)]
+struct ReferencedIds<'a> {
+    comments: BTreeSet<&'a str>,
+    geometries: BTreeSet<&'a str>,
+    equipments: BTreeSet<&'a str>,
+    levels: BTreeSet<&'a str>,
+}
+
+impl<'a> ReferencedIds<'a> {
+    fn new(
+        stop_areas: impl Iterator<Item = &'a StopArea>,
+        stop_points: impl Iterator<Item = &'a StopPoint>,
+    ) -> Self {
+        let mut ids = ReferencedIds::default();
+        for stop_area in stop_areas {
+            ids.comments
+                .extend(stop_area.comment_links.iter().map(String::as_str));
+            ids.geometries.extend(stop_area.geometry_id.as_deref());
+            ids.equipments.extend(stop_area.equipment_id.as_deref());
+            ids.levels.extend(stop_area.level_id.as_deref());
+        }
+        for stop_point in stop_points {
+            ids.comments
+                .extend(stop_point.comment_links.iter().map(String::as_str));
+            ids.geometries.extend(stop_point.geometry_id.as_deref());
+            ids.equipments.extend(stop_point.equipment_id.as_deref());
+            ids.levels.extend(stop_point.level_id.as_deref());
+        }
+        ids
+    }
+}
+
+impl UnusedStops {
+    /// The stop areas and stop points of `collections`, with the objects
+    /// they reference.
+    pub fn new(collections: &Collections) -> Self {
+        let ids = ReferencedIds::new(
+            collections.stop_areas.values(),
+            collections.stop_points.values(),
+        );
+        UnusedStops {
+            stop_areas: collections.stop_areas.values().cloned().collect(),
+            stop_points: collections.stop_points.values().cloned().collect(),
+            comments: referenced(&collections.comments, &ids.comments, clone_comment),
+            geometries: referenced(&collections.geometries, &ids.geometries, clone_geometry),
+            equipments: referenced(&collections.equipments, &ids.equipments, Equipment::clone),
+            levels: referenced(&collections.levels, &ids.levels, Level::clone),
+        }
+    }
+
+    /// Keeps only the stops missing from `model`, and the objects they
+    /// reference also missing from `model`.
+    pub fn retain_unused(&mut self, model: &Model) {
+        self.stop_areas
+            .retain(|stop_area| !model.stop_areas.contains_id(&stop_area.id));
+        self.stop_points
+            .retain(|stop_point| !model.stop_points.contains_id(&stop_point.id));
+        let ids = ReferencedIds::new(self.stop_areas.iter(), self.stop_points.iter());
+        self.comments.retain(|comment| {
+            ids.comments.contains(comment.id.as_str()) && !model.comments.contains_id(&comment.id)
+        });
+        self.geometries.retain(|geometry| {
+            ids.geometries.contains(geometry.id.as_str())
+                && !model.geometries.contains_id(&geometry.id)
+        });
+        self.equipments.retain(|equipment| {
+            ids.equipments.contains(equipment.id.as_str())
+                && !model.equipments.contains_id(&equipment.id)
+        });
+        self.levels.retain(|level| {
+            ids.levels.contains(level.id.as_str()) && !model.levels.contains_id(&level.id)
+        });
+    }
+
+    /// Adds the ids of the unused stops, and of the objects they reference,
+    /// to `collection_ids`.
+    pub fn add_ids(&self, collection_ids: &mut CollectionIds) {
+        fn add<'a, T: Id<T> + 'a>(
+            collection_ids: &mut CollectionIds,
+            collection: &'static str,
+            objects: impl IntoIterator<Item = &'a T>,
+        ) {
+            collection_ids
+                .entry(collection)
+                .or_default()
+                .extend(objects.into_iter().map(|object| object.id().to_string()));
+        }
+        add(collection_ids, "stop_areas", &self.stop_areas);
+        add(collection_ids, "stop_points", &self.stop_points);
+        add(collection_ids, "comments", &self.comments);
+        add(collection_ids, "geometries", &self.geometries);
+        add(collection_ids, "equipments", &self.equipments);
+        add(collection_ids, "levels", &self.levels);
+    }
+
+    fn stops(&self) -> impl Iterator<Item = Stop<'_>> {
+        let stop_areas = self.stop_areas.iter().map(|stop_area| Stop {
+            stop_id: &stop_area.id,
+            stop_name: &stop_area.name,
+            stop_code: None,
+            visible: stop_area.visible as u8,
+            fare_zone_id: None,
+            stop_lon: stop_area.coord.lon,
+            stop_lat: stop_area.coord.lat,
+            location_type: 1,
+            parent_station: None,
+            stop_timezone: stop_area.timezone.map(|timezone| timezone.to_string()),
+            geometry_id: stop_area.geometry_id.as_deref(),
+            equipment_id: stop_area.equipment_id.as_deref(),
+            level_id: stop_area.level_id.as_deref(),
+            platform_code: None,
+        });
+        let stop_points = self.stop_points.iter().map(|stop_point| Stop {
+            stop_id: &stop_point.id,
+            stop_name: &stop_point.name,
+            stop_code: stop_point.code.as_deref(),
+            visible: stop_point.visible as u8,
+            fare_zone_id: stop_point.fare_zone_id.as_deref(),
+            stop_lon: stop_point.coord.lon,
+            stop_lat: stop_point.coord.lat,
+            location_type: if stop_point.stop_type == StopType::Zone {
+                2
+            } else {
+                0
+            },
+            parent_station: Some(&stop_point.stop_area_id),
+            stop_timezone: stop_point.timezone.map(|timezone| timezone.to_string()),
+            geometry_id: stop_point.geometry_id.as_deref(),
+            equipment_id: stop_point.equipment_id.as_deref(),
+            level_id: stop_point.level_id.as_deref(),
+            platform_code: stop_point.platform_code.as_deref(),
+        });
+        stop_areas.chain(stop_points)
+    }
+
+    fn stop_attributes(&self) -> Vec<StopAttributes<'_>> {
+        let stop_areas = self.stop_areas.iter().map(|stop_area| StopAttributes {
+            object_type: ObjectType::StopArea,
+            id: &stop_area.id,
+            codes: &stop_area.codes,
+            object_properties: &stop_area.object_properties,
+            comment_links: &stop_area.comment_links,
+        });
+        let stop_points = self.stop_points.iter().map(|stop_point| StopAttributes {
+            object_type: ObjectType::StopPoint,
+            id: &stop_point.id,
+            codes: &stop_point.codes,
+            object_properties: &stop_point.object_properties,
+            comment_links: &stop_point.comment_links,
+        });
+        stop_areas.chain(stop_points).collect()
+    }
+
+    /// Appends the unused stops to the NTFS in `path`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        append(path, "stops.txt", self.stops())?;
+        let stop_attributes = self.stop_attributes();
+        append(
+            path,
+            "object_codes.txt",
+            stop_attributes.iter().flat_map(|stop| {
+                stop.codes.iter().map(move |(system, code)| Code {
+                    object_type: stop.object_type.clone(),
+                    object_id: stop.id,
+                    object_system: system,
+                    object_code: code,
+                })
+            }),
+        )?;
+        append(
+            path,
+            "object_properties.txt",
+            stop_attributes.iter().flat_map(|stop| {
+                stop.object_properties
+                    .iter()
+                    .map(move |(name, value)| ObjectProperty {
+                        object_type: stop.object_type.clone(),
+                        object_id: stop.id,
+                        object_property_name: name,
+                        object_property_value: value,
+                    })
+            }),
+        )?;
+        append(path, "comments.txt", &self.comments)?;
+        append(
+            path,
+            "comment_links.txt",
+            stop_attributes.iter().flat_map(|stop| {
+                stop.comment_links
+                    .iter()
+                    .map(move |comment_id| CommentLink {
+                        object_id: stop.id,
+                        object_type: stop.object_type.clone(),
+                        comment_id,
+                    })
+            }),
+        )?;
+        append(path, "geometries.txt", &self.geometries)?;
+        append(path, "equipments.txt", &self.equipments)?;
+        append(path, "levels.txt", &self.levels)?;
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use assert_cmd::prelude::*;
 use std::process::Command;
 use tempfile::TempDir;
 use transit_model::test_utils::*;
+use transit_model::Model;
 
 #[test]
 fn test_extract_network() {
@@ -140,3 +141,411 @@ fn test_extract_multiple_line_by_line_code() {
         "tests/fixtures/output_extract_multiple_lines",
     );
 }
+
+fn filtered_model(args: &[&str]) -> Model {
+    let output_dir = TempDir::new().expect("create temp dir failed");
+    Command::cargo_bin("filter-ntfs")
+        .expect("Failed to find binary 'filter-ntfs'")
+        .arg("--input")
+        .arg("tests/fixtures/input_filters/")
+        .arg("--output")
+        .arg(output_dir.path().to_str().unwrap())
+        .args(args)
+        .assert()
+        .success();
+    transit_model::ntfs::read(output_dir.path()).unwrap()
+}
+
+fn filtered_trips(args: &[&str]) -> Vec<String> {
+    let mut trips: Vec<String> = filtered_model(args)
+        .vehicle_journeys
+        .values()
+        .map(|vehicle_journey| vehicle_journey.id.clone())
+        .collect();
+    trips.sort();
+    trips
+}
+
+#[test]
+fn test_extract_stop_area() {
+    assert_eq!(
+        vec!["trip1", "trip3"],
+        filtered_trips(&["extract", "--stop-areas", "stop_area_id:sa1"])
+    );
+}
+
+#[test]
+fn test_extract_commercial_mode() {
+    assert_eq!(
+        vec!["trip2"],
+        filtered_trips(&["extract", "--commercial-modes", "commercial_mode_id:Train"])
+    );
+}
+
+#[test]
+fn test_remove_physical_mode() {
+    assert_eq!(
+        vec!["trip2"],
+        filtered_trips(&["remove", "--physical-modes", "physical_mode_id:Bus"])
+    );
+}
+
+#[test]
+fn test_remove_company() {
+    assert_eq!(
+        vec!["trip1"],
+        filtered_trips(&["remove", "--companies", "company_id:company2"])
+    );
+}
+
+#[test]
+fn test_extract_contributor() {
+    assert_eq!(
+        vec!["trip2"],
+        filtered_trips(&["extract", "--contributors", "contributor_id:contributor2"])
+    );
+}
+
+#[test]
+fn test_extract_with_unknown_company() {
+    let output_dir = TempDir::new().expect("create temp dir failed");
+    Command::cargo_bin("filter-ntfs")
+        .expect("Failed to find binary 'filter-ntfs'")
+        .arg("--input")
+        .arg("tests/fixtures/input_filters/")
+        .arg("--output")
+        .arg(output_dir.path().to_str().unwrap())
+        .arg("extract")
+        .arg("--companies")
+        .arg("company_id:unknown")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Company \'unknown\' not found"));
+}
+
+#[test]
+fn test_extract_date_range() {
+    let model = filtered_model(&[
+        "extract",
+        "--start-date",
+        "2019-01-07",
+        "--end-date",
+        "2019-01-11",
+    ]);
+    let trips: Vec<_> = model.vehicle_journeys.values().map(|vj| &vj.id).collect();
+    assert_eq!(vec!["trip1"], trips);
+    let dates: Vec<_> = model
+        .calendars
+        .get("service1")
+        .unwrap()
+        .dates
+        .iter()
+        .map(|date| date.to_string())
+        .collect();
+    assert_eq!(
+        vec![
+            "2019-01-07",
+            "2019-01-08",
+            "2019-01-09",
+            "2019-01-10",
+            "2019-01-11"
+        ],
+        dates
+    );
+    let dataset = model.datasets.get("dataset1").unwrap();
+    assert_eq!("2019-01-07", dataset.start_date.to_string());
+    assert_eq!("2019-01-11", dataset.end_date.to_string());
+}
+
+#[test]
+fn test_extract_weekdays() {
+    assert_eq!(
+        vec!["trip2", "trip3"],
+        filtered_trips(&["extract", "--weekdays", "sat", "--weekdays", "sun"])
+    );
+}
+
+#[test]
+fn test_extract_time_of_day() {
+    assert_eq!(
+        vec!["trip2"],
+        filtered_trips(&["extract", "--start-time", "10:00", "--end-time", "15:00"])
+    );
+}
+
+#[test]
+fn test_filter_and_time_window() {
+    assert_eq!(
+        vec!["trip3"],
+        filtered_trips(&[
+            "extract",
+            "--physical-modes",
+            "physical_mode_id:Bus",
+            "--weekdays",
+            "sun",
+        ])
+    );
+}
+
+#[test]
+fn test_extract_bbox() {
+    assert_eq!(
+        vec!["trip1", "trip3"],
+        filtered_trips(&["extract", "--bbox", "2.30,48.80,2.355,48.855"])
+    );
+}
+
+#[test]
+fn test_extract_bbox_cut_trips() {
+    let model = filtered_model(&["extract", "--bbox", "2.30,48.80,2.40,48.90", "--cut-trips"]);
+    let mut trips: Vec<_> = model.vehicle_journeys.values().map(|vj| &vj.id).collect();
+    trips.sort();
+    // trip3 only has 1 stop in the bounding box
+    assert_eq!(vec!["trip1", "trip2"], trips);
+    let trip2 = model.vehicle_journeys.get("trip2").unwrap();
+    let stops: Vec<_> = trip2
+        .stop_times
+        .iter()
+        .map(|stop_time| &model.stop_points[stop_time.stop_point_idx].id)
+        .collect();
+    assert_eq!(vec!["sp2", "sp4"], stops);
+    // The stops out of the bounding box are not used anymore
+    assert!(!model.stop_points.contains_id("sp3"));
+    assert!(!model.stop_areas.contains_id("sa3"));
+}
+
+#[test]
+fn test_remove_area() {
+    assert_eq!(
+        vec!["trip1"],
+        filtered_trips(&["remove", "--area", "tests/fixtures/area.geojson"])
+    );
+}
+
+#[test]
+fn test_remove_area_cut_trips() {
+    let model = filtered_model(&[
+        "remove",
+        "--area",
+        "tests/fixtures/area.geojson",
+        "--cut-trips",
+    ]);
+    let mut trips: Vec<_> = model.vehicle_journeys.values().map(|vj| &vj.id).collect();
+    trips.sort();
+    assert_eq!(vec!["trip1", "trip2"], trips);
+    assert_eq!(
+        2,
+        model
+            .vehicle_journeys
+            .get("trip2")
+            .unwrap()
+            .stop_times
+            .len()
+    );
+}
+
+#[test]
+fn test_extract_filter_config() {
+    assert_eq!(
+        vec!["trip1"],
+        filtered_trips(&[
+            "extract",
+            "--filter-config",
+            "tests/fixtures/filter_configs/network_but_not_company.json",
+        ])
+    );
+}
+
+#[test]
+fn test_remove_filter_config() {
+    assert_eq!(
+        vec!["trip1"],
+        filtered_trips(&[
+            "remove",
+            "--filter-config",
+            "tests/fixtures/filter_configs/trains_or_line_of_company.json",
+        ])
+    );
+}
+
+#[test]
+fn test_extract_filter_config_with_codes() {
+    assert_eq!(
+        vec!["trip1", "trip2"],
+        filtered_trips(&[
+            "extract",
+            "--filter-config",
+            "tests/fixtures/filter_configs/night_or_code_but_not_company.json",
+        ])
+    );
+}
+
+#[test]
+fn test_removal_report() {
+    let output_dir = TempDir::new().expect("create temp dir failed");
+    let report_dir = TempDir::new().expect("create temp dir failed");
+    let report_path = report_dir.path().join("report.json");
+    Command::cargo_bin("filter-ntfs")
+        .expect("Failed to find binary 'filter-ntfs'")
+        .arg("--input")
+        .arg("tests/fixtures/input_filters/")
+        .arg("--output")
+        .arg(output_dir.path())
+        .arg("extract")
+        .arg("--stop-areas")
+        .arg("stop_area_id:sa1")
+        .arg("--report")
+        .arg(&report_path)
+        .assert()
+        .success();
+    let report: serde_json::Value =
+        serde_json::from_reader(std::fs::File::open(report_path).unwrap()).unwrap();
+    let removed = &report["removed"];
+    assert_eq!(serde_json::json!(["line2"]), removed["lines"]["ids"]);
+    assert_eq!(serde_json::json!(["sp4"]), removed["stop_points"]["ids"]);
+    assert_eq!(serde_json::json!(["sa4"]), removed["stop_areas"]["ids"]);
+    assert_eq!(serde_json::json!(["comment1"]), removed["comments"]["ids"]);
+    assert_eq!(serde_json::json!(1), removed["vehicle_journeys"]["count"]);
+    assert_eq!(
+        serde_json::json!(["trip2"]),
+        removed["vehicle_journeys"]["ids"]
+    );
+}
+
+// The sorted values of `column` in `file` of the NTFS in `path`
+fn column_values(path: &std::path::Path, file: &str, column: &str) -> Vec<String> {
+    let mut reader = csv::Reader::from_path(path.join(file)).unwrap();
+    let headers = reader.headers().unwrap().clone();
+    let position = headers.iter().position(|h| h == column).unwrap();
+    let mut values: Vec<String> = reader
+        .records()
+        .map(|record| record.unwrap()[position].to_string())
+        .collect();
+    values.sort();
+    values
+}
+
+#[test]
+fn test_keep_unused_stops() {
+    let output_dir = TempDir::new().expect("create temp dir failed");
+    Command::cargo_bin("filter-ntfs")
+        .expect("Failed to find binary 'filter-ntfs'")
+        .arg("--input")
+        .arg("tests/fixtures/input_filters/")
+        .arg("--output")
+        .arg(output_dir.path())
+        .arg("extract")
+        .arg("--stop-areas")
+        .arg("stop_area_id:sa1")
+        .arg("--keep-unused-stops")
+        .assert()
+        .success();
+    assert_eq!(
+        vec!["sa1", "sa2", "sa3", "sa4", "sp1", "sp2", "sp3", "sp4"],
+        column_values(output_dir.path(), "stops.txt", "stop_id")
+    );
+    // sp4 is not served anymore, its comment is kept with it
+    assert_eq!(
+        vec!["comment1"],
+        column_values(output_dir.path(), "comments.txt", "comment_id")
+    );
+    assert_eq!(
+        vec!["sp4"],
+        column_values(output_dir.path(), "comment_links.txt", "object_id")
+    );
+}
+
+#[test]
+fn test_allow_empty() {
+    let output_dir = TempDir::new().expect("create temp dir failed");
+    let args = [
+        "remove",
+        "--networks",
+        "network_id:network1",
+        "--networks",
+        "network_id:network2",
+    ];
+    Command::cargo_bin("filter-ntfs")
+        .expect("Failed to find binary 'filter-ntfs'")
+        .arg("--input")
+        .arg("tests/fixtures/input_filters/")
+        .arg("--output")
+        .arg(output_dir.path())
+        .args(args)
+        .assert()
+        .failure();
+    Command::cargo_bin("filter-ntfs")
+        .expect("Failed to find binary 'filter-ntfs'")
+        .arg("--input")
+        .arg("tests/fixtures/input_filters/")
+        .arg("--output")
+        .arg(output_dir.path())
+        .args(args)
+        .arg("--allow-empty")
+        .assert()
+        .success();
+    let model = transit_model::ntfs::read(output_dir.path()).unwrap();
+    assert!(model.vehicle_journeys.is_empty());
+    assert!(model.stop_points.is_empty());
+}
+
+#[test]
+fn test_allow_empty_keep_unused_stops() {
+    let output_dir = TempDir::new().expect("create temp dir failed");
+    Command::cargo_bin("filter-ntfs")
+        .expect("Failed to find binary 'filter-ntfs'")
+        .arg("--input")
+        .arg("tests/fixtures/input_filters/")
+        .arg("--output")
+        .arg(output_dir.path())
+        .arg("remove")
+        .arg("--networks")
+        .arg("network_id:network1")
+        .arg("--networks")
+        .arg("network_id:network2")
+        .arg("--allow-empty")
+        .arg("--keep-unused-stops")
+        .assert()
+        .success();
+    assert!(column_values(output_dir.path(), "trips.txt", "trip_id").is_empty());
+    assert_eq!(
+        vec!["sa1", "sa2", "sa3", "sa4", "sp1", "sp2", "sp3", "sp4"],
+        column_values(output_dir.path(), "stops.txt", "stop_id")
+    );
+    assert_eq!(
+        vec!["sp3"],
+        column_values(output_dir.path(), "object_codes.txt", "object_id")
+    );
+}
+
+#[test]
+fn test_extract_code_wildcard() {
+    assert_eq!(
+        vec!["trip1", "trip3"],
+        filtered_trips(&["extract", "--codes", "line:external_code:X*"])
+    );
+}
+
+#[test]
+fn test_extract_code_any_value() {
+    assert_eq!(
+        vec!["trip2"],
+        filtered_trips(&["extract", "--codes", "route:osm_route_id"])
+    );
+}
+
+#[test]
+fn test_extract_stop_point_code() {
+    assert_eq!(
+        vec!["trip2", "trip3"],
+        filtered_trips(&["extract", "--codes", "stop_point:source:gtfs_*"])
+    );
+}
+
+#[test]
+fn test_remove_object_property_regex() {
+    assert_eq!(
+        vec!["trip1", "trip3"],
+        filtered_trips(&["remove", "--object-properties", "line:night:/^y/"])
+    );
+}
@@ -14,59 +14,409 @@
 // along with this program.  If not, see
 // <http://www.gnu.org/licenses/>.
 
-use crate::Result;
-use crate::{Coord, Model, Poi, PoiType, Property};
-use failure::format_err;
+use crate::{Address, Coord, Model, Poi, PoiType, Property};
+use crate::{Error, Result};
+use failure::{format_err, ResultExt};
 use itertools::Itertools;
 use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::fs::File;
-use std::path::Path;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+
+/// A CSV file buffered next to the output while POIs are streamed, and
+/// copied into the archive at the end. The file is removed when dropped,
+/// including when the writing fails.
+struct BufferedCsv {
+    writer: csv::Writer<File>,
+    path: PathBuf,
+    is_empty: bool,
+}
+
+impl BufferedCsv {
+    fn new(path: PathBuf) -> Result<BufferedCsv> {
+        Ok(BufferedCsv {
+            writer: csv_writer(File::create(&path)?),
+            path,
+            is_empty: true,
+        })
+    }
+
+    fn serialize<T: Serialize>(&mut self, record: T) -> Result<()> {
+        self.writer.serialize(record)?;
+        self.is_empty = false;
+        Ok(())
+    }
+
+    /// Copies the buffered records into the archive, under `name`.
+    fn copy_into(
+        mut self,
+        zip: &mut zip::ZipWriter<File>,
+        name: &str,
+        write_if_empty: bool,
+    ) -> Result<()> {
+        self.writer
+            .flush()
+            .map_err(|e| format_err!("cannot write {}: {}", name, e))?;
+        if write_if_empty || !self.is_empty {
+            zip.start_file(name, zip::write::FileOptions::default())?;
+            std::io::copy(&mut File::open(&self.path)?, zip)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BufferedCsv {
+    fn drop(&mut self) {
+        // Nothing more can be done if the temporary file cannot be removed
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Writes a `.poi` archive while POIs are produced, without holding
+/// them all in memory.
+///
+/// POIs are streamed to `poi.txt`, their properties, translated names and
+/// addresses are buffered in temporary files next to the output, and
+/// everything else is written when calling [PoiWriter::finish]. The POIs
+/// should be written sorted by id to be read back by [PoiReader].
+pub struct PoiWriter {
+    pois: csv::Writer<zip::ZipWriter<File>>,
+    properties: BufferedCsv,
+    translations: BufferedCsv,
+    addresses: BufferedCsv,
+    poi_types: BTreeMap<String, PoiType>,
+}
+
+impl std::fmt::Debug for PoiWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("PoiWriter")
+            .field("properties_path", &self.properties.path)
+            .field("translations_path", &self.translations.path)
+            .field("addresses_path", &self.addresses.path)
+            .field("poi_types", &self.poi_types)
+            .finish()
+    }
+}
+
+impl PoiWriter {
+    /// Creates a writer of a `.poi` archive. The extension of `path` is
+    /// replaced by `poi`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<PoiWriter> {
+        let out = path.as_ref().with_extension("poi");
+        let file = File::create(&out)?;
+        let mut zip = zip::ZipWriter::new(file);
+        zip.start_file("poi.txt", zip::write::FileOptions::default())?;
+        Ok(PoiWriter {
+            pois: csv_writer(zip),
+            properties: BufferedCsv::new(out.with_extension("poi_properties.tmp"))?,
+            translations: BufferedCsv::new(out.with_extension("poi_translations.tmp"))?,
+            addresses: BufferedCsv::new(out.with_extension("poi_addresses.tmp"))?,
+            poi_types: BTreeMap::new(),
+        })
+    }
+
+    /// Adds a POI type, ignored if a POI type with the same id was already
+    /// added.
+    pub fn add_poi_type(&mut self, poi_type: PoiType) {
+        self.poi_types
+            .entry(poi_type.id.clone())
+            .or_insert(poi_type);
+    }
+
+    /// Writes a POI, its properties (sorted by key), its translated names
+    /// and its address.
+    pub fn write_poi(&mut self, poi: &Poi) -> Result<()> {
+        self.pois.serialize(PoiRecord::from(poi))?;
+        for prop in poi
+            .properties
+            .iter()
+            .sorted_by(|lhs, rhs| Ord::cmp(&lhs.key, &rhs.key))
+        {
+            self.properties.serialize(PoiProperty {
+                poi_id: poi.id.clone(),
+                key: prop.key.clone(),
+                value: prop.value.clone(),
+            })?;
+        }
+        for (lang, name) in &poi.translated_names {
+            self.translations.serialize(PoiTranslation {
+                poi_id: poi.id.clone(),
+                lang: lang.clone(),
+                name: name.clone(),
+            })?;
+        }
+        if let Some(address) = &poi.address {
+            self.addresses.serialize(PoiAddress {
+                poi_id: poi.id.clone(),
+                house_number: address.house_number.clone(),
+                street: address.street.clone(),
+                postcode: address.postcode.clone(),
+                city: address.city.clone(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Writes the POI types and the buffered properties, translated names
+    /// and addresses, and closes the archive.
+    pub fn finish(self) -> Result<()> {
+        let PoiWriter {
+            pois,
+            properties,
+            translations,
+            addresses,
+            poi_types,
+        } = self;
+        let mut zip = pois
+            .into_inner()
+            .map_err(|e| format_err!("cannot write poi.txt: {}", e))?;
+
+        zip.start_file("poi_type.txt", zip::write::FileOptions::default())?;
+        write_csv(
+            &mut zip,
+            poi_types.values().cloned().map(PoiTypeRecord::from),
+        )?;
+
+        properties.copy_into(&mut zip, "poi_properties.txt", true)?;
+        // poi_translations.txt and poi_addresses.txt are extensions of the
+        // format, only written when needed
+        translations.copy_into(&mut zip, "poi_translations.txt", false)?;
+        addresses.copy_into(&mut zip, "poi_addresses.txt", false)?;
+
+        zip.finish()?;
+        Ok(())
+    }
+}
 
 /// Saves the model to a file, in CSV format.
 pub fn write_model_to_path<P>(model: &Model, path: P) -> Result<()>
 where
     P: AsRef<Path>,
 {
-    let out = path.as_ref().with_extension("poi");
-    let file = File::create(out)?;
-    let mut zip = zip::ZipWriter::new(file);
+    let mut writer = PoiWriter::from_path(path)?;
+    for poi_type in model.poi_types.values() {
+        writer.add_poi_type(poi_type.clone());
+    }
+    for poi in model.pois.values() {
+        writer.write_poi(poi)?;
+    }
+    writer.finish()
+}
 
-    zip.start_file("poi.txt", zip::write::FileOptions::default())?;
+/// Reads a `.poi` archive without loading all the POIs in memory.
+///
+/// Properties, translated names and addresses are attached to their POI
+/// while streaming, which requires `poi.txt` to be sorted by id, and
+/// `poi_properties.txt`, `poi_translations.txt` and `poi_addresses.txt` to
+/// be sorted by POI id (which is how [PoiWriter] writes POIs sorted by id).
+/// A POI or a record whose id is smaller than the one of the previous POI
+/// is an error, raised as soon as it is read.
+#[derive(Debug)]
+pub struct PoiReader {
+    path: PathBuf,
+    archive: zip::ZipArchive<File>,
+    properties_archive: zip::ZipArchive<File>,
+    translations_archive: zip::ZipArchive<File>,
+    addresses_archive: zip::ZipArchive<File>,
+}
 
-    write_csv(
-        &mut zip,
-        model.pois.iter().map(|(_, poi)| PoiRecord::from(poi)),
-    )?;
+impl PoiReader {
+    /// Opens a `.poi` archive.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<PoiReader> {
+        let path = path.as_ref().to_path_buf();
+        let archive = zip::ZipArchive::new(File::open(&path)?)?;
+        let properties_archive = zip::ZipArchive::new(File::open(&path)?)?;
+        let translations_archive = zip::ZipArchive::new(File::open(&path)?)?;
+        let addresses_archive = zip::ZipArchive::new(File::open(&path)?)?;
+        Ok(PoiReader {
+            path,
+            archive,
+            properties_archive,
+            translations_archive,
+            addresses_archive,
+        })
+    }
 
-    zip.start_file("poi_type.txt", zip::write::FileOptions::default())?;
+    /// Reads all the POI types of the archive.
+    pub fn poi_types(&mut self) -> Result<HashMap<String, PoiType>> {
+        let zipper = self.archive.by_name("poi_type.txt")?;
+        read_csv::<_, PoiTypeRecord>(zipper)
+            .map(|rec| {
+                let poi_type = PoiType::from(rec?);
+                Ok((poi_type.id.clone(), poi_type))
+            })
+            .collect()
+    }
 
-    write_csv(
-        &mut zip,
-        model
-            .poi_types
-            .iter()
-            .sorted_by_key(|pt| pt.0)
-            .map(|pt| PoiTypeRecord::from(pt.1.clone())),
-    )?;
-
-    zip.start_file("poi_properties.txt", zip::write::FileOptions::default())?;
-
-    write_csv(
-        &mut zip,
-        model.pois.iter().flat_map(|(_, poi)| {
-            poi.properties
-                .iter()
-                .map(move |prop| PoiProperty {
-                    poi_id: poi.id.clone(),
-                    key: prop.key.clone(),
-                    value: prop.value.clone(),
-                })
-                .sorted_by(|lhs, rhs| Ord::cmp(&lhs.key, &rhs.key))
-        }),
-    )?;
+    /// Streams the POIs of the archive with their properties, translated
+    /// names and addresses.
+    pub fn pois(&mut self) -> Result<impl Iterator<Item = Result<Poi>> + '_> {
+        let pois = read_csv::<_, PoiRecord>(self.archive.by_name("poi.txt")?);
+        // For poi_properties.txt, poi_translations.txt and poi_addresses.txt,
+        // it's a bit different: If the file is not present, it does not mean
+        // it is an error.
+        let properties = self
+            .properties_archive
+            .by_name("poi_properties.txt")
+            .ok()
+            .map(read_csv::<_, PoiProperty>)
+            .into_iter()
+            .flatten()
+            .peekable();
+        let translations = self
+            .translations_archive
+            .by_name("poi_translations.txt")
+            .ok()
+            .map(read_csv::<_, PoiTranslation>)
+            .into_iter()
+            .flatten()
+            .peekable();
+        let addresses = self
+            .addresses_archive
+            .by_name("poi_addresses.txt")
+            .ok()
+            .map(read_csv::<_, PoiAddress>)
+            .into_iter()
+            .flatten()
+            .peekable();
+        Ok(GroupedPois {
+            path: &self.path,
+            previous_poi_id: None,
+            pois,
+            properties,
+            translations,
+            addresses,
+        })
+    }
+}
 
-    Ok(())
+/// A record of a file attached to `poi.txt` through a `poi_id` column.
+trait PoiChild {
+    const KIND: &'static str;
+    fn poi_id(&self) -> &str;
+}
+
+/// Pops the records belonging to the POI `poi_id` at the head of `records`.
+///
+/// Fails on a record of a smaller POI id, as the records are sorted by POI
+/// id and it would otherwise never be attached to its POI.
+fn take_group<I, T>(records: &mut Peekable<I>, poi_id: &str, path: &Path) -> Result<Vec<T>>
+where
+    I: Iterator<Item = Result<T>>,
+    T: PoiChild,
+{
+    let mut group = Vec::new();
+    loop {
+        match records.peek() {
+            Some(Ok(record)) if record.poi_id() == poi_id => {}
+            Some(Ok(record)) if record.poi_id() < poi_id => {
+                return Err(format_err!(
+                    "in file '{}', {} of poi '{}' found after poi '{}', the records should be sorted by poi id",
+                    path.display(),
+                    T::KIND,
+                    record.poi_id(),
+                    poi_id
+                ));
+            }
+            Some(Err(_)) => {}
+            _ => break,
+        }
+        if let Some(record) = records.next() {
+            group.push(record?);
+        }
+    }
+    Ok(group)
+}
+
+/// Builds an error for a record left over after all POIs have been read.
+fn orphan_error<T: PoiChild>(path: &Path, record: Result<T>) -> Result<Poi> {
+    let record = record?;
+    Err(format_err!(
+        "in file '{}', cannot find poi '{}' for {} insertion",
+        path.display(),
+        record.poi_id(),
+        T::KIND
+    ))
+}
+
+/// Iterator attaching the properties, translated names and addresses to the
+/// POIs, all read in the same order.
+struct GroupedPois<'a, P, Q, R, S>
+where
+    Q: Iterator<Item = Result<PoiProperty>>,
+    R: Iterator<Item = Result<PoiTranslation>>,
+    S: Iterator<Item = Result<PoiAddress>>,
+{
+    path: &'a Path,
+    /// Id of the previous POI, to detect the POIs out of order
+    previous_poi_id: Option<String>,
+    pois: P,
+    properties: Peekable<Q>,
+    translations: Peekable<R>,
+    addresses: Peekable<S>,
+}
+
+impl<'a, P, Q, R, S> Iterator for GroupedPois<'a, P, Q, R, S>
+where
+    P: Iterator<Item = Result<PoiRecord>>,
+    Q: Iterator<Item = Result<PoiProperty>>,
+    R: Iterator<Item = Result<PoiTranslation>>,
+    S: Iterator<Item = Result<PoiAddress>>,
+{
+    type Item = Result<Poi>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let path = self.path;
+        let record = match self.pois.next() {
+            Some(record) => record,
+            None => {
+                // Remaining records do not belong to any POI
+                if let Some(property) = self.properties.next() {
+                    return Some(orphan_error(path, property));
+                }
+                if let Some(translation) = self.translations.next() {
+                    return Some(orphan_error(path, translation));
+                }
+                return self
+                    .addresses
+                    .next()
+                    .map(|address| orphan_error(path, address));
+            }
+        };
+        let previous_poi_id = &mut self.previous_poi_id;
+        let properties = &mut self.properties;
+        let translations = &mut self.translations;
+        let addresses = &mut self.addresses;
+        let poi = record.and_then(Poi::try_from).and_then(|mut poi| {
+            if let Some(previous_poi_id) = previous_poi_id.as_ref() {
+                if poi.id <= *previous_poi_id {
+                    return Err(format_err!(
+                        "in file '{}', poi '{}' found after poi '{}', the pois should be sorted by id",
+                        path.display(),
+                        poi.id,
+                        previous_poi_id
+                    ));
+                }
+            }
+            *previous_poi_id = Some(poi.id.clone());
+            poi.properties = take_group(properties, &poi.id, path)?
+                .into_iter()
+                .map(Property::from)
+                .collect();
+            poi.translated_names = take_group(translations, &poi.id, path)?
+                .into_iter()
+                .map(|translation: PoiTranslation| (translation.lang, translation.name))
+                .collect();
+            poi.address = take_group(addresses, &poi.id, path)?
+                .into_iter()
+                .next()
+                .map(Address::from);
+            Ok(poi)
+        });
+        Some(poi)
+    }
 }
 
 /// Takes a zipped file containing pois, types, and properties,
@@ -84,7 +434,7 @@ where
         reader
             .map(|rec| {
                 let rec: PoiRecord = rec?;
-                let poi = Poi::from(rec);
+                let poi = Poi::try_from(rec)?;
                 Ok((poi.id.clone(), poi))
             })
             .collect::<Result<_>>()?
@@ -103,21 +453,47 @@ where
     // For poi_properties.txt, it's a bit different: If the file is not
     // present, it does not mean it is an error.
     if let Ok(zipper) = zip.by_name("poi_properties.txt") {
-        let reader = read_csv(zipper);
-        reader
-            .map(|rec| {
-                let poi_property: PoiProperty = rec?;
-                let poi = pois.get_mut(&poi_property.poi_id).ok_or_else(|| {
-                    format_err!(
-                        "in file '{}', cannot find poi '{}' for property insertion",
-                        path.as_ref().display(),
-                        &poi_property.poi_id
-                    )
-                })?;
-                poi.properties.push(Property::from(poi_property));
-                Ok(())
-            })
-            .collect::<Result<()>>()?;
+        read_csv(zipper).try_for_each(|rec| -> Result<()> {
+            let poi_property: PoiProperty = rec?;
+            let poi = pois.get_mut(&poi_property.poi_id).ok_or_else(|| {
+                format_err!(
+                    "in file '{}', cannot find poi '{}' for property insertion",
+                    path.as_ref().display(),
+                    &poi_property.poi_id
+                )
+            })?;
+            poi.properties.push(Property::from(poi_property));
+            Ok(())
+        })?;
+    }
+    if let Ok(zipper) = zip.by_name("poi_translations.txt") {
+        read_csv(zipper).try_for_each(|rec| -> Result<()> {
+            let translation: PoiTranslation = rec?;
+            let poi = pois.get_mut(&translation.poi_id).ok_or_else(|| {
+                format_err!(
+                    "in file '{}', cannot find poi '{}' for translation insertion",
+                    path.as_ref().display(),
+                    &translation.poi_id
+                )
+            })?;
+            poi.translated_names
+                .insert(translation.lang, translation.name);
+            Ok(())
+        })?;
+    }
+    if let Ok(zipper) = zip.by_name("poi_addresses.txt") {
+        read_csv(zipper).try_for_each(|rec| -> Result<()> {
+            let address: PoiAddress = rec?;
+            let poi = pois.get_mut(&address.poi_id).ok_or_else(|| {
+                format_err!(
+                    "in file '{}', cannot find poi '{}' for address insertion",
+                    path.as_ref().display(),
+                    &address.poi_id
+                )
+            })?;
+            poi.address = Some(Address::from(address));
+            Ok(())
+        })?;
     }
     Ok(Model { pois, poi_types })
 }
@@ -175,17 +551,22 @@ impl From<&Poi> for PoiRecord {
     }
 }
 
-impl From<PoiRecord> for Poi {
-    fn from(record: PoiRecord) -> Poi {
-        Poi {
+impl TryFrom<PoiRecord> for Poi {
+    type Error = Error;
+    fn try_from(record: PoiRecord) -> Result<Poi> {
+        let coord = Coord::try_new(record.lon, record.lat)
+            .with_context(|_| format!("poi '{}' rejected", record.id))?;
+        Ok(Poi {
             id: record.id,
             name: record.name,
-            coord: Coord::new(record.lon, record.lat),
+            coord,
             poi_type_id: record.type_id,
             properties: Vec::new(),
+            translated_names: BTreeMap::new(),
+            address: None,
             visible: record.visible,
             weight: record.weight,
-        }
+        })
     }
 }
 
@@ -196,6 +577,13 @@ struct PoiProperty {
     pub value: String,
 }
 
+impl PoiChild for PoiProperty {
+    const KIND: &'static str = "property";
+    fn poi_id(&self) -> &str {
+        &self.poi_id
+    }
+}
+
 impl From<PoiProperty> for Property {
     fn from(property: PoiProperty) -> Property {
         Property {
@@ -205,6 +593,49 @@ impl From<PoiProperty> for Property {
     }
 }
 
+/// A translated name of a POI, stored in `poi_translations.txt`
+#[derive(Debug, Deserialize, Serialize, Ord, PartialOrd, Eq, PartialEq)]
+struct PoiTranslation {
+    pub poi_id: String,
+    pub lang: String,
+    pub name: String,
+}
+
+impl PoiChild for PoiTranslation {
+    const KIND: &'static str = "translation";
+    fn poi_id(&self) -> &str {
+        &self.poi_id
+    }
+}
+
+/// The address of a POI, stored in `poi_addresses.txt`
+#[derive(Debug, Deserialize, Serialize)]
+struct PoiAddress {
+    pub poi_id: String,
+    pub house_number: Option<String>,
+    pub street: Option<String>,
+    pub postcode: Option<String>,
+    pub city: Option<String>,
+}
+
+impl PoiChild for PoiAddress {
+    const KIND: &'static str = "address";
+    fn poi_id(&self) -> &str {
+        &self.poi_id
+    }
+}
+
+impl From<PoiAddress> for Address {
+    fn from(address: PoiAddress) -> Address {
+        Address {
+            house_number: address.house_number,
+            street: address.street,
+            postcode: address.postcode,
+            city: address.city,
+        }
+    }
+}
+
 /// A type of POI
 /// We use a different type for serialization, because we want to make sure
 /// we have adequate headers. In some files (json?) the headers are just
@@ -238,6 +669,14 @@ impl From<PoiType> for PoiTypeRecord {
     }
 }
 
+/// Builds a CSV writer with the `.poi` format settings.
+fn csv_writer<W: std::io::Write>(writer: W) -> csv::Writer<W> {
+    csv::WriterBuilder::new()
+        .has_headers(true)
+        .delimiter(b';')
+        .from_writer(writer)
+}
+
 /// Converts items into CSV, and streams them to a writer.
 fn write_csv<W, I, T>(writer: W, items: I) -> Result<()>
 where
@@ -245,10 +684,7 @@ where
     I: Iterator<Item = T>,
     T: Serialize,
 {
-    let mut csv_writer = csv::WriterBuilder::new()
-        .has_headers(true)
-        .delimiter(b';')
-        .from_writer(writer);
+    let mut csv_writer = csv_writer(writer);
     for item in items {
         csv_writer.serialize(item)?;
     }
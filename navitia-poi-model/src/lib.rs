@@ -22,6 +22,7 @@
 mod io;
 pub mod objects;
 
+pub use io::{PoiReader, PoiWriter};
 pub use objects::*;
 
 /// The data type for errors in [navitia-poi-model], just an alias
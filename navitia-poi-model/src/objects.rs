@@ -30,13 +30,29 @@ use std::collections::{
 use std::path::Path;
 
 /// A thin wrapper around [geo::Coordinate]
+///
+/// A `Coord` has no default value and can only be built with
+/// [Coord::try_new], so its coordinates are always valid.
 #[derive(Debug, Clone)]
-pub struct Coord(pub geo::Coordinate<f64>);
+pub struct Coord(geo::Coordinate<f64>);
 impl Coord {
-    /// Create a new Coord from longitude and latitude.
-    /// Values should be expressed in degrees
-    pub fn new(lon: f64, lat: f64) -> Coord {
-        Coord(geo::Coordinate { x: lon, y: lat })
+    /// Create a new Coord from longitude and latitude, expressed in degrees.
+    /// Fails if the values are not valid (see [Coord::is_valid]).
+    pub fn try_new(lon: f64, lat: f64) -> Result<Coord> {
+        let coord = Coord(geo::Coordinate { x: lon, y: lat });
+        if coord.is_null() {
+            Err(format_err!(
+                "invalid coordinates (lon: 0, lat: 0), usually the value of missing coordinates"
+            ))
+        } else if coord.is_valid() {
+            Ok(coord)
+        } else {
+            Err(format_err!(
+                "invalid coordinates (lon: {}, lat: {}), expected -180 <= lon <= 180 and -90 <= lat <= 90",
+                lon,
+                lat
+            ))
+        }
     }
 
     /// Return the longitude
@@ -49,19 +65,19 @@ impl Coord {
         self.y
     }
 
-    /// Returns true if the latitude and the longitude are
-    /// those corresponding to the default values.
-    pub fn is_default(&self) -> bool {
+    // Returns true if the latitude and the longitude are both 0, the value
+    // of missing coordinates in many sources
+    fn is_null(&self) -> bool {
         self.lat() == 0. && self.lon() == 0.
     }
 
-    /// Returns true if latitude and longitude are in
+    /// Returns true if latitude and longitude are not both 0 and are in
     /// a valid range:
     ///
-    /// - -90 < lat < 90
-    /// - -180 < lon < 180
+    /// - -90 <= lat <= 90
+    /// - -180 <= lon <= 180
     pub fn is_valid(&self) -> bool {
-        !self.is_default()
+        !self.is_null()
             && -90. <= self.lat()
             && self.lat() <= 90.
             && -180. <= self.lon()
@@ -76,12 +92,6 @@ impl ::std::ops::Deref for Coord {
     }
 }
 
-impl Default for Coord {
-    fn default() -> Coord {
-        Coord(geo::Coordinate { x: 0., y: 0. })
-    }
-}
-
 /// A Property of a [Poi]
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Property {
@@ -93,6 +103,22 @@ pub struct Property {
     pub value: String,
 }
 
+/// A postal address
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Address {
+    /// House number, e.g. `6` or `12bis`
+    pub house_number: Option<String>,
+
+    /// Name of the street
+    pub street: Option<String>,
+
+    /// Postal code
+    pub postcode: Option<String>,
+
+    /// Name of the city
+    pub city: Option<String>,
+}
+
 /// A Poi
 #[derive(Debug, Clone)]
 pub struct Poi {
@@ -102,7 +128,7 @@ pub struct Poi {
     /// Name of the POI
     pub name: String,
 
-    /// Coordinates of the POI
+    /// Coordinates of the POI, always in a valid range
     pub coord: Coord,
 
     /// The POI type. It is a pointer to a [PoiType]
@@ -111,6 +137,13 @@ pub struct Poi {
     /// List of key values related to the POI
     pub properties: Vec<Property>,
 
+    /// Names of the POI in other languages, indexed by language code
+    /// (e.g. `fr`, `en`)
+    pub translated_names: BTreeMap<String, String>,
+
+    /// Postal address of the POI, if known
+    pub address: Option<Address>,
+
     /// Indicates if the POI is visible in the map
     pub visible: bool,
 
@@ -195,3 +228,18 @@ impl Model {
         Ok(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coord_validity() {
+        assert!(Coord::try_new(2.35, 48.85).is_ok());
+        assert!(Coord::try_new(180., -90.).is_ok());
+        assert!(Coord::try_new(0., 48.85).is_ok());
+        assert!(Coord::try_new(0., 0.).is_err());
+        assert!(Coord::try_new(2.35, 90.1).is_err());
+        assert!(Coord::try_new(-180.1, 48.85).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use super::get_way_coord;
+use super::grid_index::PointIndex;
+use geo::Point;
+use navitia_poi_model::objects::{Address, Coord};
+use std::collections::BTreeMap;
+
+/// Builds an address from the `addr:*` tags of an OSM object.
+/// Returns `None` if the object has neither a house number nor a street.
+pub fn address_from_tags(tags: &osmpbfreader::Tags) -> Option<Address> {
+    let get = |key: &str| tags.get(key).map(|value| value.to_string());
+    let house_number = get("addr:housenumber");
+    let street = get("addr:street").or_else(|| get("addr:place"));
+    if house_number.is_none() && street.is_none() {
+        return None;
+    }
+    Some(Address {
+        house_number,
+        street,
+        postcode: get("addr:postcode"),
+        city: get("addr:city"),
+    })
+}
+
+/// Returns true if the OSM object is a usable address: a house number on a
+/// street.
+pub fn is_address(tags: &osmpbfreader::Tags) -> bool {
+    tags.contains_key("addr:housenumber")
+        && (tags.contains_key("addr:street") || tags.contains_key("addr:place"))
+}
+
+/// A spatial index of addresses, used to find the nearest address of a
+/// point within a radius.
+#[derive(Debug)]
+pub struct AddressIndex {
+    radius: f64,
+    index: PointIndex<Address>,
+}
+
+impl AddressIndex {
+    /// Creates an empty index, `radius` being the maximum distance (in
+    /// meters) of a lookup.
+    pub fn new(radius: f64) -> Self {
+        AddressIndex {
+            radius,
+            index: PointIndex::new(radius),
+        }
+    }
+
+    /// Indexes all the addresses (nodes and buildings) of the OSM objects.
+    pub fn from_objects(
+        obj_map: &BTreeMap<osmpbfreader::OsmId, osmpbfreader::OsmObj>,
+        radius: f64,
+    ) -> Self {
+        let mut index = AddressIndex::new(radius);
+        for obj in obj_map.values().filter(|obj| is_address(obj.tags())) {
+            let coord = match obj {
+                osmpbfreader::OsmObj::Node(node) => Coord::try_new(node.lon(), node.lat()).ok(),
+                osmpbfreader::OsmObj::Way(way) => get_way_coord(obj_map, way).ok(),
+                osmpbfreader::OsmObj::Relation(_) => None,
+            };
+            if let (Some(coord), Some(address)) = (coord, address_from_tags(obj.tags())) {
+                index.insert(&coord, address);
+            }
+        }
+        index
+    }
+
+    /// Adds an address located at `coord`.
+    pub fn insert(&mut self, coord: &Coord, address: Address) {
+        self.index
+            .insert(Point::new(coord.lon(), coord.lat()), address);
+    }
+
+    /// Returns the nearest address of `coord` within the radius, if any.
+    pub fn nearest(&self, coord: &Coord) -> Option<&Address> {
+        self.index
+            .nearest(Point::new(coord.lon(), coord.lat()), self.radius)
+            .map(|(_, address)| address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(v: &[(&str, &str)]) -> osmpbfreader::Tags {
+        v.iter().map(|&(k, v)| (k.into(), v.into())).collect()
+    }
+
+    fn address(house_number: &str, street: &str) -> Address {
+        Address {
+            house_number: Some(house_number.to_string()),
+            street: Some(street.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn address_from_tags_test() {
+        assert_eq!(None, address_from_tags(&tags(&[("addr:city", "Paris")])));
+        assert_eq!(
+            Some(Address {
+                house_number: Some("6".to_string()),
+                street: Some("quai de Gesvres".to_string()),
+                postcode: Some("75004".to_string()),
+                city: Some("Paris".to_string()),
+            }),
+            address_from_tags(&tags(&[
+                ("addr:housenumber", "6"),
+                ("addr:street", "quai de Gesvres"),
+                ("addr:postcode", "75004"),
+                ("addr:city", "Paris"),
+            ]))
+        );
+        assert!(is_address(&tags(&[
+            ("addr:housenumber", "6"),
+            ("addr:place", "Hameau de la Reine"),
+        ])));
+        assert!(!is_address(&tags(&[("addr:street", "quai de Gesvres")])));
+    }
+
+    #[test]
+    fn nearest_address_test() {
+        let mut index = AddressIndex::new(50.);
+        index.insert(
+            &Coord::try_new(2.3497, 48.8569).unwrap(),
+            address("6", "quai de Gesvres"),
+        );
+        index.insert(
+            &Coord::try_new(2.3502, 48.8569).unwrap(),
+            address("8", "quai de Gesvres"),
+        );
+        index.insert(
+            &Coord::try_new(2.3600, 48.8569).unwrap(),
+            address("1", "rue de Rivoli"),
+        );
+
+        // ~15m from the first address, ~22m from the second one
+        let found = index
+            .nearest(&Coord::try_new(2.3499, 48.8569).unwrap())
+            .unwrap();
+        assert_eq!(Some("6".to_string()), found.house_number);
+        // nothing within 50m
+        assert!(index
+            .nearest(&Coord::try_new(2.3550, 48.8569).unwrap())
+            .is_none());
+    }
+}
@@ -0,0 +1,138 @@
+use super::OsmPbfReader;
+use crate::Result;
+use geo::algorithm::contains::Contains;
+use geo::{MultiPolygon, Point};
+use log::warn;
+use osm_boundaries_utils::build_boundary;
+use std::collections::BTreeSet;
+
+/// An administrative region, read from a `boundary=administrative` relation.
+#[derive(Debug, Clone)]
+pub struct Admin {
+    /// Id like `admin:osm:relation:120965`
+    pub id: String,
+    pub name: String,
+    pub level: u32,
+    pub boundary: MultiPolygon<f64>,
+    // (min lon, min lat, max lon, max lat) of the boundary
+    bounding_box: (f64, f64, f64, f64),
+}
+
+impl Admin {
+    pub fn new(id: String, name: String, level: u32, boundary: MultiPolygon<f64>) -> Self {
+        let mut bounding_box = (
+            f64::INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+        );
+        for coord in boundary
+            .0
+            .iter()
+            .flat_map(|polygon| polygon.exterior().0.iter())
+        {
+            bounding_box.0 = bounding_box.0.min(coord.x);
+            bounding_box.1 = bounding_box.1.min(coord.y);
+            bounding_box.2 = bounding_box.2.max(coord.x);
+            bounding_box.3 = bounding_box.3.max(coord.y);
+        }
+        Admin {
+            id,
+            name,
+            level,
+            boundary,
+            bounding_box,
+        }
+    }
+
+    /// Returns true if the point (lon, lat) is inside the boundary.
+    pub fn contains(&self, point: &Point<f64>) -> bool {
+        let (min_lon, min_lat, max_lon, max_lat) = self.bounding_box;
+        point.x() >= min_lon
+            && point.x() <= max_lon
+            && point.y() >= min_lat
+            && point.y() <= max_lat
+            && self.boundary.contains(point)
+    }
+}
+
+/// The admin level of an administrative boundary, if it is one of `levels`.
+pub fn admin_level(tags: &osmpbfreader::Tags, levels: &BTreeSet<u32>) -> Option<u32> {
+    if !tags.contains("boundary", "administrative") {
+        return None;
+    }
+    tags.get("admin_level")
+        .and_then(|level| level.parse().ok())
+        .filter(|level| levels.contains(level))
+}
+
+/// Reads the administrative regions of the given admin levels (8 for the
+/// cities in France), sorted by id.
+pub fn read_admins(pbf: &mut OsmPbfReader, levels: &BTreeSet<u32>) -> Result<Vec<Admin>> {
+    let objects =
+        pbf.get_objs_and_deps(|o| o.is_relation() && admin_level(o.tags(), levels).is_some())?;
+    let mut admins: Vec<Admin> = objects
+        .values()
+        .filter_map(|obj| obj.relation())
+        .filter_map(|relation| {
+            let level = admin_level(&relation.tags, levels)?;
+            let boundary = build_boundary(relation, &objects);
+            if boundary.is_none() {
+                warn!(
+                    "Impossible to build the boundary of the admin relation {:?}",
+                    relation.id.0
+                );
+            }
+            let name = relation
+                .tags
+                .get("name")
+                .map(|name| name.to_string())
+                .unwrap_or_default();
+            Some(Admin::new(
+                format!("admin:osm:relation:{}", relation.id.0),
+                name,
+                level,
+                boundary?,
+            ))
+        })
+        .collect();
+    admins.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(admins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::{LineString, Polygon};
+
+    fn tags(v: &[(&str, &str)]) -> osmpbfreader::Tags {
+        v.iter().map(|&(k, v)| (k.into(), v.into())).collect()
+    }
+
+    #[test]
+    fn admin_level_test() {
+        let levels: BTreeSet<u32> = vec![6, 8].into_iter().collect();
+        let city = tags(&[("boundary", "administrative"), ("admin_level", "8")]);
+        assert_eq!(Some(8), admin_level(&city, &levels));
+        let region = tags(&[("boundary", "administrative"), ("admin_level", "4")]);
+        assert_eq!(None, admin_level(&region, &levels));
+        let park = tags(&[("boundary", "national_park"), ("admin_level", "8")]);
+        assert_eq!(None, admin_level(&park, &levels));
+    }
+
+    #[test]
+    fn contains_test() {
+        let square = Polygon::new(
+            LineString::from(vec![(0., 0.), (2., 0.), (2., 2.), (0., 2.), (0., 0.)]),
+            vec![],
+        );
+        let admin = Admin::new(
+            "admin:osm:relation:1".to_string(),
+            "Square".to_string(),
+            8,
+            MultiPolygon(vec![square]),
+        );
+        assert!(admin.contains(&Point::new(1., 1.)));
+        assert!(!admin.contains(&Point::new(3., 1.)));
+    }
+}
@@ -0,0 +1,169 @@
+//! Spatial indexes bucketing objects in a grid, to find the objects around a
+//! point without scanning all of them.
+
+use geo::algorithm::haversine_distance::HaversineDistance;
+use geo::Point;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+// Approximate length of a degree of latitude, in meters
+const METERS_PER_DEGREE: f64 = 111_320.;
+
+/// Objects bucketed in a grid whose cells are about `cell_size` meters high.
+///
+/// An object is either located at a point, or spread over all the cells of
+/// its bounding box.
+#[derive(Debug, Clone)]
+pub struct GridIndex<T> {
+    // Size of the cells, in degrees
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<T>>,
+}
+
+impl<T> GridIndex<T> {
+    /// Creates an empty index with cells of about `cell_size` meters.
+    pub fn new(cell_size: f64) -> Self {
+        GridIndex {
+            cell_size: (cell_size / METERS_PER_DEGREE).max(1e-5),
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, point: &Point<f64>) -> (i64, i64) {
+        (
+            (point.x() / self.cell_size).floor() as i64,
+            (point.y() / self.cell_size).floor() as i64,
+        )
+    }
+
+    /// Adds an object located at `point`.
+    pub fn insert(&mut self, point: &Point<f64>, object: T) {
+        let cell = self.cell(point);
+        self.cells.entry(cell).or_insert_with(Vec::new).push(object);
+    }
+
+    /// Adds an object in all the cells of the bounding box from `min` to
+    /// `max`.
+    pub fn insert_bbox(&mut self, min: &Point<f64>, max: &Point<f64>, object: T)
+    where
+        T: Clone,
+    {
+        let (min_x, min_y) = self.cell(min);
+        let (max_x, max_y) = self.cell(max);
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                self.cells
+                    .entry((x, y))
+                    .or_insert_with(Vec::new)
+                    .push(object.clone());
+            }
+        }
+    }
+
+    /// The objects of the cells less than `radius` meters away from `point`.
+    /// Some of them can be farther than `radius`, and an object inserted
+    /// with a bounding box can be returned several times.
+    pub fn candidates<'a>(
+        &'a self,
+        point: &Point<f64>,
+        radius: f64,
+    ) -> impl Iterator<Item = &'a T> + 'a {
+        let (x, y) = self.cell(point);
+        let y_span = (radius / METERS_PER_DEGREE / self.cell_size).ceil() as i64;
+        // A degree of longitude gets shorter towards the poles, more cells
+        // have to be looked at horizontally
+        let x_span =
+            (radius / METERS_PER_DEGREE / point.y().to_radians().cos().max(0.01) / self.cell_size)
+                .ceil() as i64;
+        (x - x_span..=x + x_span)
+            .flat_map(move |i| (y - y_span..=y + y_span).map(move |j| (i, j)))
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+}
+
+/// Objects located at a point, to find the ones within a distance.
+#[derive(Debug, Clone)]
+pub struct PointIndex<T> {
+    grid: GridIndex<(Point<f64>, T)>,
+}
+
+impl<T> PointIndex<T> {
+    /// Creates an empty index, best suited for lookups of about `radius`
+    /// meters.
+    pub fn new(radius: f64) -> Self {
+        PointIndex {
+            grid: GridIndex::new(radius),
+        }
+    }
+
+    /// Adds an object located at `point`.
+    pub fn insert(&mut self, point: Point<f64>, object: T) {
+        self.grid.insert(&point, (point, object));
+    }
+
+    /// The objects within `radius` meters of `point`, with their distance.
+    pub fn around<'a>(
+        &'a self,
+        point: Point<f64>,
+        radius: f64,
+    ) -> impl Iterator<Item = (f64, &'a T)> + 'a {
+        self.grid
+            .candidates(&point, radius)
+            .map(move |(object_point, object)| (point.haversine_distance(object_point), object))
+            .filter(move |(distance, _)| *distance <= radius)
+    }
+
+    /// The nearest object within `radius` meters of `point`, with its
+    /// distance.
+    pub fn nearest(&self, point: Point<f64>, radius: f64) -> Option<(f64, &T)> {
+        self.around(point, radius)
+            .min_by(|(lhs, _), (rhs, _)| lhs.partial_cmp(rhs).unwrap_or(Ordering::Equal))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.grid.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_around() {
+        let mut index = PointIndex::new(50.);
+        // ~15m, ~37m and ~80m east of the lookup point
+        index.insert(Point::new(2.3501, 48.8569), "a");
+        index.insert(Point::new(2.3504, 48.8569), "b");
+        index.insert(Point::new(2.3510, 48.8569), "c");
+        let lookup = Point::new(2.3499, 48.8569);
+
+        let mut around: Vec<_> = index.around(lookup, 50.).map(|(_, &o)| o).collect();
+        around.sort();
+        assert_eq!(vec!["a", "b"], around);
+        assert_eq!(Some("a"), index.nearest(lookup, 50.).map(|(_, &o)| o));
+        // a larger radius than the cells
+        assert_eq!(3, index.around(lookup, 100.).count());
+        assert!(index.nearest(Point::new(2.36, 48.8569), 50.).is_none());
+    }
+
+    #[test]
+    fn bbox_candidates() {
+        let mut index = GridIndex::new(50.);
+        // a way of about 700m
+        index.insert_bbox(
+            &Point::new(2.35, 48.85),
+            &Point::new(2.3595, 48.8505),
+            "way",
+        );
+        assert!(index
+            .candidates(&Point::new(2.355, 48.8502), 20.)
+            .any(|&o| o == "way"));
+        assert_eq!(0, index.candidates(&Point::new(2.37, 48.86), 20.).count());
+    }
+}
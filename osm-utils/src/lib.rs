@@ -1,3 +1,6 @@
+pub mod address;
+pub mod admin;
+pub mod grid_index;
 pub mod poi;
 
 use failure::format_err;
@@ -25,7 +28,6 @@ pub fn get_way_coord(
         .skip(nb_nodes / 2)
         .filter_map(|node_id| obj_map.get(&(*node_id).into()))
         .filter_map(|obj| obj.node())
-        .map(|node| objects::Coord::new(node.lon(), node.lat()))
         .next()
         .ok_or_else(|| {
             format_err!(
@@ -33,6 +35,7 @@ pub fn get_way_coord(
                 way.id.0
             )
         })
+        .and_then(|node| objects::Coord::try_new(node.lon(), node.lat()))
 }
 
 /// Returns Coord on the relation.
@@ -43,11 +46,12 @@ pub fn get_relation_coord(
     let boundary = build_boundary(relation, obj_map);
     boundary
         .as_ref()
-        .and_then(|b| b.centroid().map(|c| objects::Coord::new(c.x(), c.y())))
+        .and_then(|b| b.centroid())
         .ok_or_else(|| {
             format_err!(
                 "Imposible to get the centroid coordinates of the relation {:?}",
                 relation.id.0
             )
         })
+        .and_then(|c| objects::Coord::try_new(c.x(), c.y()))
 }
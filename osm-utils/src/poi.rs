@@ -1,8 +1,9 @@
+use super::address::{address_from_tags, is_address, AddressIndex};
 use super::{get_relation_coord, get_way_coord, OsmPbfReader};
 use crate::Result;
-use failure::{bail, format_err};
+use failure::{bail, format_err, ResultExt};
 use log::warn;
-use navitia_poi_model::objects;
+use navitia_poi_model::{objects, PoiWriter};
 use serde_derive::Deserialize;
 use std::collections::BTreeMap;
 use std::io;
@@ -21,6 +22,14 @@ struct Rule {
 pub struct PoiConfig {
     pub poi_types: Vec<objects::PoiType>,
     rules: Vec<Rule>,
+    /// Languages for which translated names are extracted from the
+    /// `name:<lang>` tags
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// When set, POIs without `addr:*` tags get the address of the nearest
+    /// OSM address node or building within this radius (in meters)
+    #[serde(default)]
+    pub address_lookup_radius: Option<f64>,
 }
 impl Default for PoiConfig {
     fn default() -> Self {
@@ -39,6 +48,11 @@ impl PoiConfig {
     pub fn is_poi(&self, tags: &osmpbfreader::Tags) -> bool {
         self.get_poi_type(tags).is_some()
     }
+    /// Returns true if the object must be read from the pbf, either as a POI
+    /// or as an address used to complete the POIs.
+    fn is_needed(&self, tags: &osmpbfreader::Tags) -> bool {
+        self.is_poi(tags) || (self.address_lookup_radius.is_some() && is_address(tags))
+    }
     pub fn get_poi_id(&self, tags: &osmpbfreader::Tags) -> Option<&str> {
         self.get_poi_type(tags).map(|poi_type| poi_type.id.as_str())
     }
@@ -91,10 +105,24 @@ fn make_properties(tags: &osmpbfreader::Tags) -> Vec<objects::Property> {
         .collect()
 }
 
+fn make_translated_names(
+    tags: &osmpbfreader::Tags,
+    languages: &[String],
+) -> BTreeMap<String, String> {
+    languages
+        .iter()
+        .filter_map(|lang| {
+            tags.get(format!("name:{}", lang).as_str())
+                .map(|name| (lang.clone(), name.to_string()))
+        })
+        .collect()
+}
+
 fn parse_poi(
     osmobj: &osmpbfreader::OsmObj,
     obj_map: &BTreeMap<osmpbfreader::OsmId, osmpbfreader::OsmObj>,
     matcher: &PoiConfig,
+    address_index: Option<&AddressIndex>,
 ) -> Result<objects::Poi> {
     let poi_type = matcher.get_poi_type(osmobj.tags()).ok_or_else(|| {
         format_err!(
@@ -102,28 +130,20 @@ fn parse_poi(
             osmobj.id()
         )
     })?;
-    let (id, coord) = match *osmobj {
-        osmpbfreader::OsmObj::Node(ref node) => (
-            format_poi_id("node", node.id.0),
-            objects::Coord::new(node.lon(), node.lat()),
-        ),
-        osmpbfreader::OsmObj::Way(ref way) => {
-            (format_poi_id("way", way.id.0), get_way_coord(obj_map, way)?)
-        }
-        osmpbfreader::OsmObj::Relation(ref relation) => (
-            format_poi_id("relation", relation.id.0),
-            get_relation_coord(obj_map, relation)?,
-        ),
+    let id = poi_id(osmobj.id());
+    let coord = match *osmobj {
+        osmpbfreader::OsmObj::Node(ref node) => objects::Coord::try_new(node.lon(), node.lat()),
+        osmpbfreader::OsmObj::Way(ref way) => get_way_coord(obj_map, way),
+        osmpbfreader::OsmObj::Relation(ref relation) => get_relation_coord(obj_map, relation),
     };
+    let coord = coord.with_context(|_| format!("poi '{}' rejected", id))?;
 
     let name = osmobj.tags().get("name").unwrap_or(&poi_type.name);
-
-    if coord.is_default() {
-        bail!(
-            "The poi {} is rejected, cause: could not compute coordinates.",
-            id
-        );
-    }
+    let address = address_from_tags(osmobj.tags()).or_else(|| {
+        address_index
+            .and_then(|index| index.nearest(&coord))
+            .cloned()
+    });
 
     Ok(objects::Poi {
         id,
@@ -131,6 +151,8 @@ fn parse_poi(
         coord,
         poi_type_id: poi_type.id.clone(),
         properties: make_properties(osmobj.tags()),
+        translated_names: make_translated_names(osmobj.tags(), &matcher.languages),
+        address,
         visible: true,
         weight: 0,
     })
@@ -140,22 +162,87 @@ fn format_poi_id(osm_type: &str, id: i64) -> String {
     format!("osm:{}:{}", osm_type, id)
 }
 
-/// Extract POIs from an OSM pbf.
-pub fn extract_pois(pbf: &mut OsmPbfReader, matcher: &PoiConfig) -> BTreeMap<String, objects::Poi> {
-    let objects = pbf.get_objs_and_deps(|o| matcher.is_poi(o.tags())).unwrap();
-    objects
-        .iter()
-        .filter(|&(_, obj)| matcher.is_poi(obj.tags()))
-        .filter_map(|(_, obj)| match parse_poi(obj, &objects, matcher) {
-            Ok(poi) => Some((poi.id.clone(), poi)),
+fn osm_type_and_id(osm_id: osmpbfreader::OsmId) -> (&'static str, i64) {
+    match osm_id {
+        osmpbfreader::OsmId::Node(id) => ("node", id.0),
+        osmpbfreader::OsmId::Way(id) => ("way", id.0),
+        osmpbfreader::OsmId::Relation(id) => ("relation", id.0),
+    }
+}
+
+fn poi_id(osm_id: osmpbfreader::OsmId) -> String {
+    let (osm_type, id) = osm_type_and_id(osm_id);
+    format_poi_id(osm_type, id)
+}
+
+// Ordered like the POI ids, without allocating them: the OSM type, then the
+// id written in decimal and padded with zeros, shorter ids coming first like
+// shorter strings
+fn poi_id_key(osm_id: osmpbfreader::OsmId) -> (&'static str, [u8; 20]) {
+    let (osm_type, id) = osm_type_and_id(osm_id);
+    let mut digits = [0; 20];
+    io::Write::write_fmt(&mut &mut digits[..], format_args!("{}", id))
+        .expect("an i64 is at most 20 characters long");
+    (osm_type, digits)
+}
+
+fn parse_pois<'a>(
+    objects: &'a BTreeMap<osmpbfreader::OsmId, osmpbfreader::OsmObj>,
+    matcher: &'a PoiConfig,
+    address_index: Option<&'a AddressIndex>,
+) -> impl Iterator<Item = objects::Poi> + 'a {
+    // Sorted by POI id, the order of the POIs of a model
+    let mut poi_ids: Vec<osmpbfreader::OsmId> = objects
+        .values()
+        .filter(|obj| matcher.is_poi(obj.tags()))
+        .map(|obj| obj.id())
+        .collect();
+    poi_ids.sort_unstable_by_key(|&id| poi_id_key(id));
+    poi_ids.into_iter().filter_map(move |id| {
+        let obj = &objects[&id];
+        match parse_poi(obj, objects, matcher, address_index) {
+            Ok(poi) => Some(poi),
             Err(err) => {
                 warn!("Error parsing POI {:?}: {}", obj.id(), err);
                 None
             }
-        })
+        }
+    })
+}
+
+/// Extract POIs from an OSM pbf.
+pub fn extract_pois(pbf: &mut OsmPbfReader, matcher: &PoiConfig) -> BTreeMap<String, objects::Poi> {
+    let objects = pbf
+        .get_objs_and_deps(|o| matcher.is_needed(o.tags()))
+        .unwrap();
+    let address_index = matcher
+        .address_lookup_radius
+        .map(|radius| AddressIndex::from_objects(&objects, radius));
+    parse_pois(&objects, matcher, address_index.as_ref())
+        .map(|poi| (poi.id.clone(), poi))
         .collect()
 }
 
+/// Extract POIs from an OSM pbf and write them as they are parsed,
+/// without collecting them.
+pub fn write_pois(
+    pbf: &mut OsmPbfReader,
+    matcher: &PoiConfig,
+    writer: &mut PoiWriter,
+) -> Result<()> {
+    let objects = pbf.get_objs_and_deps(|o| matcher.is_needed(o.tags()))?;
+    let address_index = matcher
+        .address_lookup_radius
+        .map(|radius| AddressIndex::from_objects(&objects, radius));
+    for poi_type in &matcher.poi_types {
+        writer.add_poi_type(poi_type.clone());
+    }
+    for poi in parse_pois(&objects, matcher, address_index.as_ref()) {
+        writer.write_poi(&poi)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,6 +256,26 @@ mod tests {
         PoiConfig::from_reader(io::Cursor::new(s))
     }
 
+    #[test]
+    fn poi_id_key_order() {
+        use osmpbfreader::{NodeId, OsmId, RelationId, WayId};
+        let mut osm_ids = vec![
+            OsmId::Way(WayId(2)),
+            OsmId::Node(NodeId(123)),
+            OsmId::Node(NodeId(-5)),
+            OsmId::Relation(RelationId(1)),
+            OsmId::Node(NodeId(13)),
+            OsmId::Node(NodeId(12)),
+            OsmId::Way(WayId(10)),
+            OsmId::Node(NodeId(i64::MIN)),
+        ];
+        let mut expected: Vec<String> = osm_ids.iter().map(|&id| poi_id(id)).collect();
+        expected.sort();
+        osm_ids.sort_by_key(|&id| poi_id_key(id));
+        let ids: Vec<String> = osm_ids.into_iter().map(poi_id).collect();
+        assert_eq!(expected, ids);
+    }
+
     #[test]
     fn default_test() {
         let c = PoiConfig::default();
@@ -202,6 +309,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn translated_names_test() {
+        let c = from_str(
+            r#"{
+            "poi_types": [{"id": "amenity:townhall", "name": "Mairie"}],
+            "rules": [
+                {
+                    "osm_tags_filters": [{"key": "amenity", "value": "townhall"}],
+                    "poi_type_id": "amenity:townhall"
+                }
+            ],
+            "languages": ["de", "en"]
+        }"#,
+        )
+        .unwrap();
+        let names = make_translated_names(
+            &tags(&[
+                ("name", "Hôtel de ville"),
+                ("name:en", "City Hall"),
+                ("name:fr", "Hôtel de ville"),
+                ("name:de", "Rathaus"),
+            ]),
+            &c.languages,
+        );
+        assert_eq!(2, names.len());
+        assert_eq!("Rathaus", names["de"]);
+        assert_eq!("City Hall", names["en"]);
+        assert!(PoiConfig::default().languages.is_empty());
+    }
+
     #[test]
     fn parsing_errors() {
         from_str("").unwrap_err();
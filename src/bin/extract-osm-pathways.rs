@@ -0,0 +1,88 @@
+use chrono::{DateTime, FixedOffset};
+use log::info;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use tartare_tools::{
+    pathways::{self, PathwayOptions},
+    report::Report,
+    Result,
+};
+use transit_model::{ntfs, Model};
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "extract-osm-pathways",
+    about = "Extract the entrances and pathways of the stop areas from OpenStreetMap.",
+    rename_all = "kebab-case"
+)]
+struct Opt {
+    /// input directory.
+    #[structopt(short, long, parse(from_os_str), default_value = ".")]
+    input: PathBuf,
+
+    /// osm pbf file.
+    #[structopt(short, long, parse(from_os_str))]
+    pbf: PathBuf,
+
+    /// output directory
+    #[structopt(short, long, parse(from_os_str))]
+    output: PathBuf,
+
+    /// maximum distance (in meters) between a stop area and the osm
+    /// entrances, stairs, elevators and footways not members of its
+    /// stop_area relation
+    #[structopt(long, default_value = "150")]
+    max_distance: f64,
+
+    /// walking speed (in m/s) on the walkways
+    #[structopt(long, default_value = "1.12")]
+    walking_speed: f64,
+
+    /// speed (in m/s) along the stairs and the escalators
+    #[structopt(long, default_value = "0.5")]
+    stairs_speed: f64,
+
+    /// time (in seconds) to change level with an elevator
+    #[structopt(long, default_value = "60")]
+    elevator_time: u32,
+
+    /// output json file of the report
+    #[structopt(short, long, parse(from_os_str))]
+    report: Option<PathBuf>,
+
+    /// current datetime
+    #[structopt(
+        short = "x",
+        long,
+        parse(try_from_str),
+        default_value = &transit_model::CURRENT_DATETIME
+    )]
+    current_datetime: DateTime<FixedOffset>,
+}
+
+fn run(opt: Opt) -> Result<()> {
+    info!("Launching extract-osm-pathways.");
+
+    let model = ntfs::read(opt.input)?;
+    let mut collections = model.into_collections();
+    let options = PathwayOptions {
+        max_distance: opt.max_distance,
+        walking_speed: opt.walking_speed,
+        stairs_speed: opt.stairs_speed,
+        elevator_time: opt.elevator_time,
+    };
+    let mut report = Report::default();
+    pathways::from_osm(&opt.pbf, &mut collections, &options, &mut report)?;
+    if let Some(report_path) = opt.report {
+        fs::write(report_path, serde_json::to_string_pretty(&report)?)?;
+    }
+    let model = Model::new(collections)?;
+    transit_model::ntfs::write(&model, opt.output, opt.current_datetime)?;
+
+    Ok(())
+}
+
+fn main() {
+    tartare_tools::runner::launch_run(run);
+}
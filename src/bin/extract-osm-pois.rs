@@ -33,8 +33,7 @@ fn run(opt: Opt) -> Result<()> {
         }
     };
 
-    let poi_model = osm::extract_pois(opt.input, matcher)?;
-    poi_model.save_to_path(opt.output)
+    osm::extract_pois_to_path(opt.input, matcher, opt.output)
 }
 
 fn main() {
@@ -1,8 +1,13 @@
 use chrono::{DateTime, FixedOffset};
 use log::info;
+use std::fs;
 use std::path::PathBuf;
 use structopt::StructOpt;
-use tartare_tools::{improve_stop_positions, Result};
+use tartare_tools::{
+    improve_stop_positions::{self, PositionPolicy},
+    report::Report,
+    Result,
+};
 use transit_model::{ntfs, Model};
 
 #[derive(Debug, StructOpt)]
@@ -28,6 +33,30 @@ struct Opt {
     #[structopt(long, short = "d", default_value = "20")]
     min_distance: f64,
 
+    /// use the public_transport=stop_position osm stops rather than the
+    /// platforms, when a stop point has some (only when map-ntfs-with-osm
+    /// has been run with --osm-stop-tags accepting them)
+    #[structopt(long)]
+    prefer_stop_positions: bool,
+
+    /// keep the position of a stop point if its osm stops are farther apart
+    /// than this distance (in meters)
+    #[structopt(long)]
+    max_spread: Option<f64>,
+
+    /// refuse to move a stop point farther than this distance (in meters)
+    #[structopt(long)]
+    max_displacement: Option<f64>,
+
+    /// recompute the position of all the stop areas from their stop points,
+    /// and not only the ones of the moved stop points
+    #[structopt(long)]
+    recompute_all_stop_areas: bool,
+
+    /// output json file of the report of the refused moves
+    #[structopt(short, long, parse(from_os_str))]
+    report: Option<PathBuf>,
+
     /// current datetime
     #[structopt(
         short = "x",
@@ -43,7 +72,18 @@ fn run(opt: Opt) -> Result<()> {
 
     let model = ntfs::read(opt.input)?;
     let mut collections = model.into_collections();
-    improve_stop_positions::improve_with_pbf(&opt.pbf, &mut collections, opt.min_distance)?;
+    let policy = PositionPolicy {
+        min_distance: opt.min_distance,
+        prefer_stop_positions: opt.prefer_stop_positions,
+        max_spread: opt.max_spread,
+        max_displacement: opt.max_displacement,
+        recompute_all_stop_areas: opt.recompute_all_stop_areas,
+    };
+    let mut report = Report::default();
+    improve_stop_positions::improve_with_pbf(&opt.pbf, &mut collections, &policy, &mut report)?;
+    if let Some(report_path) = opt.report {
+        fs::write(report_path, serde_json::to_string_pretty(&report)?)?;
+    }
     let model = Model::new(collections)?;
     transit_model::ntfs::write(&model, opt.output, opt.current_datetime)?;
 
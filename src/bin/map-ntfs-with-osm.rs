@@ -1,10 +1,16 @@
 use chrono::{DateTime, FixedOffset};
 use failure::bail;
 use log::info;
-use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
 use structopt::StructOpt;
-use tartare_tools::{improve_stop_positions, Result};
+use tartare_tools::{
+    improve_stop_positions::{
+        self, CuratedMapping, GeoMatching, LineEnrichment, MatchingOptions, MatchingReport,
+        NetworkMapping, NetworkMatcher, OsmStopFilter, SequenceAlignment,
+    },
+    Result,
+};
 use transit_model::ntfs;
 
 #[derive(Debug, StructOpt)]
@@ -22,10 +28,19 @@ struct Opt {
     #[structopt(short, long, parse(from_os_str))]
     pbf: PathBuf,
 
-    /// networks mapping
+    /// networks mapping (like ntfs_network_id=osm_network_label, can be
+    /// repeated to give several labels to a network)
     #[structopt(short, long)]
     networks: Vec<String>,
 
+    /// json file of the networks mapping, giving for each ntfs network the
+    /// osm network labels, network regexes, operators or wikidata ids of
+    /// its osm lines (like {"network:TCL": [{"label": "TCL"}, {"regex":
+    /// "^Transports en Commun"}, {"operator": "Keolis Lyon"}, {"wikidata":
+    /// "Q1234"}]})
+    #[structopt(long, parse(from_os_str))]
+    networks_config: Option<PathBuf>,
+
     /// output directory
     #[structopt(short, long, parse(from_os_str))]
     output: PathBuf,
@@ -34,6 +49,59 @@ struct Opt {
     #[structopt(short, long)]
     force_double_stop_point_matching: bool,
 
+    /// also match stop points to the osm stops closer than this distance (in
+    /// meters) with a similar name, even on lines without osm relation
+    #[structopt(long)]
+    geo_matching_distance: Option<f64>,
+
+    /// match journey patterns with osm routes by aligning their stops, when
+    /// the alignment score (1 for identical sequences) is at least this value
+    #[structopt(long)]
+    alignment_min_score: Option<f64>,
+
+    /// distance (in meters) beyond which 2 stops are not considered close
+    /// when aligning journey patterns with osm routes
+    #[structopt(long, default_value = "100")]
+    alignment_max_distance: f64,
+
+    /// minimum name similarity (between 0 and 1) for the geographic matching
+    /// and the alignment
+    #[structopt(long, default_value = "0.8")]
+    min_name_similarity: f64,
+
+    /// osm tags of the stops (like railway=tram_stop), defaults to
+    /// public_transport=platform and highway=bus_stop
+    #[structopt(long)]
+    osm_stop_tags: Vec<String>,
+
+    /// also consider the osm ways as stops, and not only the nodes
+    #[structopt(long)]
+    include_osm_stop_ways: bool,
+
+    /// curated mapping (csv or json file written with --mapping-output),
+    /// whose matchings take precedence over the automatic ones
+    #[structopt(long, parse(from_os_str))]
+    curated_mapping: Option<PathBuf>,
+
+    /// fill the colors, text colors and missing names of the matched lines
+    /// from their osm lines
+    #[structopt(long)]
+    enrich_lines: bool,
+
+    /// overwrite the existing colors and names of the lines with
+    /// --enrich-lines
+    #[structopt(long, requires = "enrich-lines")]
+    force_line_enrichment: bool,
+
+    /// output file of the mapping between ntfs and osm objects (json if the
+    /// extension is .json, csv otherwise)
+    #[structopt(long, parse(from_os_str))]
+    mapping_output: Option<PathBuf>,
+
+    /// output json file of the matching report
+    #[structopt(short, long, parse(from_os_str))]
+    report: Option<PathBuf>,
+
     /// current datetime
     #[structopt(
         short = "x",
@@ -48,23 +116,80 @@ fn run(opt: Opt) -> Result<()> {
     info!("Launching map-ntfs-with-osm.");
 
     let model = ntfs::read(opt.input)?;
-    let mut ntfs_network_to_osm = HashMap::new();
+    let mut network_mapping = match opt.networks_config {
+        Some(path) => NetworkMapping::from_path(path)?,
+        None => NetworkMapping::default(),
+    };
     for network_map in opt.networks.iter() {
         let split: Vec<_> = network_map.split('=').collect();
-        match split.len() {
-            2 => ntfs_network_to_osm.insert(split[0], split[1]),
+        match split.as_slice() {
+            [ntfs_network_id, osm_network] => network_mapping.add(
+                ntfs_network_id,
+                NetworkMatcher::Label(osm_network.to_string()),
+            ),
             _ => bail!("networks mapping should be like ntfs_network_id=osm_network_label"),
         };
     }
-    if ntfs_network_to_osm.is_empty() {
+    if network_mapping.is_empty() {
         bail!("networks mapping should contain at least one mapping");
     }
+    let mut osm_stop_filter = OsmStopFilter {
+        include_ways: opt.include_osm_stop_ways,
+        ..Default::default()
+    };
+    if !opt.osm_stop_tags.is_empty() {
+        osm_stop_filter.tags = opt
+            .osm_stop_tags
+            .iter()
+            .map(|tag| {
+                let split: Vec<_> = tag.split('=').collect();
+                match split.as_slice() {
+                    [key, value] => Ok((key.to_string(), value.to_string())),
+                    _ => bail!("osm stop tags should be like key=value"),
+                }
+            })
+            .collect::<Result<_>>()?;
+    }
+    let min_name_similarity = opt.min_name_similarity;
+    let alignment_max_distance = opt.alignment_max_distance;
+    let options = MatchingOptions {
+        force_double_stop_point_matching: opt.force_double_stop_point_matching,
+        geo_matching: opt.geo_matching_distance.map(|max_distance| GeoMatching {
+            max_distance,
+            min_name_similarity,
+        }),
+        sequence_alignment: opt.alignment_min_score.map(|min_score| SequenceAlignment {
+            min_score,
+            max_distance: alignment_max_distance,
+            min_name_similarity,
+        }),
+        osm_stop_filter,
+        curated_mapping: match opt.curated_mapping {
+            Some(path) => CuratedMapping::from_path(path)?,
+            None => CuratedMapping::default(),
+        },
+        line_enrichment: if opt.enrich_lines {
+            Some(LineEnrichment {
+                force: opt.force_line_enrichment,
+            })
+        } else {
+            None
+        },
+    };
+    let mut report = MatchingReport::default();
     let enriched_model = improve_stop_positions::enrich_object_codes(
         &opt.pbf,
         model,
-        ntfs_network_to_osm,
-        opt.force_double_stop_point_matching,
+        network_mapping,
+        &options,
+        &mut report,
     )?;
+    if let Some(mapping_path) = opt.mapping_output {
+        improve_stop_positions::write_mapping(&report.mapping(), mapping_path)?;
+    }
+    if let Some(report_path) = opt.report {
+        fs::write(report_path, serde_json::to_string_pretty(&report)?)?;
+    }
     transit_model::ntfs::write(&enriched_model, opt.output, opt.current_datetime)?;
 
     Ok(())
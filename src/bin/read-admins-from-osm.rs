@@ -0,0 +1,77 @@
+use chrono::{DateTime, FixedOffset};
+use log::info;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use tartare_tools::{
+    read_admins::{self, AdminOptions, AdminOutput},
+    report::Report,
+    Result,
+};
+use transit_model::{ntfs, Model};
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "read-admins-from-osm",
+    about = "Assign the stop areas to the administrative regions of OpenStreetMap.",
+    rename_all = "kebab-case"
+)]
+struct Opt {
+    /// input directory.
+    #[structopt(short, long, parse(from_os_str), default_value = ".")]
+    input: PathBuf,
+
+    /// osm pbf file.
+    #[structopt(short, long, parse(from_os_str))]
+    pbf: PathBuf,
+
+    /// output directory
+    #[structopt(short, long, parse(from_os_str))]
+    output: PathBuf,
+
+    /// admin levels of the osm administrative boundaries (8 for the cities
+    /// and 6 for the departments in France), can be repeated
+    #[structopt(long, default_value = "8")]
+    admin_level: Vec<u32>,
+
+    /// write the admins as codes of the stop areas or in admin_stations.txt
+    #[structopt(long, default_value = "codes", possible_values = &["codes", "admin-stations"])]
+    admin_output: AdminOutput,
+
+    /// output json file of the report
+    #[structopt(short, long, parse(from_os_str))]
+    report: Option<PathBuf>,
+
+    /// current datetime
+    #[structopt(
+        short = "x",
+        long,
+        parse(try_from_str),
+        default_value = &transit_model::CURRENT_DATETIME
+    )]
+    current_datetime: DateTime<FixedOffset>,
+}
+
+fn run(opt: Opt) -> Result<()> {
+    info!("Launching read-admins-from-osm.");
+
+    let model = ntfs::read(opt.input)?;
+    let mut collections = model.into_collections();
+    let options = AdminOptions {
+        levels: opt.admin_level.into_iter().collect(),
+        output: opt.admin_output,
+    };
+    let mut report = Report::default();
+    read_admins::from_osm(&opt.pbf, &mut collections, &options, &mut report)?;
+    if let Some(report_path) = opt.report {
+        fs::write(report_path, serde_json::to_string_pretty(&report)?)?;
+    }
+    let model = Model::new(collections)?;
+    transit_model::ntfs::write(&model, opt.output, opt.current_datetime)?;
+
+    Ok(())
+}
+
+fn main() {
+    tartare_tools::runner::launch_run(run);
+}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, FixedOffset};
+use log::info;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use tartare_tools::{
+    read_equipments::{self, Precedence},
+    report::Report,
+    Result,
+};
+use transit_model::{ntfs, Model};
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "read-equipments-from-osm",
+    about = "Read the equipments of the stop points from OpenStreetMap.",
+    rename_all = "kebab-case"
+)]
+struct Opt {
+    /// input directory.
+    #[structopt(short, long, parse(from_os_str), default_value = ".")]
+    input: PathBuf,
+
+    /// osm pbf file.
+    #[structopt(short, long, parse(from_os_str))]
+    pbf: PathBuf,
+
+    /// output directory
+    #[structopt(short, long, parse(from_os_str))]
+    output: PathBuf,
+
+    /// values kept when the ntfs and osm disagree: with `ntfs`, osm only
+    /// fills the unknown equipment properties
+    #[structopt(long, default_value = "ntfs", possible_values = &["ntfs", "osm"])]
+    precedence: Precedence,
+
+    /// output json file of the report
+    #[structopt(short, long, parse(from_os_str))]
+    report: Option<PathBuf>,
+
+    /// current datetime
+    #[structopt(
+        short = "x",
+        long,
+        parse(try_from_str),
+        default_value = &transit_model::CURRENT_DATETIME
+    )]
+    current_datetime: DateTime<FixedOffset>,
+}
+
+fn run(opt: Opt) -> Result<()> {
+    info!("Launching read-equipments-from-osm.");
+
+    let model = ntfs::read(opt.input)?;
+    let mut collections = model.into_collections();
+    let mut report = Report::default();
+    read_equipments::from_osm(&opt.pbf, &mut collections, opt.precedence, &mut report)?;
+    if let Some(report_path) = opt.report {
+        fs::write(report_path, serde_json::to_string_pretty(&report)?)?;
+    }
+    let model = Model::new(collections)?;
+    transit_model::ntfs::write(&model, opt.output, opt.current_datetime)?;
+
+    Ok(())
+}
+
+fn main() {
+    tartare_tools::runner::launch_run(run);
+}
@@ -1,8 +1,13 @@
 use chrono::{DateTime, FixedOffset};
 use log::info;
+use std::fs;
 use std::path::PathBuf;
 use structopt::StructOpt;
-use tartare_tools::{read_shapes, Result};
+use tartare_tools::{
+    read_shapes::{self, GeometryMode, MapMatching, ShapeOptions},
+    report::Report,
+    Result,
+};
 use transit_model::{ntfs, Model};
 
 #[derive(Debug, StructOpt)]
@@ -24,6 +29,35 @@ struct Opt {
     #[structopt(short, long, parse(from_os_str))]
     output: PathBuf,
 
+    /// stitch the shapes of the routes into a single linestring, oriented and
+    /// cut to their first and last stops when these are closer than this
+    /// distance (in meters)
+    #[structopt(long)]
+    fit_routes_to_stops: Option<f64>,
+
+    /// what to do with the lines and routes already having a geometry: keep
+    /// it, replace it, or keep the longer one or the one closer to the stops
+    #[structopt(
+        long,
+        default_value = "keep",
+        possible_values = &["keep", "replace", "keep-longer", "keep-closer"]
+    )]
+    geometry_mode: GeometryMode,
+
+    /// output json file of the report
+    #[structopt(short, long, parse(from_os_str))]
+    report: Option<PathBuf>,
+
+    /// also generate the shapes of the trips without osm relation, by
+    /// routing between their stops on the osm ways
+    #[structopt(long)]
+    map_matching: bool,
+
+    /// maximum distance (in meters) between a stop point and the osm way it
+    /// is snapped to, for the map matching
+    #[structopt(long, default_value = "50")]
+    max_snapping_distance: f64,
+
     /// current datetime
     #[structopt(
         short = "x",
@@ -39,7 +73,21 @@ fn run(opt: Opt) -> Result<()> {
 
     let model = ntfs::read(opt.input)?;
     let mut collections = model.into_collections();
-    read_shapes::from_osm(&opt.pbf, &mut collections)?;
+    let options = ShapeOptions {
+        fit_routes_to_stops: opt.fit_routes_to_stops,
+        geometry_mode: opt.geometry_mode,
+    };
+    let mut report = Report::default();
+    read_shapes::from_osm(&opt.pbf, &mut collections, &options, &mut report)?;
+    if opt.map_matching {
+        let map_matching = MapMatching {
+            max_snapping_distance: opt.max_snapping_distance,
+        };
+        read_shapes::from_osm_graph(&opt.pbf, &mut collections, &map_matching)?;
+    }
+    if let Some(report_path) = opt.report {
+        fs::write(report_path, serde_json::to_string_pretty(&report)?)?;
+    }
     let model = Model::new(collections)?;
     transit_model::ntfs::write(&model, opt.output, opt.current_datetime)?;
 
@@ -0,0 +1,199 @@
+//! Helpers to update the equipments of the stop points.
+use transit_model::{
+    model::Collections,
+    objects::{Availability, Equipment},
+    PrefixConfiguration,
+};
+use typed_index_collection::CollectionWithId;
+
+pub const APPROPRIATE_ESCORT: &str = "appropriate_escort";
+pub const APPROPRIATE_SIGNAGE: &str = "appropriate_signage";
+pub const AUDIBLE_ANNOUNCEMENT: &str = "audible_announcement";
+pub const BIKE_ACCEPTED: &str = "bike_accepted";
+pub const BIKE_DEPOT: &str = "bike_depot";
+pub const ELEVATOR: &str = "elevator";
+pub const ESCALATOR: &str = "escalator";
+pub const SHELTERED: &str = "sheltered";
+pub const VISUAL_ANNOUNCEMENT: &str = "visual_announcement";
+pub const WHEELCHAIR_BOARDING: &str = "wheelchair_boarding";
+
+/// Names of the equipment properties of the stop points.
+pub const STOP_POINT_EQUIPMENTS: &[&str] = &[
+    APPROPRIATE_ESCORT,
+    APPROPRIATE_SIGNAGE,
+    AUDIBLE_ANNOUNCEMENT,
+    BIKE_ACCEPTED,
+    BIKE_DEPOT,
+    ELEVATOR,
+    ESCALATOR,
+    SHELTERED,
+    VISUAL_ANNOUNCEMENT,
+    WHEELCHAIR_BOARDING,
+];
+
+/// The property of an equipment with the name `property_name`, one of
+/// `STOP_POINT_EQUIPMENTS`.
+pub fn equipment_property_mut<'a>(
+    equipment: &'a mut Equipment,
+    property_name: &str,
+) -> Option<&'a mut Availability> {
+    match property_name {
+        APPROPRIATE_ESCORT => Some(&mut equipment.appropriate_escort),
+        APPROPRIATE_SIGNAGE => Some(&mut equipment.appropriate_signage),
+        AUDIBLE_ANNOUNCEMENT => Some(&mut equipment.audible_announcement),
+        BIKE_ACCEPTED => Some(&mut equipment.bike_accepted),
+        BIKE_DEPOT => Some(&mut equipment.bike_depot),
+        ELEVATOR => Some(&mut equipment.elevator),
+        ESCALATOR => Some(&mut equipment.escalator),
+        SHELTERED => Some(&mut equipment.sheltered),
+        VISUAL_ANNOUNCEMENT => Some(&mut equipment.visual_announcement),
+        WHEELCHAIR_BOARDING => Some(&mut equipment.wheelchair_boarding),
+        _ => None,
+    }
+}
+
+/// The prefix configuration of the objects created in `collections`,
+/// deduced from the id of a dataset.
+pub fn get_prefix(collections: &Collections) -> PrefixConfiguration {
+    collections
+        .datasets
+        .values()
+        // Take any dataset, no reason to chose one in particular
+        .next()
+        .map(|dataset| &dataset.id)
+        .map(|id| id.split(':').collect::<Vec<_>>())
+        .map(|split| {
+            let mut prefix_conf = PrefixConfiguration::default();
+            if split.len() > 1 {
+                // If the original `dataset id` contains ':', the first part
+                // must be the `data_prefix` and we assume the last part is the
+                // `schedule_subprefix`. This should work fine for the following cases:
+                // - `IDFM:a1b2c3d4e5f6g7h8`
+                // - `IDFM:RATP:a1b2c3d4e5f6g7h8`
+                prefix_conf.set_data_prefix(split[0]);
+                let subprefix = split[split.len() - 1];
+                let truncate_index = usize::min(subprefix.len(), 6);
+                prefix_conf.set_schedule_subprefix(&subprefix[0..truncate_index]);
+            }
+            prefix_conf
+        })
+        .unwrap_or_default()
+}
+
+/// Generates an id not used in `collection`.
+pub fn generate_object_id<T>(
+    collection: &mut CollectionWithId<T>,
+    prefix_conf: &PrefixConfiguration,
+) -> String {
+    let mut inc = 0;
+    let mut available = false;
+    let mut id = String::new();
+    while !available {
+        inc += 1;
+        id = prefix_conf.schedule_prefix(inc.to_string().as_str());
+        available = !collection.contains_id(&id);
+    }
+    id
+}
+
+/// Returns the id of an equipment similar to `equipment`, creating it if
+/// there is none.
+pub fn get_id_or_create_equipment(
+    equipment: Equipment,
+    collection_equipments: &mut CollectionWithId<Equipment>,
+    prefix_conf: &PrefixConfiguration,
+) -> String {
+    let similar_equipments: Vec<&Equipment> = collection_equipments
+        .values()
+        .filter(|eq| eq.is_similar(&equipment))
+        .collect();
+
+    if !similar_equipments.is_empty() {
+        // Similar equipments may exist, especially after a merge of ntfs (with different prefixes)
+        // We take the first one that matches. Rare case.
+        return similar_equipments[0].id.clone();
+    }
+
+    let equipment_id = generate_object_id(collection_equipments, prefix_conf);
+
+    collection_equipments
+        .push(Equipment {
+            id: equipment_id.clone(),
+            ..equipment
+        })
+        .unwrap();
+    equipment_id
+}
+
+/// The equipment of a stop point, or a default equipment if it has none.
+pub fn stop_point_equipment(collections: &Collections, stop_point_id: &str) -> Equipment {
+    collections
+        .stop_points
+        .get(stop_point_id)
+        .and_then(|sp| sp.equipment_id.as_ref())
+        .and_then(|eq_id| collections.equipments.get(eq_id).cloned())
+        .unwrap_or_default()
+}
+
+/// Sets the equipment of a stop point, reusing a similar equipment if there
+/// is one.
+pub fn set_stop_point_equipment(
+    collections: &mut Collections,
+    stop_point_id: &str,
+    equipment: Equipment,
+    prefix_conf: &PrefixConfiguration,
+) {
+    if let Some(mut sp) = collections.stop_points.get_mut(stop_point_id) {
+        sp.equipment_id = Some(get_id_or_create_equipment(
+            equipment,
+            &mut collections.equipments,
+            prefix_conf,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod get_prefix {
+        use super::*;
+        use transit_model::objects::Dataset;
+
+        fn schedule_prefix_from_dataset_id(dataset_id: &str, id: &str) -> String {
+            let mut collections = Collections::default();
+            collections.datasets = CollectionWithId::from(Dataset {
+                id: dataset_id.to_string(),
+                ..Default::default()
+            });
+            let prefix_conf = get_prefix(&collections);
+            prefix_conf.schedule_prefix(id)
+        }
+
+        #[test]
+        fn no_data_prefix() {
+            let prefixed_id = schedule_prefix_from_dataset_id("a1b2c3d4e5f6g7h8", "foo");
+            assert_eq!("foo", prefixed_id);
+        }
+
+        #[test]
+        fn with_data_prefix() {
+            let prefixed_id = schedule_prefix_from_dataset_id("IDFM:a1b2c3d4e5f6g7h8", "foo");
+            assert_eq!("IDFM:a1b2c3:foo", prefixed_id);
+        }
+
+        #[test]
+        fn with_multiple_data_prefix() {
+            let prefixed_id = schedule_prefix_from_dataset_id("IDFM:RATP:a1b2c3d4e5f6g7h8", "foo");
+            assert_eq!("IDFM:a1b2c3:foo", prefixed_id);
+        }
+    }
+
+    #[test]
+    fn equipment_properties() {
+        let mut equipment = Equipment::default();
+        *equipment_property_mut(&mut equipment, SHELTERED).unwrap() = Availability::Available;
+        assert_eq!(Availability::Available, equipment.sheltered);
+        assert!(equipment_property_mut(&mut equipment, "air_conditioned").is_none());
+    }
+}
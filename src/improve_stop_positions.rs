@@ -1,3 +1,28 @@
+mod alignment;
+mod geo_matching;
+mod line_enrichment;
+mod mapping;
+mod matching_report;
+mod network_mapping;
+mod osm_stop_filter;
+mod similarity;
+
+pub use self::alignment::SequenceAlignment;
+pub use self::geo_matching::GeoMatching;
+pub use self::line_enrichment::LineEnrichment;
+pub use self::mapping::{
+    read_mapping, write_mapping, CuratedMapping, MappingRecord, MatchingMethod, NtfsObjectType,
+};
+pub use self::matching_report::{Coverage, MatchingReport, NetworkCoverage, ReportCategory};
+pub use self::network_mapping::{NetworkMapping, NetworkMatcher};
+pub use self::osm_stop_filter::OsmStopFilter;
+pub use self::similarity::name_similarity;
+
+use self::alignment::align;
+use self::geo_matching::{find_osm_stop, OsmStopIndex};
+use self::line_enrichment::enrich_line;
+use self::similarity::sanitize;
+use crate::report::Report;
 use crate::Result;
 use failure::format_err;
 use geo::algorithm::centroid::Centroid;
@@ -7,9 +32,8 @@ use osm_transit_extractor::*;
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::Path;
 use transit_model::model::{Collections, Model};
-use transit_model::objects::{Coord, StopPoint as NtfsStopPoint};
+use transit_model::objects::{Coord, Route as NtfsRoute, StopPoint as NtfsStopPoint};
 use typed_index_collection::{CollectionWithId, Idx};
-use unidecode::unidecode;
 
 fn point_list_to_centroid_coord(point_list: Vec<Point<f64>>) -> Coord {
     let multi_point: MultiPoint<_> = point_list.into();
@@ -26,22 +50,100 @@ fn compare_almost_equal(a: &str, b: &str) -> bool {
     sanitize(a) == sanitize(b)
 }
 
-fn sanitize(broken: &str) -> String {
-    unidecode(&broken).to_lowercase()
+/// Options of the matching between NTFS and OSM objects.
+#[derive(Debug, Clone, Default)]
+pub struct MatchingOptions {
+    /// Keep the stop points mapped to 2 OSM stops (and the OSM stops mapped
+    /// to 2 stop points).
+    pub force_double_stop_point_matching: bool,
+    /// Also match the stop points not matched through an OSM route, by
+    /// proximity and name similarity.
+    pub geo_matching: Option<GeoMatching>,
+    /// Match the journey patterns with the OSM routes by aligning their
+    /// stops, instead of requiring the same number of stops.
+    pub sequence_alignment: Option<SequenceAlignment>,
+    /// The OSM objects considered as stops.
+    pub osm_stop_filter: OsmStopFilter,
+    /// Matchings taking precedence over the automatic ones.
+    pub curated_mapping: CuratedMapping,
+    /// Also update the colors and names of the matched lines.
+    pub line_enrichment: Option<LineEnrichment>,
+}
+
+fn insert_mapping(
+    map_ntfs_to_osm_points: &mut StopPointMap,
+    map_osm_to_ntfs_points: &mut StopPointMap,
+    ntfs_stop_point_id: &str,
+    osm_stop_point_id: &str,
+) {
+    map_ntfs_to_osm_points
+        .entry(ntfs_stop_point_id.to_string())
+        .or_insert_with(BTreeSet::new)
+        .insert(osm_stop_point_id.to_string());
+    map_osm_to_ntfs_points
+        .entry(osm_stop_point_id.to_string())
+        .or_insert_with(BTreeSet::new)
+        .insert(ntfs_stop_point_id.to_string());
 }
 
-pub fn enrich_object_codes<S: ::std::hash::BuildHasher>(
+fn remove_mapping(
+    map_ntfs_to_osm_points: &mut StopPointMap,
+    map_osm_to_ntfs_points: &mut StopPointMap,
+    ntfs_stop_point_id: &str,
+) {
+    for osm_stop_point_id in map_ntfs_to_osm_points
+        .remove(ntfs_stop_point_id)
+        .unwrap_or_default()
+    {
+        if let Some(ntfs_stop_point_ids) = map_osm_to_ntfs_points.get_mut(&osm_stop_point_id) {
+            ntfs_stop_point_ids.remove(ntfs_stop_point_id);
+            if ntfs_stop_point_ids.is_empty() {
+                map_osm_to_ntfs_points.remove(&osm_stop_point_id);
+            }
+        }
+    }
+}
+
+/// Adds the ids of the matching OSM objects as codes of the lines, routes
+/// and stop points of the mapped networks. The networks are mapped either
+/// through a `NetworkMapping` or through a map of NTFS network ids to OSM
+/// network labels.
+///
+/// The matched and rejected objects, as well as the coverage of each
+/// network, are recorded in `report`.
+pub fn enrich_object_codes<N: Into<NetworkMapping>>(
     osm_pbf_path: &Path,
     model: Model,
-    ntfs_network_to_osm: HashMap<&str, &str, S>,
-    force_double_stop_point_matching: bool,
+    network_mapping: N,
+    options: &MatchingOptions,
+    report: &mut MatchingReport,
 ) -> Result<Model> {
-    for (ntfs_network_id, _) in ntfs_network_to_osm.iter() {
-        if model.networks.get(&ntfs_network_id).is_none() {
+    let network_mapping = network_mapping.into();
+    let model = match_objects(osm_pbf_path, model, &network_mapping, options, report)?;
+    report.compute_coverage(&model, &network_mapping);
+    Ok(model)
+}
+
+fn match_objects(
+    osm_pbf_path: &Path,
+    model: Model,
+    network_mapping: &NetworkMapping,
+    options: &MatchingOptions,
+    report: &mut MatchingReport,
+) -> Result<Model> {
+    for ntfs_network_id in network_mapping.network_ids() {
+        if model.networks.get(ntfs_network_id).is_none() {
             warn!(
                 "The network (id={:?}) doesn't exist in the provided NTFS",
                 &ntfs_network_id
             );
+            report.add_rejection(
+                format!(
+                    "network {:?} doesn't exist in the provided NTFS",
+                    ntfs_network_id
+                ),
+                ReportCategory::UnknownNetwork,
+            );
         }
     }
     let mut parsed_pbf = parse_osm_pbf(
@@ -54,154 +156,418 @@ pub fn enrich_object_codes<S: ::std::hash::BuildHasher>(
     let mut ntfs_routes = model.routes.clone();
     let mut ntfs_stop_points = model.stop_points.clone();
 
+    // Without osm lines or routes, only the geographic matching and the
+    // curated stop points can still map some stop points
+    let stop_points_only_matched_by_lines = options.geo_matching.is_none()
+        && options
+            .curated_mapping
+            .objects(NtfsObjectType::StopPoint)
+            .next()
+            .is_none();
+
     //in osm-transit-extractor version "0.4.0" None is never return but an empty vec
-    let osm_lines = if let Some(lines) = objects.lines.as_ref().filter(|v| !v.is_empty()) {
-        lines
-    } else {
+    let osm_lines = objects.lines.as_deref().unwrap_or_default();
+    if osm_lines.is_empty() {
         warn!(
             "no lines found in osm for file {}",
             osm_pbf_path.to_str().unwrap()
         );
-        return Ok(model);
-    };
+        if stop_points_only_matched_by_lines {
+            return Ok(model);
+        }
+    }
 
     //in osm-transit-extractor version "0.4.0" None is never return but an empty vec
-    let osm_routes_map = if let Some(osm_routes) = objects.routes.as_ref().filter(|v| !v.is_empty())
-    {
-        osm_routes
-            .iter()
-            .map(|r| (&r.id, r))
-            .collect::<HashMap<_, _>>()
-    } else {
+    let osm_routes_map = objects
+        .routes
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .map(|r| (&r.id, r))
+        .collect::<HashMap<_, _>>();
+    if osm_routes_map.is_empty() {
         warn!(
             "no routes found in osm for file {}",
             osm_pbf_path.to_str().unwrap()
         );
-        return Ok(model);
-    };
+        if stop_points_only_matched_by_lines {
+            return Ok(model);
+        }
+    }
 
     let osm_stops_map = objects
         .stop_points
         .iter()
-        .filter(|&sp| {
-            sp.id
-                .split(':')
-                .next()
-                .map(|obj_type| obj_type == "node")
-                .unwrap_or_default()
-        })
-        .filter(|&sp| {
-            sp.all_osm_tags.contains("public_transport", "plateform")
-                || sp.all_osm_tags.contains("highway", "bus_stop")
-        })
+        .filter(|&sp| options.osm_stop_filter.accepts(&sp.id, &sp.all_osm_tags))
         .map(|sp| (&sp.id, sp))
         .collect::<HashMap<_, _>>();
     let mut map_ntfs_to_osm_points: StopPointMap = HashMap::new();
     let mut map_osm_to_ntfs_points: StopPointMap = HashMap::new();
+    // Stop points of the matched journey patterns without a stop of the
+    // same name in the OSM route
+    let mut name_mismatches: HashMap<Idx<NtfsStopPoint>, &str> = HashMap::new();
+    // How each (NTFS stop point, OSM stop) pair has been matched
+    let mut stop_matchings: HashMap<(String, String), (MatchingMethod, Option<f64>)> =
+        HashMap::new();
+    // NTFS routes matched with at least one OSM route
+    let mut matched_route_ids: HashSet<String> = HashSet::new();
     for line in ntfs_lines.iter_mut() {
-        let osm_network_of_line = match ntfs_network_to_osm.get(line.network_id.as_str()) {
-            Some(&osm_network) => osm_network,
-            None => continue,
-        };
-        if let Some(code) = &line.code {
-            let corresponding_osm_lines = osm_lines
+        if !network_mapping.contains(&line.network_id) {
+            continue;
+        }
+        let line_idx = model.lines.get_idx(&line.id).unwrap();
+        let curated_osm_line_ids = options
+            .curated_mapping
+            .osm_ids(NtfsObjectType::Line, &line.id);
+        let corresponding_osm_lines = if let Some(osm_line_ids) = curated_osm_line_ids {
+            osm_lines
+                .iter()
+                .filter(|l| osm_line_ids.contains(&l.id))
+                .collect::<Vec<_>>()
+        } else if let Some(code) = &line.code {
+            osm_lines
                 .iter()
                 .filter(|l| {
-                    compare_almost_equal(&l.network, &osm_network_of_line)
+                    network_mapping.matches(&line.network_id, &l.all_osm_tags)
                         && l.all_osm_tags.contains("ref", &code)
                 })
+                .collect::<Vec<_>>()
+        } else {
+            report.add_rejection(
+                format!("line {:?} has no code to match the osm ref", &line.id),
+                ReportCategory::LineWithoutRef,
+            );
+            continue;
+        };
+        let line_method = if curated_osm_line_ids.is_some() {
+            MatchingMethod::Curated
+        } else {
+            MatchingMethod::Ref
+        };
+        if corresponding_osm_lines.len() == 1 {
+            let corresponding_osm_line = corresponding_osm_lines[0];
+            report.add_matched_line(&line.id, &corresponding_osm_line.id, line_method);
+            line.codes
+                .insert(("osm_line_id".to_string(), corresponding_osm_line.id.clone()));
+            line.codes.insert((
+                "osm_network".to_string(),
+                corresponding_osm_line.network.clone(),
+            ));
+            line.codes.insert((
+                "osm_company".to_string(),
+                corresponding_osm_line.operator.clone(),
+            ));
+            let routes_of_line = osm_routes_map
+                .iter()
+                .filter_map(|(&id, &route)| {
+                    if corresponding_osm_line.routes_id.contains(id) {
+                        Some(route)
+                    } else {
+                        None
+                    }
+                })
                 .collect::<Vec<_>>();
-            if corresponding_osm_lines.len() == 1 {
-                let corresponding_osm_line = corresponding_osm_lines[0];
-                line.codes
-                    .insert(("osm_line_id".to_string(), corresponding_osm_line.id.clone()));
-                line.codes.insert((
-                    "osm_network".to_string(),
-                    corresponding_osm_line.network.clone(),
-                ));
-                line.codes.insert((
-                    "osm_company".to_string(),
-                    corresponding_osm_line.operator.clone(),
-                ));
-                let routes_of_line = osm_routes_map
+            if let Some(line_enrichment) = &options.line_enrichment {
+                let route_tags: Vec<_> = routes_of_line
                     .iter()
-                    .filter_map(|(&id, &route)| {
-                        if corresponding_osm_line.routes_id.contains(id) {
-                            Some(route)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                let vj_patterns: HashSet<(Vec<Idx<NtfsStopPoint>>, &str, &str)> = model
-                    .get_corresponding_from_idx(model.lines.get_idx(&line.id).unwrap())
-                    .iter()
-                    .map(|vj_idx| &model.vehicle_journeys[*vj_idx])
-                    .fold(HashSet::new(), |mut acc, vj| {
-                        acc.insert((
-                            vj.stop_times.iter().map(|st| st.stop_point_idx).collect(),
-                            &model.stop_points[vj.stop_times.last().unwrap().stop_point_idx].name,
-                            &vj.route_id,
-                        ));
-                        acc
-                    });
+                    .map(|route| &route.all_osm_tags)
+                    .collect();
+                enrich_line(
+                    line,
+                    &corresponding_osm_line.all_osm_tags,
+                    &route_tags,
+                    line_enrichment,
+                    report,
+                );
+            }
+            let vj_patterns: HashSet<(Vec<Idx<NtfsStopPoint>>, &str, &str)> = model
+                .get_corresponding_from_idx(line_idx)
+                .iter()
+                .map(|vj_idx| &model.vehicle_journeys[*vj_idx])
+                .fold(HashSet::new(), |mut acc, vj| {
+                    acc.insert((
+                        vj.stop_times.iter().map(|st| st.stop_point_idx).collect(),
+                        &model.stop_points[vj.stop_times.last().unwrap().stop_point_idx].name,
+                        &vj.route_id,
+                    ));
+                    acc
+                });
 
-                for route in routes_of_line {
-                    let route_points = route
-                        .ordered_route_points
-                        .iter()
-                        .filter_map(|r| osm_stops_map.get(&r.stop_point_id))
-                        .collect::<Vec<_>>();
-                    let corresponding_vj_patterns = vj_patterns
-                        .iter()
-                        .filter(|(stops, destination, _)| {
-                            stops.len() == route_points.len()
-                                && compare_almost_equal(destination, &route.destination)
-                        })
-                        .collect::<Vec<_>>();
-                    for (stop_points_idx, _, ntfs_route_id) in corresponding_vj_patterns {
-                        let mut ntfs_route = ntfs_routes.get_mut(ntfs_route_id).unwrap();
-                        ntfs_route
-                            .codes
-                            .insert(("osm_route_id".to_string(), route.id.clone()));
-                        for &&route_point in &route_points {
-                            for stop_point_idx in stop_points_idx {
-                                let ntfs_stop_point = ntfs_stop_points.index_mut(*stop_point_idx);
-                                if compare_almost_equal(&ntfs_stop_point.name, &route_point.name) {
-                                    map_ntfs_to_osm_points
-                                        .entry(ntfs_stop_point.id.clone())
-                                        .or_insert_with(BTreeSet::new)
-                                        .insert(route_point.id.clone());
-                                    map_osm_to_ntfs_points
-                                        .entry(route_point.id.clone())
-                                        .or_insert_with(BTreeSet::new)
-                                        .insert(ntfs_stop_point.id.clone());
-                                }
+            for route in routes_of_line {
+                let route_points = route
+                    .ordered_route_points
+                    .iter()
+                    .filter_map(|r| osm_stops_map.get(&r.stop_point_id))
+                    .collect::<Vec<_>>();
+                // The journey patterns of a curated route are only matched
+                // with its curated OSM routes, whatever their destination
+                // and their score
+                let candidate_patterns = vj_patterns.iter().filter_map(
+                    |(stop_points_idx, destination, ntfs_route_id)| match options
+                        .curated_mapping
+                        .osm_ids(NtfsObjectType::Route, ntfs_route_id)
+                    {
+                        Some(osm_route_ids) if osm_route_ids.contains(&route.id) => {
+                            Some((stop_points_idx, ntfs_route_id, true))
+                        }
+                        Some(_) => None,
+                        None if compare_almost_equal(destination, &route.destination) => {
+                            Some((stop_points_idx, ntfs_route_id, false))
+                        }
+                        None => None,
+                    },
+                );
+                for (stop_points_idx, ntfs_route_id, is_curated) in candidate_patterns {
+                    let (stop_pairs, score) = match &options.sequence_alignment {
+                        None => {
+                            if !is_curated && stop_points_idx.len() != route_points.len() {
+                                continue;
                             }
+                            let stop_pairs = stop_points_idx
+                                .iter()
+                                .flat_map(|&stop_point_idx| {
+                                    route_points
+                                        .iter()
+                                        .map(move |&&route_point| (stop_point_idx, route_point))
+                                })
+                                .filter(|(stop_point_idx, route_point)| {
+                                    compare_almost_equal(
+                                        &model.stop_points[*stop_point_idx].name,
+                                        &route_point.name,
+                                    )
+                                })
+                                .collect::<Vec<_>>();
+                            (stop_pairs, None)
                         }
+                        Some(sequence_alignment) => {
+                            let alignment = align(
+                                &stop_points_idx[..],
+                                &route_points[..],
+                                |&idx, &&route_point| {
+                                    sequence_alignment
+                                        .stop_similarity(&model.stop_points[idx], route_point)
+                                },
+                            );
+                            if !is_curated && alignment.score < sequence_alignment.min_score {
+                                continue;
+                            }
+                            let stop_pairs = alignment
+                                .pairs
+                                .into_iter()
+                                .map(|(i, j)| (stop_points_idx[i], *route_points[j]))
+                                .filter(|(stop_point_idx, route_point)| {
+                                    name_similarity(
+                                        &model.stop_points[*stop_point_idx].name,
+                                        &route_point.name,
+                                    ) >= sequence_alignment.min_name_similarity
+                                })
+                                .collect::<Vec<_>>();
+                            (stop_pairs, Some(alignment.score))
+                        }
+                    };
+                    for stop_point_idx in stop_points_idx {
+                        if stop_pairs.iter().all(|(idx, _)| idx != stop_point_idx) {
+                            name_mismatches.insert(*stop_point_idx, &route.id);
+                        }
+                    }
+                    let stop_method = if score.is_some() {
+                        MatchingMethod::Alignment
+                    } else {
+                        MatchingMethod::StopSequence
+                    };
+                    let route_method = if is_curated {
+                        MatchingMethod::Curated
+                    } else {
+                        stop_method
+                    };
+                    report.add_matched_route(ntfs_route_id, &route.id, route_method, score);
+                    matched_route_ids.insert(ntfs_route_id.to_string());
+                    // The alignment score is only kept in the matching report,
+                    // the codes only hold osm ids
+                    let mut ntfs_route = ntfs_routes.get_mut(ntfs_route_id).unwrap();
+                    ntfs_route
+                        .codes
+                        .insert(("osm_route_id".to_string(), route.id.clone()));
+                    for (stop_point_idx, route_point) in stop_pairs {
+                        let ntfs_stop_point = &model.stop_points[stop_point_idx];
+                        insert_mapping(
+                            &mut map_ntfs_to_osm_points,
+                            &mut map_osm_to_ntfs_points,
+                            &ntfs_stop_point.id,
+                            &route_point.id,
+                        );
+                        let stop_score = score
+                            .map(|_| name_similarity(&ntfs_stop_point.name, &route_point.name));
+                        stop_matchings
+                            .entry((ntfs_stop_point.id.clone(), route_point.id.clone()))
+                            .or_insert((stop_method, stop_score));
                     }
                 }
+            }
+            let ntfs_routes_of_line: BTreeSet<Idx<NtfsRoute>> =
+                model.get_corresponding_from_idx(line_idx);
+            for route_idx in ntfs_routes_of_line {
+                let route_id = &model.routes[route_idx].id;
+                if !matched_route_ids.contains(route_id) {
+                    report.add_rejection(
+                        format!(
+                            "route {:?} doesn't match any osm route of line {:?}",
+                            route_id, corresponding_osm_line.id
+                        ),
+                        ReportCategory::RouteNotMatched,
+                    );
+                }
+            }
+        } else {
+            info!(
+                "found {} osm lines corresponding to line {:?}",
+                corresponding_osm_lines.len(),
+                &line.id
+            );
+            let category = if corresponding_osm_lines.is_empty() {
+                ReportCategory::OsmLineNotFound
+            } else {
+                ReportCategory::SeveralOsmLines
+            };
+            let message = if curated_osm_line_ids.is_some() {
+                if corresponding_osm_lines.is_empty() {
+                    format!("curated osm line not found for line {:?}", &line.id)
+                } else {
+                    format!(
+                        "found {} curated osm lines corresponding to line {:?}",
+                        corresponding_osm_lines.len(),
+                        &line.id
+                    )
+                }
             } else {
-                info!(
-                    "found {} osm lines corresponding to line {:?}",
+                format!(
+                    "found {} osm lines with ref {:?} corresponding to line {:?}",
                     corresponding_osm_lines.len(),
+                    line.code.as_deref().unwrap_or_default(),
                     &line.id
+                )
+            };
+            report.add_rejection(message, category);
+        }
+    }
+    let network_stop_points = stop_points_of_networks(&model, network_mapping);
+    if let Some(geo_matching) = &options.geo_matching {
+        let index = OsmStopIndex::new(osm_stops_map.values().cloned(), geo_matching.max_distance);
+        let mut nb_geo_matches = 0;
+        for &stop_point_idx in &network_stop_points {
+            let ntfs_stop_point = &model.stop_points[stop_point_idx];
+            if map_ntfs_to_osm_points.contains_key(&ntfs_stop_point.id) {
+                continue;
+            }
+            if let Some(osm_stop) = find_osm_stop(
+                &index,
+                &ntfs_stop_point.coord,
+                &ntfs_stop_point.name,
+                geo_matching,
+            ) {
+                insert_mapping(
+                    &mut map_ntfs_to_osm_points,
+                    &mut map_osm_to_ntfs_points,
+                    &ntfs_stop_point.id,
+                    &osm_stop.id,
                 );
+                stop_matchings.insert(
+                    (ntfs_stop_point.id.clone(), osm_stop.id.clone()),
+                    (
+                        MatchingMethod::Proximity,
+                        Some(name_similarity(&ntfs_stop_point.name, &osm_stop.name)),
+                    ),
+                );
+                nb_geo_matches += 1;
             }
         }
+        info!(
+            "{} stop points matched by proximity and name similarity",
+            nb_geo_matches
+        );
     }
+    // The curated stop points are left out of the automatic mapping, so
+    // that they don't make other mappings ambiguous
+    let curated_stop_points = options
+        .curated_mapping
+        .objects(NtfsObjectType::StopPoint)
+        .filter(|(ntfs_id, _)| ntfs_stop_points.get(ntfs_id).is_some())
+        .collect::<Vec<_>>();
+    for (ntfs_id, _) in &curated_stop_points {
+        remove_mapping(
+            &mut map_ntfs_to_osm_points,
+            &mut map_osm_to_ntfs_points,
+            ntfs_id,
+        );
+    }
+    let candidate_osm_stops = map_ntfs_to_osm_points.clone();
     clean_up_multiple_mappings(
         &mut map_ntfs_to_osm_points,
         &mut map_osm_to_ntfs_points,
-        force_double_stop_point_matching,
+        options.force_double_stop_point_matching,
     );
     clean_up_multiple_mappings(
         &mut map_osm_to_ntfs_points,
         &mut map_ntfs_to_osm_points,
-        force_double_stop_point_matching,
+        options.force_double_stop_point_matching,
     );
+    let osm_stop_ids: HashSet<&str> = objects
+        .stop_points
+        .iter()
+        .map(|sp| sp.id.as_str())
+        .collect();
+    for (ntfs_id, osm_ids) in curated_stop_points {
+        for osm_id in osm_ids
+            .iter()
+            .filter(|id| osm_stop_ids.contains(id.as_str()))
+        {
+            insert_mapping(
+                &mut map_ntfs_to_osm_points,
+                &mut map_osm_to_ntfs_points,
+                ntfs_id,
+                osm_id,
+            );
+            stop_matchings.insert(
+                (ntfs_id.to_string(), osm_id.clone()),
+                (MatchingMethod::Curated, None),
+            );
+        }
+    }
+    for stop_point_idx in network_stop_points {
+        let stop_point_id = &model.stop_points[stop_point_idx].id;
+        if map_ntfs_to_osm_points.contains_key(stop_point_id) {
+            continue;
+        }
+        if let Some(osm_stops) = candidate_osm_stops.get(stop_point_id) {
+            report.add_rejection(
+                format!(
+                    "ambiguous mapping of stop point {:?} with osm stops {:?}",
+                    stop_point_id, osm_stops
+                ),
+                ReportCategory::AmbiguousStopMapping,
+            );
+        } else if let Some(osm_route_id) = name_mismatches.get(&stop_point_idx) {
+            report.add_rejection(
+                format!(
+                    "stop point {:?} has no stop with a similar name in osm route {:?}",
+                    stop_point_id, osm_route_id
+                ),
+                ReportCategory::NameMismatch,
+            );
+        } else {
+            report.add_rejection(
+                format!("stop point {:?} doesn't match any osm stop", stop_point_id),
+                ReportCategory::StopPointNotMatched,
+            );
+        }
+    }
     for (ntfs_point_id, osm_points) in map_ntfs_to_osm_points {
         for osm_stop_id in osm_points {
+            let (method, score) = stop_matchings
+                .get(&(ntfs_point_id.clone(), osm_stop_id.clone()))
+                .cloned()
+                .unwrap_or((MatchingMethod::StopSequence, None));
+            report.add_matched_stop_point(&ntfs_point_id, &osm_stop_id, method, score);
             ntfs_stop_points
                 .get_mut(&ntfs_point_id)
                 .unwrap()
@@ -216,6 +582,24 @@ pub fn enrich_object_codes<S: ::std::hash::BuildHasher>(
     Ok(Model::new(collections)?)
 }
 
+/// Stop points served by the lines of the mapped networks, whether these
+/// lines have an OSM relation or not.
+fn stop_points_of_networks(
+    model: &Model,
+    network_mapping: &NetworkMapping,
+) -> BTreeSet<Idx<NtfsStopPoint>> {
+    model
+        .lines
+        .iter()
+        .filter(|(_, line)| network_mapping.contains(&line.network_id))
+        .flat_map(|(line_idx, _)| {
+            let stop_points: BTreeSet<Idx<NtfsStopPoint>> =
+                model.get_corresponding_from_idx(line_idx);
+            stop_points
+        })
+        .collect()
+}
+
 fn clean_up_multiple_mappings(
     map: &mut StopPointMap,
     reverse_map: &mut StopPointMap,
@@ -231,10 +615,52 @@ fn clean_up_multiple_mappings(
     });
 }
 
+/// How the stop points are moved to the position of their OSM stops.
+#[derive(Debug, Clone)]
+pub struct PositionPolicy {
+    /// Minimum distance (in meters) between the NTFS and the OSM positions
+    /// to move a stop point.
+    pub min_distance: f64,
+    /// Only use the `public_transport=stop_position` OSM stops of a stop
+    /// point, when it has some, and not its platforms.
+    ///
+    /// By default, `map-ntfs-with-osm` only maps the stop points with
+    /// platforms and bus stops (see `OsmStopFilter`), so this has no effect
+    /// unless the stop points have been mapped with a filter accepting the
+    /// `public_transport=stop_position` nodes.
+    pub prefer_stop_positions: bool,
+    /// Keep the NTFS position if the OSM stops of a stop point are farther
+    /// apart than this distance (in meters).
+    pub max_spread: Option<f64>,
+    /// Refuse to move a stop point farther than this distance (in meters).
+    pub max_displacement: Option<f64>,
+    /// Recompute the position of all the stop areas, and not only the ones
+    /// of the moved stop points.
+    pub recompute_all_stop_areas: bool,
+}
+
+impl PositionPolicy {
+    pub fn new(min_distance: f64) -> Self {
+        PositionPolicy {
+            min_distance,
+            prefer_stop_positions: false,
+            max_spread: None,
+            max_displacement: None,
+            recompute_all_stop_areas: false,
+        }
+    }
+}
+
+/// Moves the stop points with `osm_stop_points_id` codes to the centroid of
+/// their OSM stops, according to `policy`, and their stop areas to the
+/// centroid of their stop points.
+///
+/// The refused moves are recorded in `report`.
 pub fn improve_with_pbf(
     osm_pbf_path: &Path,
     collections: &mut Collections,
-    min_distance: f64,
+    policy: &PositionPolicy,
+    report: &mut Report<ReportCategory>,
 ) -> Result<()> {
     let mut parsed_pbf = parse_osm_pbf(
         osm_pbf_path
@@ -248,35 +674,100 @@ pub fn improve_with_pbf(
     let mut stop_points = collections.stop_points.take();
     let mut stop_area_ids_to_update = BTreeSet::new();
     for stop_point in stop_points.iter_mut().filter(|sp| !sp.codes.is_empty()) {
-        let osm_coords: Vec<Point<f64>> = stop_point
+        let osm_stops: Vec<_> = stop_point
             .codes
             .iter()
             .filter_map(|(code_type, code_value)| match code_type.as_str() {
-                "osm_stop_points_id" => osm_stop_points_map.get(code_value).map(|osm_stop_point| {
-                    Point::new(osm_stop_point.coord.lon, osm_stop_point.coord.lat)
-                }),
+                "osm_stop_points_id" => osm_stop_points_map.get(code_value),
                 _ => None,
             })
             .collect();
-        if osm_coords.is_empty() {
+        if osm_stops.is_empty() {
+            continue;
+        }
+        let stop_positions: Vec<_> = osm_stops
+            .iter()
+            .filter(|osm_stop| {
+                osm_stop
+                    .all_osm_tags
+                    .contains("public_transport", "stop_position")
+            })
+            .cloned()
+            .collect();
+        let osm_stops = if policy.prefer_stop_positions && !stop_positions.is_empty() {
+            stop_positions
+        } else {
+            osm_stops
+        };
+        let osm_coords: Vec<Coord> = osm_stops
+            .iter()
+            .map(|osm_stop| Coord {
+                lon: osm_stop.coord.lon,
+                lat: osm_stop.coord.lat,
+            })
+            .collect();
+        if let Some(max_spread) = policy.max_spread {
+            let spread = osm_coords
+                .iter()
+                .flat_map(|coord| osm_coords.iter().map(move |other| coord.distance_to(other)))
+                .fold(0., f64::max);
+            if spread > max_spread {
+                report.add_warning(
+                    format!(
+                        "stop point {:?} not moved, its osm stops are {:.0}m apart",
+                        stop_point.id, spread
+                    ),
+                    ReportCategory::OsmStopsTooFarApart,
+                );
+                continue;
+            }
+        }
+        let new_coords = point_list_to_centroid_coord(
+            osm_coords
+                .iter()
+                .map(|coord| Point::new(coord.lon, coord.lat))
+                .collect(),
+        );
+        let displacement = stop_point.coord.distance_to(&new_coords);
+        if displacement <= policy.min_distance {
             continue;
         }
-        let new_coords = point_list_to_centroid_coord(osm_coords);
-        if stop_point.coord.distance_to(&new_coords) > min_distance {
-            stop_point.coord = new_coords;
-            stop_area_ids_to_update.insert(stop_point.stop_area_id.clone());
+        if let Some(max_displacement) = policy.max_displacement {
+            if displacement > max_displacement {
+                report.add_warning(
+                    format!(
+                        "stop point {:?} not moved, its osm stops are {:.0}m away",
+                        stop_point.id, displacement
+                    ),
+                    ReportCategory::DisplacementTooLong,
+                );
+                continue;
+            }
         }
+        stop_point.coord = new_coords;
+        stop_area_ids_to_update.insert(stop_point.stop_area_id.clone());
     }
-    for stop_area_id in stop_area_ids_to_update {
-        // @TODO if performance issue, use get_corresponding from transit_model or find
-        // something else
-        let osm_coords: Vec<Point<f64>> = stop_points
-            .iter()
-            .filter(|sp| sp.stop_area_id == stop_area_id)
-            .map(|sp| Point::new(sp.coord.lon, sp.coord.lat))
+    if policy.recompute_all_stop_areas {
+        stop_area_ids_to_update = collections
+            .stop_areas
+            .values()
+            .map(|stop_area| stop_area.id.clone())
             .collect();
-        let new_coords = point_list_to_centroid_coord(osm_coords);
-        collections.stop_areas.get_mut(&stop_area_id).unwrap().coord = new_coords;
+    }
+    // Index of the stop points coordinates by stop area
+    let mut stop_area_points: HashMap<&str, Vec<Point<f64>>> = HashMap::new();
+    for stop_point in stop_points
+        .iter()
+        .filter(|sp| stop_area_ids_to_update.contains(&sp.stop_area_id))
+    {
+        stop_area_points
+            .entry(&stop_point.stop_area_id)
+            .or_insert_with(Vec::new)
+            .push(Point::new(stop_point.coord.lon, stop_point.coord.lat));
+    }
+    for (stop_area_id, points) in stop_area_points {
+        let new_coords = point_list_to_centroid_coord(points);
+        collections.stop_areas.get_mut(stop_area_id).unwrap().coord = new_coords;
     }
     collections.stop_points = CollectionWithId::new(stop_points)?;
     Ok(())
@@ -0,0 +1,140 @@
+//! Global alignment (Needleman-Wunsch) of two sequences of stops.
+use super::similarity::name_similarity;
+use osm_transit_extractor::StopPoint as OsmStopPoint;
+use transit_model::objects::{Coord, StopPoint as NtfsStopPoint};
+
+// Score of a stop present in one sequence only
+const GAP_PENALTY: f64 = -0.5;
+
+/// Thresholds of the alignment of the NTFS journey patterns with the OSM
+/// routes.
+#[derive(Debug, Clone)]
+pub struct SequenceAlignment {
+    /// Minimum score of the alignment (1 for identical sequences) to match a
+    /// journey pattern with an OSM route.
+    pub min_score: f64,
+    /// Distance in meters beyond which 2 stops are not considered close.
+    pub max_distance: f64,
+    /// Minimum name similarity of 2 aligned stops to map them.
+    pub min_name_similarity: f64,
+}
+
+impl SequenceAlignment {
+    /// Similarity of 2 stops, between -1 and 1, from the similarity of their
+    /// names and their distance.
+    pub(crate) fn stop_similarity(
+        &self,
+        ntfs_stop: &NtfsStopPoint,
+        osm_stop: &OsmStopPoint,
+    ) -> f64 {
+        let osm_coord = Coord {
+            lon: osm_stop.coord.lon,
+            lat: osm_stop.coord.lat,
+        };
+        let proximity = (1. - ntfs_stop.coord.distance_to(&osm_coord) / self.max_distance).max(0.);
+        name_similarity(&ntfs_stop.name, &osm_stop.name) + proximity - 1.
+    }
+}
+
+/// Result of the alignment of 2 sequences.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Alignment {
+    /// Score of the alignment divided by the length of the longest sequence:
+    /// 1 for identical sequences, lower with each mismatch or missing stop.
+    pub score: f64,
+    /// Indexes of the aligned elements, in order.
+    pub pairs: Vec<(usize, usize)>,
+}
+
+/// Aligns `lhs` and `rhs`, `similarity` giving the score of 2 aligned
+/// elements, between -1 (different) and 1 (identical).
+pub(crate) fn align<A, B, F>(lhs: &[A], rhs: &[B], similarity: F) -> Alignment
+where
+    F: Fn(&A, &B) -> f64,
+{
+    let (n, m) = (lhs.len(), rhs.len());
+    if n == 0 || m == 0 {
+        return Alignment {
+            score: if n == m { 1. } else { GAP_PENALTY },
+            pairs: Vec::new(),
+        };
+    }
+    // scores[i][j] is the best score aligning lhs[..i] with rhs[..j]
+    let mut scores = vec![vec![0.; m + 1]; n + 1];
+    scores[0] = (0..=m).map(|j| j as f64 * GAP_PENALTY).collect();
+    for (i, row) in scores.iter_mut().enumerate() {
+        row[0] = i as f64 * GAP_PENALTY;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let diagonal = scores[i - 1][j - 1] + similarity(&lhs[i - 1], &rhs[j - 1]);
+            let up = scores[i - 1][j] + GAP_PENALTY;
+            let left = scores[i][j - 1] + GAP_PENALTY;
+            scores[i][j] = diagonal.max(up).max(left);
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (n, m);
+    while i > 0 && j > 0 {
+        let diagonal = scores[i - 1][j - 1] + similarity(&lhs[i - 1], &rhs[j - 1]);
+        if (scores[i][j] - diagonal).abs() < f64::EPSILON {
+            pairs.push((i - 1, j - 1));
+            i -= 1;
+            j -= 1;
+        } else if (scores[i][j] - (scores[i - 1][j] + GAP_PENALTY)).abs() < f64::EPSILON {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+    pairs.reverse();
+    Alignment {
+        score: scores[n][m] / n.max(m) as f64,
+        pairs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same(lhs: &&str, rhs: &&str) -> f64 {
+        if lhs == rhs {
+            1.
+        } else {
+            -1.
+        }
+    }
+
+    #[test]
+    fn identical_sequences() {
+        let stops = ["A", "B", "C"];
+        let alignment = align(&stops, &stops, same);
+        assert_eq!(1., alignment.score);
+        assert_eq!(vec![(0, 0), (1, 1), (2, 2)], alignment.pairs);
+    }
+
+    #[test]
+    fn missing_stop() {
+        let alignment = align(&["A", "B", "C", "D"], &["A", "C", "D"], same);
+        assert_eq!(vec![(0, 0), (2, 1), (3, 2)], alignment.pairs);
+        assert_eq!((3. + GAP_PENALTY) / 4., alignment.score);
+    }
+
+    #[test]
+    fn extra_stop_and_mismatch() {
+        let alignment = align(&["A", "B", "C"], &["X", "A", "B", "Y"], same);
+        assert!(alignment.pairs.contains(&(0, 1)));
+        assert!(alignment.pairs.contains(&(1, 2)));
+        assert!(alignment.score < 0.5);
+    }
+
+    #[test]
+    fn empty_sequence() {
+        let empty: [&str; 0] = [];
+        let alignment = align(&empty, &["A"], same);
+        assert!(alignment.pairs.is_empty());
+        assert!(alignment.score < 0.);
+    }
+}
@@ -0,0 +1,75 @@
+//! Matching of NTFS stop points to OSM stops by geographic proximity and
+//! name similarity.
+use super::similarity::name_similarity;
+use geo::Point;
+use osm_transit_extractor::StopPoint as OsmStopPoint;
+use osm_utils::grid_index::PointIndex;
+use transit_model::objects::Coord;
+
+/// Thresholds of the geographic matching.
+#[derive(Debug, Clone)]
+pub struct GeoMatching {
+    /// Maximum distance in meters between the NTFS stop point and the OSM
+    /// stop.
+    pub max_distance: f64,
+    /// Minimum similarity of the names, between 0 and 1 (see
+    /// [name_similarity]).
+    pub min_name_similarity: f64,
+}
+
+/// A grid of OSM stops to find the stops around a point without scanning
+/// all of them.
+pub(crate) struct OsmStopIndex<'a> {
+    index: PointIndex<&'a OsmStopPoint>,
+}
+
+impl<'a> OsmStopIndex<'a> {
+    /// Indexes the OSM stops for lookups of about `radius` meters.
+    pub(crate) fn new<I>(osm_stops: I, radius: f64) -> Self
+    where
+        I: IntoIterator<Item = &'a OsmStopPoint>,
+    {
+        let mut index = PointIndex::new(radius);
+        for osm_stop in osm_stops {
+            index.insert(Point::new(osm_stop.coord.lon, osm_stop.coord.lat), osm_stop);
+        }
+        OsmStopIndex { index }
+    }
+
+    /// Returns the OSM stops within `radius` meters of `coord`, with their
+    /// distance.
+    pub(crate) fn around<'b>(
+        &'b self,
+        coord: &Coord,
+        radius: f64,
+    ) -> impl Iterator<Item = (f64, &'a OsmStopPoint)> + 'b {
+        self.index
+            .around(Point::new(coord.lon, coord.lat), radius)
+            .map(|(distance, &osm_stop)| (distance, osm_stop))
+    }
+}
+
+/// Finds the best OSM stop for a NTFS stop point: the most similar name
+/// among the stops close enough, the closest one in case of a tie.
+pub(crate) fn find_osm_stop<'a>(
+    index: &OsmStopIndex<'a>,
+    coord: &Coord,
+    name: &str,
+    params: &GeoMatching,
+) -> Option<&'a OsmStopPoint> {
+    index
+        .around(coord, params.max_distance)
+        .map(|(distance, osm_stop)| (name_similarity(name, &osm_stop.name), distance, osm_stop))
+        .filter(|(similarity, _, _)| *similarity >= params.min_name_similarity)
+        .max_by(|(lhs_sim, lhs_dist, _), (rhs_sim, rhs_dist, _)| {
+            lhs_sim
+                .partial_cmp(rhs_sim)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| {
+                    rhs_dist
+                        .partial_cmp(lhs_dist)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+        })
+        .map(|(_, _, osm_stop)| osm_stop)
+}
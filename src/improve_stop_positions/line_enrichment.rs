@@ -0,0 +1,192 @@
+use super::matching_report::{MatchingReport, ReportCategory};
+use osmpbfreader::Tags;
+use transit_model::objects::{Line, Rgb};
+
+/// Update of the colors and names of the lines from their matched OSM
+/// lines.
+#[derive(Debug, Clone, Default)]
+pub struct LineEnrichment {
+    /// Overwrite the existing colors and names of the lines.
+    pub force: bool,
+}
+
+// An OSM colour like `#1E90FF` or `#FFF`. The named colours are not
+// supported.
+fn parse_colour(colour: &str) -> Option<Rgb> {
+    let hex = colour.trim().trim_start_matches('#');
+    if !hex.is_ascii() {
+        return None;
+    }
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| vec![c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return None,
+    };
+    let component = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+    Some(Rgb {
+        red: component(0)?,
+        green: component(2)?,
+        blue: component(4)?,
+    })
+}
+
+fn rgb_to_string(rgb: &Rgb) -> String {
+    format!("{:02X}{:02X}{:02X}", rgb.red, rgb.green, rgb.blue)
+}
+
+// Value of a tag of the OSM line, or the value shared by all its routes
+fn line_tag<'a>(key: &str, line_tags: &'a Tags, route_tags: &[&'a Tags]) -> Option<&'a str> {
+    if let Some(value) = line_tags.get(key) {
+        return Some(value);
+    }
+    let mut values = route_tags.iter().map(|tags| tags.get(key));
+    let first_value = values.next()??;
+    if values.all(|value| value.map(|value| value == first_value) == Some(true)) {
+        Some(first_value)
+    } else {
+        None
+    }
+}
+
+fn update_color(
+    line_id: &str,
+    field: &str,
+    color: &mut Option<Rgb>,
+    osm_colour: Option<&str>,
+    force: bool,
+    report: &mut MatchingReport,
+) {
+    let osm_colour = match osm_colour {
+        Some(osm_colour) => osm_colour,
+        None => return,
+    };
+    let osm_color = match parse_colour(osm_colour) {
+        Some(osm_color) => osm_color,
+        None => {
+            report.add_rejection(
+                format!(
+                    "invalid osm colour {:?} for the {} of line {:?}",
+                    osm_colour, field, line_id
+                ),
+                ReportCategory::InvalidOsmColour,
+            );
+            return;
+        }
+    };
+    if color.as_ref() == Some(&osm_color) || (color.is_some() && !force) {
+        return;
+    }
+    report.add_line_update(
+        line_id,
+        field,
+        color.as_ref().map(rgb_to_string),
+        rgb_to_string(&osm_color),
+    );
+    *color = Some(osm_color);
+}
+
+/// Fills the color, the text color and, if it is empty, the name of a line
+/// from the tags of its OSM line (and of its OSM routes for the colors).
+pub(crate) fn enrich_line(
+    line: &mut Line,
+    line_tags: &Tags,
+    route_tags: &[&Tags],
+    enrichment: &LineEnrichment,
+    report: &mut MatchingReport,
+) {
+    update_color(
+        &line.id,
+        "color",
+        &mut line.color,
+        line_tag("colour", line_tags, route_tags),
+        enrichment.force,
+        report,
+    );
+    update_color(
+        &line.id,
+        "text_color",
+        &mut line.text_color,
+        line_tag("text_colour", line_tags, route_tags),
+        enrichment.force,
+        report,
+    );
+    if let Some(osm_name) = line_tags.get("name") {
+        if *osm_name != line.name && (line.name.is_empty() || enrichment.force) {
+            let old_name = Some(line.name.clone()).filter(|name| !name.is_empty());
+            report.add_line_update(&line.id, "name", old_name, osm_name.clone());
+            line.name = osm_name.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(v: &[(&str, &str)]) -> Tags {
+        v.iter().map(|&(k, v)| (k.into(), v.into())).collect()
+    }
+
+    fn rgb(red: u8, green: u8, blue: u8) -> Rgb {
+        Rgb { red, green, blue }
+    }
+
+    #[test]
+    fn osm_colours() {
+        assert_eq!(Some(rgb(0x1E, 0x90, 0xFF)), parse_colour("#1e90ff"));
+        assert_eq!(Some(rgb(0xFF, 0xFF, 0xFF)), parse_colour("FFF"));
+        assert_eq!(None, parse_colour("blue"));
+        assert_eq!(None, parse_colour("#12345"));
+    }
+
+    #[test]
+    fn fill_missing_fields() {
+        let mut line = Line {
+            id: "L1".to_string(),
+            text_color: Some(rgb(0, 0, 0)),
+            ..Default::default()
+        };
+        let line_tags = tags(&[("name", "Ligne 1"), ("text_colour", "#FFFFFF")]);
+        // The routes agree on the colour
+        let route_tags = tags(&[("colour", "#FF0000")]);
+        let mut report = MatchingReport::default();
+        enrich_line(
+            &mut line,
+            &line_tags,
+            &[&route_tags, &route_tags],
+            &LineEnrichment::default(),
+            &mut report,
+        );
+        assert_eq!(Some(rgb(0xFF, 0, 0)), line.color);
+        assert_eq!(Some(rgb(0, 0, 0)), line.text_color);
+        assert_eq!("Ligne 1", line.name);
+        let serialized_report = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            2,
+            serialized_report["updated_lines"]["L1"]
+                .as_array()
+                .unwrap()
+                .len()
+        );
+
+        enrich_line(
+            &mut line,
+            &line_tags,
+            &[],
+            &LineEnrichment { force: true },
+            &mut report,
+        );
+        // No colour on the line nor on its routes
+        assert_eq!(Some(rgb(0xFF, 0, 0)), line.color);
+        assert_eq!(Some(rgb(0xFF, 0xFF, 0xFF)), line.text_color);
+    }
+
+    #[test]
+    fn disagreeing_routes() {
+        let red = tags(&[("colour", "#FF0000")]);
+        let blue = tags(&[("colour", "#0000FF")]);
+        assert_eq!(None, line_tag("colour", &tags(&[]), &[&red, &blue]));
+        assert_eq!(Some("#FF0000"), line_tag("colour", &tags(&[]), &[&red]));
+        assert_eq!(None, line_tag("colour", &tags(&[]), &[&red, &tags(&[])]));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::Result;
+use failure::{format_err, ResultExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::path::Path;
+
+/// Types of the NTFS objects matched with OSM objects.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum NtfsObjectType {
+    Line,
+    Route,
+    StopPoint,
+}
+
+/// How an NTFS object has been matched with an OSM object.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchingMethod {
+    /// Line code equal to the `ref` of the OSM line.
+    Ref,
+    /// Journey pattern with the same stops as the OSM route.
+    StopSequence,
+    /// Journey pattern aligned with the OSM route.
+    Alignment,
+    /// Stop point close to an OSM stop with a similar name.
+    Proximity,
+    /// Given by a curated mapping.
+    Curated,
+}
+
+/// A matching between an NTFS object and an OSM object.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MappingRecord {
+    pub ntfs_object_type: NtfsObjectType,
+    pub ntfs_id: String,
+    /// `node`, `way` or `relation`
+    pub osm_type: String,
+    pub osm_id: String,
+    pub score: Option<f64>,
+    pub method: MatchingMethod,
+}
+
+impl MappingRecord {
+    /// `osm_object_id` is an id like `relation:42`, as given by
+    /// osm_transit_extractor.
+    pub(crate) fn new(
+        ntfs_object_type: NtfsObjectType,
+        ntfs_id: &str,
+        osm_object_id: &str,
+        score: Option<f64>,
+        method: MatchingMethod,
+    ) -> Self {
+        let mut split = osm_object_id.splitn(2, ':');
+        let (osm_type, osm_id) = match (split.next(), split.next()) {
+            (Some(osm_type), Some(osm_id)) => (osm_type, osm_id),
+            _ => ("", osm_object_id),
+        };
+        MappingRecord {
+            ntfs_object_type,
+            ntfs_id: ntfs_id.to_string(),
+            osm_type: osm_type.to_string(),
+            osm_id: osm_id.to_string(),
+            score,
+            method,
+        }
+    }
+
+    fn osm_object_id(&self) -> String {
+        format!("{}:{}", self.osm_type, self.osm_id)
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().map(|ext| ext == "json").unwrap_or(false)
+}
+
+/// Writes the mapping in a json file if the extension of `path` is `.json`,
+/// in a csv file otherwise.
+pub fn write_mapping<P: AsRef<Path>>(records: &[MappingRecord], path: P) -> Result<()> {
+    let path = path.as_ref();
+    let file = File::create(path).with_context(|_| format!("Error writing {:?}", path))?;
+    if is_json(path) {
+        serde_json::to_writer_pretty(file, records)?;
+    } else {
+        let mut wtr = csv::Writer::from_writer(file);
+        for record in records {
+            wtr.serialize(record)
+                .with_context(|_| format!("Error writing {:?}", path))?;
+        }
+        wtr.flush()?;
+    }
+    Ok(())
+}
+
+/// Reads a mapping written by `write_mapping`.
+pub fn read_mapping<P: AsRef<Path>>(path: P) -> Result<Vec<MappingRecord>> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+    if is_json(path) {
+        Ok(serde_json::from_reader(file).with_context(|_| format!("Error reading {:?}", path))?)
+    } else {
+        let mut rdr = csv::Reader::from_reader(file);
+        let records = rdr
+            .deserialize()
+            .collect::<std::result::Result<_, _>>()
+            .with_context(|_| format!("Error reading {:?}", path))?;
+        Ok(records)
+    }
+}
+
+/// A manually curated mapping, whose matchings take precedence over the
+/// automatic ones.
+#[derive(Debug, Clone, Default)]
+pub struct CuratedMapping {
+    osm_ids: BTreeMap<(NtfsObjectType, String), BTreeSet<String>>,
+}
+
+impl CuratedMapping {
+    pub fn from_records(records: Vec<MappingRecord>) -> Self {
+        let mut osm_ids = BTreeMap::new();
+        for record in records {
+            osm_ids
+                .entry((record.ntfs_object_type, record.ntfs_id.clone()))
+                .or_insert_with(BTreeSet::new)
+                .insert(record.osm_object_id());
+        }
+        CuratedMapping { osm_ids }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let records = read_mapping(path)?;
+        if records.iter().any(|record| record.osm_type.is_empty()) {
+            return Err(format_err!(
+                "the curated mapping contains records without osm_type"
+            ));
+        }
+        Ok(CuratedMapping::from_records(records))
+    }
+
+    /// OSM ids (like `relation:42`) of an NTFS object, if it is curated.
+    pub(crate) fn osm_ids(
+        &self,
+        ntfs_object_type: NtfsObjectType,
+        ntfs_id: &str,
+    ) -> Option<&BTreeSet<String>> {
+        self.osm_ids.get(&(ntfs_object_type, ntfs_id.to_string()))
+    }
+
+    /// Curated NTFS objects of a type, with their OSM ids.
+    pub(crate) fn objects(
+        &self,
+        ntfs_object_type: NtfsObjectType,
+    ) -> impl Iterator<Item = (&str, &BTreeSet<String>)> {
+        self.osm_ids
+            .iter()
+            .filter(move |((object_type, _), _)| *object_type == ntfs_object_type)
+            .map(|((_, ntfs_id), osm_ids)| (ntfs_id.as_str(), osm_ids))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transit_model::test_utils::test_in_tmp_dir;
+
+    fn records() -> Vec<MappingRecord> {
+        vec![
+            MappingRecord::new(
+                NtfsObjectType::Line,
+                "L1",
+                "relation:42",
+                None,
+                MatchingMethod::Ref,
+            ),
+            MappingRecord::new(
+                NtfsObjectType::StopPoint,
+                "SP1",
+                "node:7",
+                Some(0.9),
+                MatchingMethod::Alignment,
+            ),
+        ]
+    }
+
+    #[test]
+    fn csv_and_json_round_trip() {
+        test_in_tmp_dir(|path| {
+            for file_name in &["mapping.csv", "mapping.json"] {
+                let mapping_path = path.join(file_name);
+                write_mapping(&records(), &mapping_path).unwrap();
+                assert_eq!(records(), read_mapping(&mapping_path).unwrap());
+            }
+        });
+    }
+
+    #[test]
+    fn curated_mapping() {
+        let curated_mapping = CuratedMapping::from_records(records());
+        let expected: BTreeSet<String> = vec!["relation:42".to_string()].into_iter().collect();
+        assert_eq!(
+            Some(&expected),
+            curated_mapping.osm_ids(NtfsObjectType::Line, "L1")
+        );
+        assert_eq!(None, curated_mapping.osm_ids(NtfsObjectType::Route, "L1"));
+        assert_eq!(
+            vec!["SP1"],
+            curated_mapping
+                .objects(NtfsObjectType::StopPoint)
+                .map(|(ntfs_id, _)| ntfs_id)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use super::mapping::{MappingRecord, MatchingMethod, NtfsObjectType};
+use super::network_mapping::NetworkMapping;
+use crate::report::{self, Report};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use transit_model::model::Model;
+use transit_model::objects::{Line, Route, StopPoint};
+use typed_index_collection::Idx;
+
+/// Reasons why an NTFS object has not been matched with an OSM object, or
+/// has not been moved to the position of its OSM stops.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Hash)]
+pub enum ReportCategory {
+    UnknownNetwork,
+    LineWithoutRef,
+    OsmLineNotFound,
+    SeveralOsmLines,
+    RouteNotMatched,
+    AmbiguousStopMapping,
+    NameMismatch,
+    StopPointNotMatched,
+    OsmStopsTooFarApart,
+    DisplacementTooLong,
+    InvalidOsmColour,
+}
+
+impl report::ReportCategory for ReportCategory {}
+
+/// Number of objects of a network matched with OSM objects.
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct Coverage {
+    pub total: usize,
+    pub matched: usize,
+    pub percentage: f64,
+}
+
+impl Coverage {
+    fn new(total: usize, matched: usize) -> Self {
+        let percentage = if total == 0 {
+            0.
+        } else {
+            (matched as f64 * 10_000. / total as f64).round() / 100.
+        };
+        Coverage {
+            total,
+            matched,
+            percentage,
+        }
+    }
+}
+
+/// Coverage of the lines, routes and stop points of a network.
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct NetworkCoverage {
+    pub lines: Coverage,
+    pub routes: Coverage,
+    pub stop_points: Coverage,
+}
+
+#[derive(Debug, Serialize)]
+struct Matching {
+    method: MatchingMethod,
+    score: Option<f64>,
+}
+
+// NTFS id => OSM id => matching
+type Matchings = BTreeMap<String, BTreeMap<String, Matching>>;
+
+/// A field of a line updated from its OSM line.
+#[derive(Debug, Serialize)]
+struct LineUpdate {
+    field: String,
+    old_value: Option<String>,
+    new_value: String,
+}
+
+/// Report of the matching between NTFS and OSM objects.
+///
+/// The objects which could not be matched are listed as warnings with the
+/// reason of the rejection, the matched ones with the ids of their OSM
+/// objects.
+#[derive(Debug, Default, Serialize)]
+pub struct MatchingReport {
+    #[serde(flatten)]
+    report: Report<ReportCategory>,
+    // The rejections already reported, there can be one per stop point
+    #[serde(skip)]
+    rejections: HashSet<(ReportCategory, String)>,
+    matched_lines: Matchings,
+    matched_routes: Matchings,
+    matched_stop_points: Matchings,
+    updated_lines: BTreeMap<String, Vec<LineUpdate>>,
+    coverage: BTreeMap<String, NetworkCoverage>,
+}
+
+fn insert_matching(
+    matchings: &mut Matchings,
+    ntfs_id: &str,
+    osm_id: &str,
+    method: MatchingMethod,
+    score: Option<f64>,
+) {
+    matchings
+        .entry(ntfs_id.to_string())
+        .or_insert_with(BTreeMap::new)
+        .insert(osm_id.to_string(), Matching { method, score });
+}
+
+fn mapping_records(
+    ntfs_object_type: NtfsObjectType,
+    matchings: &Matchings,
+) -> impl Iterator<Item = MappingRecord> + '_ {
+    matchings.iter().flat_map(move |(ntfs_id, osm_matchings)| {
+        osm_matchings.iter().map(move |(osm_id, matching)| {
+            MappingRecord::new(
+                ntfs_object_type,
+                ntfs_id,
+                osm_id,
+                matching.score,
+                matching.method,
+            )
+        })
+    })
+}
+
+impl MatchingReport {
+    pub(crate) fn add_rejection(&mut self, message: String, category: ReportCategory) {
+        if self.rejections.insert((category, message.clone())) {
+            self.report.add_unchecked_warning(message, category);
+        }
+    }
+
+    pub(crate) fn add_matched_line(
+        &mut self,
+        line_id: &str,
+        osm_line_id: &str,
+        method: MatchingMethod,
+    ) {
+        insert_matching(&mut self.matched_lines, line_id, osm_line_id, method, None);
+    }
+
+    pub(crate) fn add_matched_route(
+        &mut self,
+        route_id: &str,
+        osm_route_id: &str,
+        method: MatchingMethod,
+        score: Option<f64>,
+    ) {
+        insert_matching(
+            &mut self.matched_routes,
+            route_id,
+            osm_route_id,
+            method,
+            score,
+        );
+    }
+
+    pub(crate) fn add_matched_stop_point(
+        &mut self,
+        stop_point_id: &str,
+        osm_stop_id: &str,
+        method: MatchingMethod,
+        score: Option<f64>,
+    ) {
+        insert_matching(
+            &mut self.matched_stop_points,
+            stop_point_id,
+            osm_stop_id,
+            method,
+            score,
+        );
+    }
+
+    pub(crate) fn add_line_update(
+        &mut self,
+        line_id: &str,
+        field: &str,
+        old_value: Option<String>,
+        new_value: String,
+    ) {
+        self.updated_lines
+            .entry(line_id.to_string())
+            .or_insert_with(Vec::new)
+            .push(LineUpdate {
+                field: field.to_string(),
+                old_value,
+                new_value,
+            });
+    }
+
+    /// Computes the coverage of each mapped network of the model.
+    pub(crate) fn compute_coverage(&mut self, model: &Model, network_mapping: &NetworkMapping) {
+        for ntfs_network_id in network_mapping.network_ids() {
+            let network_idx = match model.networks.get_idx(ntfs_network_id) {
+                Some(network_idx) => network_idx,
+                None => continue,
+            };
+            let lines: BTreeSet<Idx<Line>> = model.get_corresponding_from_idx(network_idx);
+            let routes: BTreeSet<Idx<Route>> = model.get_corresponding_from_idx(network_idx);
+            let stop_points: BTreeSet<Idx<StopPoint>> =
+                model.get_corresponding_from_idx(network_idx);
+            let coverage = NetworkCoverage {
+                lines: Coverage::new(
+                    lines.len(),
+                    lines
+                        .iter()
+                        .filter(|&&idx| self.matched_lines.contains_key(&model.lines[idx].id))
+                        .count(),
+                ),
+                routes: Coverage::new(
+                    routes.len(),
+                    routes
+                        .iter()
+                        .filter(|&&idx| self.matched_routes.contains_key(&model.routes[idx].id))
+                        .count(),
+                ),
+                stop_points: Coverage::new(
+                    stop_points.len(),
+                    stop_points
+                        .iter()
+                        .filter(|&&idx| {
+                            self.matched_stop_points
+                                .contains_key(&model.stop_points[idx].id)
+                        })
+                        .count(),
+                ),
+            };
+            self.coverage.insert(ntfs_network_id.to_string(), coverage);
+        }
+    }
+
+    /// The mapping between the matched NTFS objects and the OSM objects.
+    pub fn mapping(&self) -> Vec<MappingRecord> {
+        mapping_records(NtfsObjectType::Line, &self.matched_lines)
+            .chain(mapping_records(NtfsObjectType::Route, &self.matched_routes))
+            .chain(mapping_records(
+                NtfsObjectType::StopPoint,
+                &self.matched_stop_points,
+            ))
+            .collect()
+    }
+
+    /// Coverage of a network, once computed.
+    pub fn coverage(&self, ntfs_network_id: &str) -> Option<&NetworkCoverage> {
+        self.coverage.get(ntfs_network_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coverage_percentage() {
+        assert_eq!(0., Coverage::new(0, 0).percentage);
+        assert_eq!(100., Coverage::new(4, 4).percentage);
+        assert_eq!(33.33, Coverage::new(3, 1).percentage);
+    }
+
+    #[test]
+    fn alignment_score_in_mapping() {
+        let mut report = MatchingReport::default();
+        report.add_matched_route(
+            "route1",
+            "relation:42",
+            MatchingMethod::Alignment,
+            Some(0.87),
+        );
+        let mapping = report.mapping();
+        assert_eq!(1, mapping.len());
+        assert_eq!("42", mapping[0].osm_id);
+        assert_eq!(Some(0.87), mapping[0].score);
+    }
+
+    #[test]
+    fn rejections_reported_once() {
+        let mut report = MatchingReport::default();
+        for _ in 0..3 {
+            report.add_rejection("stop1".to_string(), ReportCategory::StopPointNotMatched);
+        }
+        report.add_rejection("stop1".to_string(), ReportCategory::NameMismatch);
+        report.add_rejection("stop2".to_string(), ReportCategory::StopPointNotMatched);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(3, json["warnings"].as_array().unwrap().len());
+        assert!(json.get("rejections").is_none());
+    }
+}
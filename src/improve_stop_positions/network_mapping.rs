@@ -0,0 +1,183 @@
+use super::compare_almost_equal;
+use crate::Result;
+use failure::{format_err, ResultExt};
+use osmpbfreader::Tags;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::hash::BuildHasher;
+use std::path::Path;
+
+/// How the OSM lines of a NTFS network are recognized.
+#[derive(Debug, Clone)]
+pub enum NetworkMatcher {
+    /// `network` tag almost equal to the label (ignoring case and accents).
+    Label(String),
+    /// `network` tag matching the regex.
+    Regex(Regex),
+    /// `operator` tag almost equal to the label (ignoring case and accents).
+    Operator(String),
+    /// `network:wikidata` or `operator:wikidata` tag equal to the Wikidata
+    /// id (like `Q1234`).
+    Wikidata(String),
+}
+
+impl NetworkMatcher {
+    /// Returns true if the OSM line with these tags matches.
+    pub fn matches(&self, tags: &Tags) -> bool {
+        let tag = |key: &str| tags.get(key).map(|value| value.as_str());
+        match self {
+            NetworkMatcher::Label(label) => tag("network")
+                .map(|network| compare_almost_equal(network, label))
+                .unwrap_or(false),
+            NetworkMatcher::Regex(regex) => tag("network")
+                .map(|network| regex.is_match(network))
+                .unwrap_or(false),
+            NetworkMatcher::Operator(label) => tag("operator")
+                .map(|operator| compare_almost_equal(operator, label))
+                .unwrap_or(false),
+            NetworkMatcher::Wikidata(id) => {
+                tag("network:wikidata") == Some(id.as_str())
+                    || tag("operator:wikidata") == Some(id.as_str())
+            }
+        }
+    }
+}
+
+// A matcher as written in the configuration file, like `{"regex": "^TCL"}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MatcherConfig {
+    Label(String),
+    Regex(String),
+    Operator(String),
+    Wikidata(String),
+}
+
+impl MatcherConfig {
+    fn into_matcher(self) -> Result<NetworkMatcher> {
+        Ok(match self {
+            MatcherConfig::Label(label) => NetworkMatcher::Label(label),
+            MatcherConfig::Regex(regex) => NetworkMatcher::Regex(
+                Regex::new(&regex).with_context(|_| format!("invalid regex {:?}", regex))?,
+            ),
+            MatcherConfig::Operator(label) => NetworkMatcher::Operator(label),
+            MatcherConfig::Wikidata(id) => NetworkMatcher::Wikidata(id),
+        })
+    }
+}
+
+/// The NTFS networks to map, with the matchers recognizing their OSM lines.
+/// An OSM line belongs to a network if any of its matchers matches.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkMapping {
+    matchers: BTreeMap<String, Vec<NetworkMatcher>>,
+}
+
+impl NetworkMapping {
+    /// Reads the mapping from a json file like
+    /// `{"network:TCL": [{"label": "TCL"}, {"regex": "^Transports en Commun"},
+    /// {"operator": "Keolis Lyon"}, {"wikidata": "Q1234"}]}`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|_| format!("Error reading {:?}", path))?;
+        let config: BTreeMap<String, Vec<MatcherConfig>> =
+            serde_json::from_reader(file).with_context(|_| format!("Error reading {:?}", path))?;
+        let mut network_mapping = NetworkMapping::default();
+        for (ntfs_network_id, matchers) in config {
+            if matchers.is_empty() {
+                return Err(format_err!(
+                    "network {:?} has no matcher in {:?}",
+                    ntfs_network_id,
+                    path
+                ));
+            }
+            for matcher in matchers {
+                network_mapping.add(&ntfs_network_id, matcher.into_matcher()?);
+            }
+        }
+        Ok(network_mapping)
+    }
+
+    pub fn add(&mut self, ntfs_network_id: &str, matcher: NetworkMatcher) {
+        self.matchers
+            .entry(ntfs_network_id.to_string())
+            .or_insert_with(Vec::new)
+            .push(matcher);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matchers.is_empty()
+    }
+
+    pub fn contains(&self, ntfs_network_id: &str) -> bool {
+        self.matchers.contains_key(ntfs_network_id)
+    }
+
+    /// Ids of the mapped NTFS networks.
+    pub fn network_ids(&self) -> impl Iterator<Item = &str> {
+        self.matchers.keys().map(String::as_str)
+    }
+
+    /// Returns true if the OSM line with these tags belongs to the NTFS
+    /// network.
+    pub fn matches(&self, ntfs_network_id: &str, tags: &Tags) -> bool {
+        self.matchers
+            .get(ntfs_network_id)
+            .map(|matchers| matchers.iter().any(|matcher| matcher.matches(tags)))
+            .unwrap_or(false)
+    }
+}
+
+impl<S: BuildHasher> From<HashMap<&str, &str, S>> for NetworkMapping {
+    fn from(ntfs_network_to_osm: HashMap<&str, &str, S>) -> Self {
+        let mut network_mapping = NetworkMapping::default();
+        for (ntfs_network_id, osm_network) in ntfs_network_to_osm {
+            network_mapping.add(
+                ntfs_network_id,
+                NetworkMatcher::Label(osm_network.to_string()),
+            );
+        }
+        network_mapping
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(v: &[(&str, &str)]) -> Tags {
+        v.iter().map(|&(k, v)| (k.into(), v.into())).collect()
+    }
+
+    #[test]
+    fn matchers() {
+        let tcl = tags(&[
+            ("network", "Transports en Commun Lyonnais"),
+            ("operator", "Keolis Lyon"),
+            ("network:wikidata", "Q1234"),
+        ]);
+        let label = NetworkMatcher::Label("transports en commun lyonnais".to_string());
+        assert!(label.matches(&tcl));
+        assert!(!label.matches(&tags(&[("operator", "Transports en Commun Lyonnais")])));
+        let regex = NetworkMatcher::Regex(Regex::new("^(TCL|Transports en Commun)").unwrap());
+        assert!(regex.matches(&tcl));
+        assert!(regex.matches(&tags(&[("network", "TCL")])));
+        assert!(!regex.matches(&tags(&[("network", "SNCF")])));
+        assert!(NetworkMatcher::Operator("KEOLIS LYON".to_string()).matches(&tcl));
+        assert!(NetworkMatcher::Wikidata("Q1234".to_string()).matches(&tcl));
+        assert!(!NetworkMatcher::Wikidata("Q12".to_string()).matches(&tcl));
+    }
+
+    #[test]
+    fn any_matcher_of_the_network() {
+        let mut network_mapping = NetworkMapping::default();
+        network_mapping.add("TCL", NetworkMatcher::Label("TCL".to_string()));
+        network_mapping.add("TCL", NetworkMatcher::Wikidata("Q1234".to_string()));
+        assert!(network_mapping.matches("TCL", &tags(&[("network", "TCL")])));
+        assert!(network_mapping.matches("TCL", &tags(&[("operator:wikidata", "Q1234")])));
+        assert!(!network_mapping.matches("TCL", &tags(&[("network", "SNCF")])));
+        assert!(!network_mapping.matches("SNCF", &tags(&[("network", "TCL")])));
+    }
+}
@@ -0,0 +1,71 @@
+use osmpbfreader::Tags;
+
+/// The OSM objects considered as stops when matching the stop points.
+#[derive(Debug, Clone)]
+pub struct OsmStopFilter {
+    /// Accepted `(key, value)` tags, an OSM stop needs at least one of them.
+    pub tags: Vec<(String, String)>,
+    /// Also accept the ways (platforms are often mapped as ways), and not
+    /// only the nodes.
+    pub include_ways: bool,
+}
+
+impl Default for OsmStopFilter {
+    fn default() -> Self {
+        OsmStopFilter {
+            tags: vec![
+                ("public_transport".to_string(), "platform".to_string()),
+                ("highway".to_string(), "bus_stop".to_string()),
+            ],
+            include_ways: false,
+        }
+    }
+}
+
+impl OsmStopFilter {
+    /// Returns true if the OSM object (with an id like `node:42`) is a stop.
+    pub fn accepts(&self, osm_id: &str, tags: &Tags) -> bool {
+        let accepted_type = match osm_id.split(':').next() {
+            Some("node") => true,
+            Some("way") => self.include_ways,
+            _ => false,
+        };
+        accepted_type
+            && self
+                .tags
+                .iter()
+                .any(|(key, value)| tags.contains(key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(v: &[(&str, &str)]) -> Tags {
+        v.iter().map(|&(k, v)| (k.into(), v.into())).collect()
+    }
+
+    #[test]
+    fn default_filter() {
+        let filter = OsmStopFilter::default();
+        let platform = tags(&[("public_transport", "platform")]);
+        assert!(filter.accepts("node:1", &platform));
+        assert!(filter.accepts("node:1", &tags(&[("highway", "bus_stop")])));
+        assert!(!filter.accepts("node:1", &tags(&[("railway", "tram_stop")])));
+        assert!(!filter.accepts("way:1", &platform));
+        assert!(!filter.accepts("relation:1", &platform));
+    }
+
+    #[test]
+    fn tram_stops_and_ways() {
+        let filter = OsmStopFilter {
+            tags: vec![("railway".to_string(), "tram_stop".to_string())],
+            include_ways: true,
+        };
+        let tram_stop = tags(&[("railway", "tram_stop")]);
+        assert!(filter.accepts("node:1", &tram_stop));
+        assert!(filter.accepts("way:1", &tram_stop));
+        assert!(!filter.accepts("way:1", &tags(&[("highway", "bus_stop")])));
+    }
+}
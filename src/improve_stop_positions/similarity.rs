@@ -0,0 +1,98 @@
+//! Fuzzy comparison of stop names.
+use std::collections::BTreeSet;
+use strsim::normalized_levenshtein;
+use unidecode::unidecode;
+
+pub(crate) fn sanitize(broken: &str) -> String {
+    unidecode(broken).to_lowercase()
+}
+
+fn tokens(name: &str) -> BTreeSet<String> {
+    sanitize(name)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn join<'a, I: IntoIterator<Item = &'a String>>(tokens: I) -> String {
+    tokens
+        .into_iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Maximum penalty of the comparisons with the common words, when no word is
+// common to both names
+const UNMATCHED_WORDS_PENALTY: f64 = 0.1;
+
+/// Token set ratio of two names, between 0 (nothing in common) and 1
+/// (same set of words).
+///
+/// Names are sanitized and split into words. The common words are compared
+/// with the common words followed by the remaining words of each name, so
+/// that "Gare" and "Gare SNCF" are close, and the order of the words does
+/// not matter. These comparisons are penalized by the proportion of words
+/// found in only one of the names, so that only the same set of words gives
+/// a similarity of 1.
+pub fn name_similarity(lhs: &str, rhs: &str) -> f64 {
+    let lhs = tokens(lhs);
+    let rhs = tokens(rhs);
+    if lhs.is_empty() || rhs.is_empty() {
+        return if lhs == rhs { 1. } else { 0. };
+    }
+    let intersection = join(lhs.intersection(&rhs));
+    let with_remainder =
+        |remainder: String| format!("{} {}", intersection, remainder).trim().to_string();
+    let lhs_full = with_remainder(join(lhs.difference(&rhs)));
+    let rhs_full = with_remainder(join(rhs.difference(&lhs)));
+    let nb_unmatched = lhs.symmetric_difference(&rhs).count();
+    let penalty = UNMATCHED_WORDS_PENALTY * nb_unmatched as f64 / lhs.union(&rhs).count() as f64;
+    [
+        normalized_levenshtein(&intersection, &lhs_full) - penalty,
+        normalized_levenshtein(&intersection, &rhs_full) - penalty,
+        normalized_levenshtein(&lhs_full, &rhs_full),
+    ]
+    .iter()
+    .cloned()
+    .fold(0., f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_names() {
+        assert_eq!(
+            1.,
+            name_similarity("Gare Saint-Charles", "gare saint charles")
+        );
+        assert_eq!(1., name_similarity("Église", "EGLISE"));
+        assert_eq!(
+            1.,
+            name_similarity("Saint-Charles Gare", "Gare Saint-Charles")
+        );
+    }
+
+    #[test]
+    fn close_names() {
+        assert!(name_similarity("Gare", "Gare SNCF") > 0.9);
+        assert!(name_similarity("Castellane", "Castelane") > 0.8);
+    }
+
+    #[test]
+    fn subset_names() {
+        let similarity = name_similarity("Gare", "Gare SNCF");
+        assert!(similarity < 1.);
+        assert!(name_similarity("Gare", "Gare Saint-Charles") < similarity);
+        assert_eq!(similarity, name_similarity("Gare SNCF", "Gare"));
+    }
+
+    #[test]
+    fn different_names() {
+        assert!(name_similarity("Castellane", "Vieux Port") < 0.5);
+        assert_eq!(0., name_similarity("", "Vieux Port"));
+    }
+}
@@ -1,5 +1,9 @@
+pub mod equipments;
 pub mod improve_stop_positions;
+pub mod pathways;
 pub mod poi;
+pub mod read_admins;
+pub mod read_equipments;
 pub mod read_shapes;
 pub mod report;
 pub mod runner;
@@ -0,0 +1,754 @@
+//! Extraction of the entrances, pathways and levels of the stop areas from
+//! OpenStreetMap.
+use crate::report::{self, Report};
+use crate::Result;
+use failure::ResultExt;
+use geo::algorithm::haversine_distance::HaversineDistance;
+use geo::Point;
+use log::info;
+use osm_utils::grid_index::{GridIndex, PointIndex};
+use osmpbfreader::{NodeId, OsmId, OsmObj, OsmPbfReader, Tags, Way, WayId};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::path::Path;
+use transit_model::model::Collections;
+use transit_model::objects::{
+    Coord, Level, Pathway, PathwayMode, StopArea, StopLocation, StopType,
+};
+use typed_index_collection::CollectionWithId;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub enum ReportCategory {
+    OsmStopAreaNotMatched,
+    StopAreaWithoutEntrance,
+}
+
+impl report::ReportCategory for ReportCategory {}
+
+/// Options of the extraction of the pathways.
+#[derive(Debug, Clone)]
+pub struct PathwayOptions {
+    /// Maximum distance (in meters) between a stop area and the OSM
+    /// entrances, stairs, elevators and footways not members of its
+    /// `public_transport=stop_area` relation.
+    pub max_distance: f64,
+    /// Walking speed (in m/s), used for the traversal time of the walkways.
+    pub walking_speed: f64,
+    /// Speed (in m/s) along the stairs and the escalators.
+    pub stairs_speed: f64,
+    /// Time (in seconds) to change level with an elevator.
+    pub elevator_time: u32,
+}
+
+impl Default for PathwayOptions {
+    fn default() -> Self {
+        PathwayOptions {
+            max_distance: 150.,
+            walking_speed: 1.12,
+            stairs_speed: 0.5,
+            elevator_time: 60,
+        }
+    }
+}
+
+fn is_stop_area(tags: &Tags) -> bool {
+    tags.contains("public_transport", "stop_area")
+}
+
+fn is_entrance(tags: &Tags) -> bool {
+    tags.contains("railway", "subway_entrance") || tags.contains_key("entrance")
+}
+
+fn is_elevator(tags: &Tags) -> bool {
+    tags.contains("highway", "elevator")
+}
+
+fn is_conveying(tags: &Tags) -> bool {
+    tags.get("conveying")
+        .map_or(false, |conveying| conveying != "no")
+}
+
+fn way_mode(tags: &Tags) -> Option<PathwayMode> {
+    match tags.get("highway").map(|highway| highway.as_str()) {
+        Some("steps") if is_conveying(tags) => Some(PathwayMode::Escalator),
+        Some("steps") => Some(PathwayMode::Stairs),
+        Some("elevator") => Some(PathwayMode::Elevator),
+        Some("footway") | Some("pedestrian") | Some("path") | Some("corridor")
+            if is_conveying(tags) =>
+        {
+            Some(PathwayMode::MovingSidewalk)
+        }
+        Some("footway") | Some("pedestrian") | Some("path") | Some("corridor") => {
+            Some(PathwayMode::Walkway)
+        }
+        _ => None,
+    }
+}
+
+/// The level of an OSM object, given by its `level` tag, or by its `layer`
+/// tag otherwise. Only the first level of a multi-level object (like
+/// `level=-1;0`) is kept.
+fn level(tags: &Tags) -> Option<&str> {
+    tags.get("level")
+        .or_else(|| tags.get("layer"))
+        .and_then(|level| level.split(';').next())
+        .map(str::trim)
+        .filter(|level| level.parse::<f32>().is_ok())
+}
+
+fn node_point(objects: &BTreeMap<OsmId, OsmObj>, node_id: NodeId) -> Option<Point<f64>> {
+    objects
+        .get(&node_id.into())
+        .and_then(|obj| obj.node())
+        .map(|node| Point::new(node.lon(), node.lat()))
+}
+
+fn node_tags(objects: &BTreeMap<OsmId, OsmObj>, node_id: NodeId) -> Option<&Tags> {
+    objects
+        .get(&node_id.into())
+        .and_then(|obj| obj.node())
+        .map(|node| &node.tags)
+}
+
+// The OSM entrances and pathway ways, bucketed in a grid to only look at the
+// ones around each stop area
+struct PathwayIndex<'a> {
+    entrances: PointIndex<NodeId>,
+    ways: GridIndex<&'a Way>,
+}
+
+impl<'a> PathwayIndex<'a> {
+    fn new(objects: &'a BTreeMap<OsmId, OsmObj>, max_distance: f64) -> Self {
+        let mut index = PathwayIndex {
+            entrances: PointIndex::new(max_distance),
+            ways: GridIndex::new(max_distance),
+        };
+        for obj in objects.values() {
+            match obj {
+                OsmObj::Node(node) if is_entrance(&node.tags) => {
+                    let point = Point::new(node.lon(), node.lat());
+                    index.entrances.insert(point, node.id);
+                }
+                OsmObj::Way(way) if way.nodes.len() > 1 && way_mode(&way.tags).is_some() => {
+                    let points: Vec<_> = way
+                        .nodes
+                        .iter()
+                        .filter_map(|node_id| node_point(objects, *node_id))
+                        .collect();
+                    if points.is_empty() {
+                        continue;
+                    }
+                    let min = Point::new(
+                        points
+                            .iter()
+                            .map(|point| point.x())
+                            .fold(f64::INFINITY, f64::min),
+                        points
+                            .iter()
+                            .map(|point| point.y())
+                            .fold(f64::INFINITY, f64::min),
+                    );
+                    let max = Point::new(
+                        points
+                            .iter()
+                            .map(|point| point.x())
+                            .fold(f64::NEG_INFINITY, f64::max),
+                        points
+                            .iter()
+                            .map(|point| point.y())
+                            .fold(f64::NEG_INFINITY, f64::max),
+                    );
+                    index.ways.insert_bbox(&min, &max, way);
+                }
+                _ => {}
+            }
+        }
+        index
+    }
+}
+
+// NTFS stop areas of each OSM stop_area relation, given by the
+// `osm_stop_area_id` code of the stop area, or by the `osm_stop_points_id`
+// codes of its stop points
+fn matched_stop_areas(
+    objects: &BTreeMap<OsmId, OsmObj>,
+    collections: &Collections,
+) -> BTreeMap<OsmId, BTreeSet<String>> {
+    let mut stop_areas_by_osm_id: HashMap<String, BTreeSet<&str>> = HashMap::new();
+    for stop_area in collections.stop_areas.values() {
+        for (_, osm_id) in stop_area
+            .codes
+            .iter()
+            .filter(|(key, _)| key == "osm_stop_area_id")
+        {
+            stop_areas_by_osm_id
+                .entry(osm_id.clone())
+                .or_insert_with(BTreeSet::new)
+                .insert(&stop_area.id);
+        }
+    }
+    let mut stop_areas_by_osm_stop: HashMap<String, BTreeSet<&str>> = HashMap::new();
+    for stop_point in collections.stop_points.values() {
+        for (_, osm_id) in stop_point
+            .codes
+            .iter()
+            .filter(|(key, _)| key == "osm_stop_points_id")
+        {
+            stop_areas_by_osm_stop
+                .entry(osm_id.clone())
+                .or_insert_with(BTreeSet::new)
+                .insert(&stop_point.stop_area_id);
+        }
+    }
+    let mut matched = BTreeMap::new();
+    for relation in objects.values().filter_map(|obj| obj.relation()) {
+        if !is_stop_area(&relation.tags) {
+            continue;
+        }
+        let osm_id = format!("relation:{}", relation.id.0);
+        let stop_area_ids = match stop_areas_by_osm_id.get(&osm_id) {
+            Some(stop_area_ids) => stop_area_ids.clone(),
+            None => relation
+                .refs
+                .iter()
+                .filter_map(|member| {
+                    let member_id = match member.member {
+                        OsmId::Node(node_id) => format!("node:{}", node_id.0),
+                        OsmId::Way(way_id) => format!("way:{}", way_id.0),
+                        OsmId::Relation(relation_id) => format!("relation:{}", relation_id.0),
+                    };
+                    stop_areas_by_osm_stop.get(&member_id)
+                })
+                .flatten()
+                .cloned()
+                .collect(),
+        };
+        matched.insert(
+            relation.id.into(),
+            stop_area_ids.into_iter().map(str::to_string).collect(),
+        );
+    }
+    matched
+}
+
+// A vertex of the graph of the pathways, which is a stop point or a stop
+// location
+#[derive(Debug, Clone, PartialEq)]
+struct Vertex {
+    id: String,
+    stop_type: StopType,
+    node_id: NodeId,
+    level: Option<String>,
+}
+
+struct StopAreaPathways {
+    stop_locations: Vec<StopLocation>,
+    pathways: Vec<Pathway>,
+    levels: BTreeSet<String>,
+}
+
+// Selects the OSM entrances and pathway ways of a stop area, builds their
+// graph and keeps the parts connected to an entrance or a stop point
+fn extract_stop_area(
+    objects: &BTreeMap<OsmId, OsmObj>,
+    index: &PathwayIndex<'_>,
+    relation_members: &BTreeSet<OsmId>,
+    stop_area: &StopArea,
+    stop_point_nodes: &BTreeMap<NodeId, String>,
+    options: &PathwayOptions,
+) -> StopAreaPathways {
+    let stop_area_point = Point::new(stop_area.coord.lon, stop_area.coord.lat);
+    let is_near = |node_id: &NodeId| {
+        node_point(objects, *node_id).map_or(false, |point| {
+            point.haversine_distance(&stop_area_point) <= options.max_distance
+        })
+    };
+    let entrances: BTreeSet<NodeId> = relation_members
+        .iter()
+        .filter_map(|osm_id| match osm_id {
+            OsmId::Node(node_id) => Some(*node_id),
+            _ => None,
+        })
+        .filter(|node_id| node_tags(objects, *node_id).map_or(false, is_entrance))
+        .chain(
+            index
+                .entrances
+                .around(stop_area_point, options.max_distance)
+                .map(|(_, node_id)| *node_id),
+        )
+        .collect();
+    // The ways of the relation and the ones whose bounding box is near the
+    // stop area, in the order of their ids
+    let candidate_ways: BTreeMap<WayId, &Way> = relation_members
+        .iter()
+        .filter_map(|osm_id| objects.get(osm_id))
+        .filter_map(|obj| obj.way())
+        .chain(
+            index
+                .ways
+                .candidates(&stop_area_point, options.max_distance)
+                .cloned(),
+        )
+        .map(|way| (way.id, way))
+        .collect();
+    let ways: Vec<&Way> = candidate_ways
+        .into_iter()
+        .map(|(_, way)| way)
+        .filter(|way| way.nodes.len() > 1)
+        .filter(|way| match way_mode(&way.tags) {
+            Some(_) if relation_members.contains(&way.id.into()) => true,
+            // Outside of the relation, only the footways with a level are kept
+            Some(PathwayMode::Walkway) | Some(PathwayMode::MovingSidewalk) => {
+                level(&way.tags).is_some() && way.nodes.iter().any(is_near)
+            }
+            Some(_) => way.nodes.iter().any(is_near),
+            None => false,
+        })
+        .collect();
+
+    let mut way_counts: HashMap<NodeId, usize> = HashMap::new();
+    for way in &ways {
+        for node_id in way.nodes.iter().collect::<BTreeSet<_>>() {
+            *way_counts.entry(*node_id).or_insert(0) += 1;
+        }
+    }
+    let node_level = |node_id: NodeId, way: Option<&Way>| {
+        node_tags(objects, node_id)
+            .and_then(level)
+            .or_else(|| way.and_then(|way| level(&way.tags)))
+            .map(str::to_string)
+    };
+    let vertex = |node_id: NodeId, way: Option<&Way>| {
+        let level = node_level(node_id, way);
+        if let Some(stop_point_id) = stop_point_nodes.get(&node_id) {
+            return Vertex {
+                id: stop_point_id.clone(),
+                stop_type: StopType::Point,
+                node_id,
+                level,
+            };
+        }
+        let id = format!("{}:osm:node:{}", stop_area.id, node_id.0);
+        let is_elevator_node = node_tags(objects, node_id).map_or(false, is_elevator);
+        match level {
+            // An elevator node is split into a vertex by level
+            Some(level) if is_elevator_node => Vertex {
+                id: format!("{}:{}", id, level),
+                stop_type: StopType::GenericNode,
+                node_id,
+                level: Some(level),
+            },
+            level => Vertex {
+                id,
+                stop_type: if entrances.contains(&node_id) {
+                    StopType::StopEntrance
+                } else {
+                    StopType::GenericNode
+                },
+                node_id,
+                level,
+            },
+        }
+    };
+    let is_key_node = |node_id: &NodeId| {
+        entrances.contains(node_id)
+            || stop_point_nodes.contains_key(node_id)
+            || node_tags(objects, *node_id).map_or(false, is_elevator)
+            || way_counts.get(node_id).map_or(false, |count| *count > 1)
+    };
+
+    let mut vertices: BTreeMap<String, Vertex> = BTreeMap::new();
+    let mut pathways = Vec::new();
+    for way in &ways {
+        let mode = way_mode(&way.tags).unwrap();
+        let mut segments: Vec<Vec<NodeId>> = vec![vec![way.nodes[0]]];
+        for (position, node_id) in way.nodes.iter().enumerate().skip(1) {
+            segments.last_mut().unwrap().push(*node_id);
+            if position + 1 < way.nodes.len() && is_key_node(node_id) {
+                segments.push(vec![*node_id]);
+            }
+        }
+        let nb_segments = segments.len();
+        for (index, segment) in segments.into_iter().enumerate() {
+            let points: Vec<_> = segment
+                .iter()
+                .filter_map(|node_id| node_point(objects, *node_id))
+                .collect();
+            if points.len() != segment.len() {
+                continue;
+            }
+            let length: f64 = points
+                .windows(2)
+                .map(|pair| pair[0].haversine_distance(&pair[1]))
+                .sum();
+            let mut from = vertex(segment[0], Some(way));
+            let mut to = vertex(segment[segment.len() - 1], Some(way));
+            let direction = way
+                .tags
+                .get("conveying")
+                .or_else(|| way.tags.get("oneway"))
+                .map(|direction| direction.as_str());
+            if direction == Some("backward") || direction == Some("-1") {
+                std::mem::swap(&mut from, &mut to);
+            }
+            let is_bidirectional = !matches!(
+                direction,
+                Some("yes") | Some("forward") | Some("backward") | Some("-1")
+            );
+            let traversal_time = match mode {
+                PathwayMode::Elevator => options.elevator_time,
+                PathwayMode::Stairs | PathwayMode::Escalator => {
+                    (length / options.stairs_speed).ceil() as u32
+                }
+                _ => (length / options.walking_speed).ceil() as u32,
+            };
+            let stair_count = match mode {
+                PathwayMode::Stairs if nb_segments == 1 => way
+                    .tags
+                    .get("step_count")
+                    .and_then(|step_count| step_count.parse().ok()),
+                _ => None,
+            };
+            pathways.push(Pathway {
+                id: format!("{}:osm:way:{}:{}", stop_area.id, way.id.0, index),
+                from_stop_id: from.id.clone(),
+                from_stop_type: from.stop_type.clone(),
+                to_stop_id: to.id.clone(),
+                to_stop_type: to.stop_type.clone(),
+                pathway_mode: mode.clone(),
+                is_bidirectional,
+                length: Decimal::from_f64(length).map(|length| length.round_dp(2)),
+                traversal_time: Some(traversal_time),
+                stair_count,
+                max_slope: None,
+                min_width: None,
+                signposted_as: None,
+                reversed_signposted_as: None,
+            });
+            vertices.insert(from.id.clone(), from);
+            vertices.insert(to.id.clone(), to);
+        }
+    }
+    // The entrances not on a pathway are kept alone
+    for node_id in &entrances {
+        let entrance = vertex(*node_id, None);
+        vertices.entry(entrance.id.clone()).or_insert(entrance);
+    }
+    // Elevators between the levels of the elevator nodes
+    let mut elevator_levels: BTreeMap<NodeId, Vec<(f32, String)>> = BTreeMap::new();
+    for node in vertices.values() {
+        match (&node.level, node_tags(objects, node.node_id)) {
+            (Some(level), Some(tags))
+                if is_elevator(tags) && node.stop_type == StopType::GenericNode =>
+            {
+                elevator_levels
+                    .entry(node.node_id)
+                    .or_insert_with(Vec::new)
+                    .push((level.parse().unwrap(), node.id.clone()))
+            }
+            _ => {}
+        }
+    }
+    for (node_id, mut levels) in elevator_levels {
+        levels.sort_by(|lhs, rhs| lhs.0.partial_cmp(&rhs.0).unwrap());
+        for pair in levels.windows(2) {
+            pathways.push(Pathway {
+                id: format!(
+                    "{}:osm:node:{}:{}:{}",
+                    stop_area.id, node_id.0, pair[0].0, pair[1].0
+                ),
+                from_stop_id: pair[0].1.clone(),
+                from_stop_type: StopType::GenericNode,
+                to_stop_id: pair[1].1.clone(),
+                to_stop_type: StopType::GenericNode,
+                pathway_mode: PathwayMode::Elevator,
+                is_bidirectional: true,
+                length: None,
+                traversal_time: Some(options.elevator_time),
+                stair_count: None,
+                max_slope: None,
+                min_width: None,
+                signposted_as: None,
+                reversed_signposted_as: None,
+            });
+        }
+    }
+
+    // Only the parts of the graph connected to an entrance or a stop point
+    // are useful
+    let mut neighbours: HashMap<&str, Vec<&str>> = HashMap::new();
+    for pathway in &pathways {
+        neighbours
+            .entry(&pathway.from_stop_id)
+            .or_insert_with(Vec::new)
+            .push(&pathway.to_stop_id);
+        neighbours
+            .entry(&pathway.to_stop_id)
+            .or_insert_with(Vec::new)
+            .push(&pathway.from_stop_id);
+    }
+    let mut kept: BTreeSet<&str> = BTreeSet::new();
+    let mut queue: VecDeque<&str> = vertices
+        .values()
+        .filter(|vertex| vertex.stop_type != StopType::GenericNode)
+        .map(|vertex| vertex.id.as_str())
+        .collect();
+    while let Some(id) = queue.pop_front() {
+        if kept.insert(id) {
+            queue.extend(neighbours.get(id).into_iter().flatten());
+        }
+    }
+    let is_kept = |pathway: &Pathway| kept.contains(pathway.from_stop_id.as_str());
+    let pathways: Vec<Pathway> = pathways.iter().filter(|p| is_kept(p)).cloned().collect();
+
+    let stop_locations: Vec<StopLocation> = vertices
+        .values()
+        .filter(|vertex| vertex.stop_type != StopType::Point)
+        .filter(|vertex| kept.contains(vertex.id.as_str()))
+        .filter_map(|vertex| {
+            let point = node_point(objects, vertex.node_id)?;
+            let tags = node_tags(objects, vertex.node_id);
+            let name = match vertex.stop_type {
+                StopType::StopEntrance => tags
+                    .and_then(|tags| tags.get("name").or_else(|| tags.get("ref")))
+                    .cloned()
+                    .unwrap_or_else(|| stop_area.name.clone()),
+                _ => String::new(),
+            };
+            Some(StopLocation {
+                id: vertex.id.clone(),
+                name,
+                code: None,
+                comment_links: Default::default(),
+                visible: vertex.stop_type == StopType::StopEntrance,
+                coord: Coord {
+                    lon: point.x(),
+                    lat: point.y(),
+                },
+                parent_id: Some(stop_area.id.clone()),
+                timezone: stop_area.timezone,
+                geometry_id: None,
+                equipment_id: None,
+                level_id: vertex.level.as_ref().map(|level| level_id(level)),
+                stop_type: vertex.stop_type.clone(),
+            })
+        })
+        .collect();
+    let levels = vertices
+        .values()
+        .filter(|vertex| vertex.stop_type != StopType::Point)
+        .filter(|vertex| kept.contains(vertex.id.as_str()))
+        .filter_map(|vertex| vertex.level.clone())
+        .collect();
+    StopAreaPathways {
+        stop_locations,
+        pathways,
+        levels,
+    }
+}
+
+fn level_id(level: &str) -> String {
+    format!("level:{}", level)
+}
+
+fn extract(
+    objects: &BTreeMap<OsmId, OsmObj>,
+    collections: &mut Collections,
+    options: &PathwayOptions,
+    report: &mut Report<ReportCategory>,
+) -> Result<()> {
+    let mut stop_point_nodes: HashMap<&str, BTreeMap<NodeId, String>> = HashMap::new();
+    for stop_point in collections.stop_points.values() {
+        for (_, osm_id) in stop_point
+            .codes
+            .iter()
+            .filter(|(key, _)| key == "osm_stop_points_id")
+        {
+            let node_id = osm_id
+                .strip_prefix("node:")
+                .and_then(|node_id| node_id.parse().ok());
+            if let Some(node_id) = node_id {
+                stop_point_nodes
+                    .entry(&stop_point.stop_area_id)
+                    .or_insert_with(BTreeMap::new)
+                    .entry(NodeId(node_id))
+                    .or_insert_with(|| stop_point.id.clone());
+            }
+        }
+    }
+    let index = PathwayIndex::new(objects, options.max_distance);
+    let mut stop_locations = Vec::new();
+    let mut pathways = Vec::new();
+    let mut levels = BTreeSet::new();
+    let mut extracted_stop_areas = BTreeSet::new();
+    let no_stop_point_nodes = BTreeMap::new();
+    for (osm_id, stop_area_ids) in matched_stop_areas(objects, collections) {
+        if stop_area_ids.is_empty() {
+            report.add_warning(
+                format!(
+                    "osm stop area \"relation:{}\" not matched with a stop area",
+                    osm_id.inner_id()
+                ),
+                ReportCategory::OsmStopAreaNotMatched,
+            );
+            continue;
+        }
+        let relation_members: BTreeSet<OsmId> = objects
+            .get(&osm_id)
+            .and_then(|obj| obj.relation())
+            .map(|relation| relation.refs.iter().map(|member| member.member).collect())
+            .unwrap_or_default();
+        for stop_area_id in stop_area_ids {
+            // A stop area matched with several OSM stop areas is only
+            // extracted once
+            if !extracted_stop_areas.insert(stop_area_id.clone()) {
+                continue;
+            }
+            let stop_area = match collections.stop_areas.get(&stop_area_id) {
+                Some(stop_area) => stop_area,
+                None => continue,
+            };
+            let extracted = extract_stop_area(
+                objects,
+                &index,
+                &relation_members,
+                stop_area,
+                stop_point_nodes
+                    .get(stop_area_id.as_str())
+                    .unwrap_or(&no_stop_point_nodes),
+                options,
+            );
+            if !extracted
+                .stop_locations
+                .iter()
+                .any(|stop_location| stop_location.stop_type == StopType::StopEntrance)
+            {
+                report.add_warning(
+                    format!("no entrance found in osm for stop area {:?}", stop_area_id),
+                    ReportCategory::StopAreaWithoutEntrance,
+                );
+            }
+            stop_locations.extend(extracted.stop_locations);
+            pathways.extend(extracted.pathways);
+            levels.extend(extracted.levels);
+        }
+    }
+    info!(
+        "{} stop locations and {} pathways extracted from osm",
+        stop_locations.len(),
+        pathways.len()
+    );
+
+    // The objects previously extracted for these stop areas are replaced
+    let is_extracted = |id: &str| {
+        extracted_stop_areas
+            .iter()
+            .any(|stop_area_id| id.starts_with(&format!("{}:osm:", stop_area_id)))
+    };
+    let mut all_stop_locations = collections.stop_locations.take();
+    all_stop_locations.retain(|stop_location| !is_extracted(&stop_location.id));
+    all_stop_locations.extend(stop_locations);
+    collections.stop_locations = CollectionWithId::new(all_stop_locations)?;
+    let mut all_pathways = collections.pathways.take();
+    all_pathways.retain(|pathway| !is_extracted(&pathway.id));
+    all_pathways.extend(pathways);
+    collections.pathways = CollectionWithId::new(all_pathways)?;
+    let mut all_levels = collections.levels.take();
+    for level in levels {
+        let id = level_id(&level);
+        if all_levels.iter().all(|existing| existing.id != id) {
+            all_levels.push(Level {
+                id,
+                level_index: level.parse().unwrap(),
+                level_name: Some(level),
+            });
+        }
+    }
+    collections.levels = CollectionWithId::new(all_levels)?;
+    Ok(())
+}
+
+/// Adds the entrances, pathways and levels of the stop areas matched with
+/// an OSM `public_transport=stop_area` relation.
+///
+/// A stop area is matched with a relation through its `osm_stop_area_id`
+/// code, or through the `osm_stop_points_id` codes of its stop points. Its
+/// entrances (`railway=subway_entrance`, `entrance=*`), stairs, elevators
+/// and footways become stop locations and pathways, whose traversal times
+/// are computed from their lengths.
+pub fn from_osm(
+    osm_pbf_path: &Path,
+    collections: &mut Collections,
+    options: &PathwayOptions,
+    report: &mut Report<ReportCategory>,
+) -> Result<()> {
+    let file =
+        File::open(osm_pbf_path).with_context(|_| format!("Error reading {:?}", osm_pbf_path))?;
+    let mut pbf = OsmPbfReader::new(file);
+    // Apart from the members of the stop_area relations, only the entrances
+    // and the ways with a node near a stop area are read
+    let mut stop_area_index = PointIndex::new(options.max_distance);
+    for stop_area in collections.stop_areas.values() {
+        stop_area_index.insert(Point::new(stop_area.coord.lon, stop_area.coord.lat), ());
+    }
+    let mut near_nodes = HashSet::new();
+    for obj in pbf.iter() {
+        if let OsmObj::Node(node) = obj? {
+            let point = Point::new(node.lon(), node.lat());
+            if stop_area_index
+                .around(point, options.max_distance)
+                .next()
+                .is_some()
+            {
+                near_nodes.insert(node.id);
+            }
+        }
+    }
+    pbf.rewind()?;
+    let objects = pbf.get_objs_and_deps(|obj| match obj {
+        OsmObj::Relation(relation) => is_stop_area(&relation.tags),
+        OsmObj::Way(way) => {
+            way_mode(&way.tags).is_some()
+                && way.nodes.iter().any(|node_id| near_nodes.contains(node_id))
+        }
+        OsmObj::Node(node) => is_entrance(&node.tags) && near_nodes.contains(&node.id),
+    })?;
+    extract(&objects, collections, options, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(v: &[(&str, &str)]) -> Tags {
+        v.iter().map(|&(k, v)| (k.into(), v.into())).collect()
+    }
+
+    #[test]
+    fn pathway_modes() {
+        assert_eq!(
+            Some(PathwayMode::Stairs),
+            way_mode(&tags(&[("highway", "steps")]))
+        );
+        assert_eq!(
+            Some(PathwayMode::Escalator),
+            way_mode(&tags(&[("highway", "steps"), ("conveying", "forward")]))
+        );
+        assert_eq!(
+            Some(PathwayMode::Walkway),
+            way_mode(&tags(&[("highway", "footway"), ("conveying", "no")]))
+        );
+        assert_eq!(None, way_mode(&tags(&[("highway", "primary")])));
+    }
+
+    #[test]
+    fn levels() {
+        assert_eq!(Some("-1"), level(&tags(&[("level", "-1;0")])));
+        assert_eq!(Some("1"), level(&tags(&[("layer", "1")])));
+        assert_eq!(Some("0"), level(&tags(&[("level", "0"), ("layer", "-1")])));
+        assert_eq!(None, level(&tags(&[("level", "mezzanine")])));
+    }
+}
@@ -1,8 +1,8 @@
 use crate::Result;
 use log::info;
-use navitia_poi_model::objects::Model;
+use navitia_poi_model::{objects::Model, PoiWriter};
 use osm_utils::{
-    poi::{extract_pois as extract_osm_pois, PoiConfig},
+    poi::{extract_pois as extract_osm_pois, write_pois as write_osm_pois, PoiConfig},
     OsmPbfReader,
 };
 use std::fs::File;
@@ -22,3 +22,16 @@ pub fn extract_pois<P: AsRef<Path>>(osm_path: P, matcher: PoiConfig) -> Result<M
             .collect(),
     })
 }
+
+/// Extracts POIs from OSM and streams them into the `.poi` file `output`.
+pub fn extract_pois_to_path<P: AsRef<Path>, Q: AsRef<Path>>(
+    osm_path: P,
+    matcher: PoiConfig,
+    output: Q,
+) -> Result<()> {
+    info!("Extracting pois from osm");
+    let mut osm_reader = OsmPbfReader::new(File::open(osm_path.as_ref())?);
+    let mut writer = PoiWriter::from_path(output)?;
+    write_osm_pois(&mut osm_reader, &matcher, &mut writer)?;
+    writer.finish()
+}
@@ -4,7 +4,8 @@ use failure::format_err;
 use failure::ResultExt;
 use log::info;
 use navitia_poi_model::objects::{
-    Coord, Model, Poi as NavitiaPoi, PoiType as NavitiaPoiType, Property as NavitiaPoiProperty,
+    Address, Coord, Model, Poi as NavitiaPoi, PoiType as NavitiaPoiType,
+    Property as NavitiaPoiProperty,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap, HashSet};
@@ -90,22 +91,63 @@ fn add_poi_with_properties(
     poi_label: String,
     poi_type: String,
     properties: Vec<NavitiaPoiProperty>,
+    address: Option<Address>,
     pois: &mut BTreeMap<String, NavitiaPoi>,
-) {
+) -> Result<()> {
     let visible = !vec!["GAB", "DEP", "BET"].contains(&sytral_poi.poi_type.as_str());
     let id: String = format!("TCL:{}:{}", sytral_poi.poi_type, poi_id);
+    let coord = Coord::try_new(sytral_poi.coord_x, sytral_poi.coord_y)
+        .with_context(|_| format!("poi '{}' rejected", id))?;
     pois.insert(
         id.clone(),
         NavitiaPoi {
             id,
             name: poi_label,
-            coord: Coord::new(sytral_poi.coord_x, sytral_poi.coord_y),
+            coord,
             poi_type_id: poi_type,
             properties,
+            translated_names: BTreeMap::new(),
+            address,
             visible,
             weight: 0, // This is the weight by default
         },
     );
+    Ok(())
+}
+
+// Splits an address like "2 Rue Roger Salengro" into the house number and
+// the street name
+fn split_house_number(full_address: &str) -> (Option<String>, String) {
+    let full_address = full_address.trim();
+    let mut parts = full_address.splitn(2, ' ');
+    match (parts.next(), parts.next()) {
+        (Some(number), Some(street)) if number.starts_with(|c: char| c.is_ascii_digit()) => {
+            (Some(number.to_string()), street.trim().to_string())
+        }
+        _ => (None, full_address.to_string()),
+    }
+}
+
+fn make_address(sytral_poi: &Poi) -> Option<Address> {
+    if sytral_poi.address.is_none()
+        && sytral_poi.postal_code.is_none()
+        && sytral_poi.city_label.is_none()
+    {
+        return None;
+    }
+    let (house_number, street) = match sytral_poi.address.as_ref() {
+        Some(address) => {
+            let (house_number, street) = split_house_number(address);
+            (house_number, Some(street))
+        }
+        None => (None, None),
+    };
+    Some(Address {
+        house_number,
+        street,
+        postcode: sytral_poi.postal_code.clone(),
+        city: sytral_poi.city_label.clone(),
+    })
 }
 
 fn get_poi_id_without_collision(
@@ -184,8 +226,10 @@ fn extract_from_main_file<P: AsRef<Path>>(
             sytral_poi.label_main.clone().unwrap(),
             poi_type,
             properties,
+            make_address(&sytral_poi),
             pois,
-        );
+        )
+        .with_context(ctx_from_path!(main_file_path))?;
     }
     Ok(())
 }
@@ -271,8 +315,10 @@ fn extract_from_parcs_relais<P: AsRef<Path>>(
             sytral_poi.label_vr.clone().unwrap(),
             poi_type,
             properties,
+            None,
             pois,
-        );
+        )
+        .with_context(ctx_from_path!(parcs_relais_file_path))?;
     }
     Ok(())
 }
@@ -320,8 +366,10 @@ fn extract_from_parcs_velos<P: AsRef<Path>>(
             sytral_poi.label_vr.clone().unwrap(),
             poi_type,
             properties,
+            None,
             pois,
-        );
+        )
+        .with_context(ctx_from_path!(parcs_velos_file_path))?;
     }
     Ok(())
 }
@@ -0,0 +1,185 @@
+//! Assignment of the stop areas to the administrative regions (cities,
+//! departments...) of OpenStreetMap containing them.
+use crate::report::{self, Report};
+use crate::Result;
+use failure::{bail, ResultExt};
+use geo::Point;
+use osm_utils::admin::{read_admins, Admin};
+use osmpbfreader::OsmPbfReader;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+use transit_model::model::Collections;
+use transit_model::objects::AdminStation;
+use typed_index_collection::Collection;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub enum ReportCategory {
+    StopAreaWithoutAdmin,
+    OverlappingAdmins,
+}
+
+impl report::ReportCategory for ReportCategory {}
+
+/// Where the admins of the stop areas are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdminOutput {
+    /// As `osm_admin_<level>_id` and `osm_admin_<level>_name` codes of the
+    /// stop areas.
+    Codes,
+    /// In `admin_stations.txt`.
+    AdminStations,
+}
+
+impl Default for AdminOutput {
+    fn default() -> Self {
+        AdminOutput::Codes
+    }
+}
+
+impl FromStr for AdminOutput {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "codes" => Ok(AdminOutput::Codes),
+            "admin-stations" => Ok(AdminOutput::AdminStations),
+            _ => bail!("unknown admin output {:?}", s),
+        }
+    }
+}
+
+/// Options of the assignment of the admins.
+#[derive(Debug, Clone)]
+pub struct AdminOptions {
+    /// Admin levels of the `boundary=administrative` relations (8 for the
+    /// cities and 6 for the departments in France).
+    pub levels: BTreeSet<u32>,
+    pub output: AdminOutput,
+}
+
+impl Default for AdminOptions {
+    fn default() -> Self {
+        AdminOptions {
+            levels: vec![8].into_iter().collect(),
+            output: AdminOutput::default(),
+        }
+    }
+}
+
+fn admin_id_code_type(level: u32) -> String {
+    format!("osm_admin_{}_id", level)
+}
+
+fn admin_name_code_type(level: u32) -> String {
+    format!("osm_admin_{}_name", level)
+}
+
+/// The admins of each level containing the stop areas.
+fn stop_area_admins<'a>(
+    collections: &Collections,
+    admins: &'a [Admin],
+    levels: &BTreeSet<u32>,
+    report: &mut Report<ReportCategory>,
+) -> BTreeMap<String, Vec<&'a Admin>> {
+    let mut stop_area_admins = BTreeMap::new();
+    for stop_area in collections.stop_areas.values() {
+        let point = Point::new(stop_area.coord.lon, stop_area.coord.lat);
+        let mut admins_of_stop_area = Vec::new();
+        for &level in levels {
+            let containing_admins: Vec<&Admin> = admins
+                .iter()
+                .filter(|admin| admin.level == level && admin.contains(&point))
+                .collect();
+            match containing_admins.as_slice() {
+                [] => report.add_warning(
+                    format!(
+                        "stop area {:?} is in no admin of level {}",
+                        stop_area.id, level
+                    ),
+                    ReportCategory::StopAreaWithoutAdmin,
+                ),
+                [admin] => admins_of_stop_area.push(*admin),
+                [admin, ..] => {
+                    report.add_warning(
+                        format!(
+                            "stop area {:?} is in several admins of level {} ({}), {:?} is kept",
+                            stop_area.id,
+                            level,
+                            containing_admins
+                                .iter()
+                                .map(|admin| admin.id.as_str())
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                            admin.id
+                        ),
+                        ReportCategory::OverlappingAdmins,
+                    );
+                    admins_of_stop_area.push(*admin);
+                }
+            }
+        }
+        stop_area_admins.insert(stop_area.id.clone(), admins_of_stop_area);
+    }
+    stop_area_admins
+}
+
+/// Assigns the stop areas to the OSM administrative regions of the
+/// configured levels containing them.
+///
+/// The admins previously assigned by this function are replaced.
+pub fn from_osm(
+    osm_pbf_path: &Path,
+    collections: &mut Collections,
+    options: &AdminOptions,
+    report: &mut Report<ReportCategory>,
+) -> Result<()> {
+    let file =
+        File::open(osm_pbf_path).with_context(|_| format!("Error reading {:?}", osm_pbf_path))?;
+    let mut pbf = OsmPbfReader::new(file);
+    let admins = read_admins(&mut pbf, &options.levels)?;
+    let stop_area_admins = stop_area_admins(collections, &admins, &options.levels, report);
+    match options.output {
+        AdminOutput::Codes => {
+            let code_types: BTreeSet<String> = options
+                .levels
+                .iter()
+                .flat_map(|&level| vec![admin_id_code_type(level), admin_name_code_type(level)])
+                .collect();
+            for (stop_area_id, admins) in stop_area_admins {
+                if let Some(mut stop_area) = collections.stop_areas.get_mut(&stop_area_id) {
+                    stop_area
+                        .codes
+                        .retain(|(code_type, _)| !code_types.contains(code_type));
+                    for admin in admins {
+                        stop_area
+                            .codes
+                            .insert((admin_id_code_type(admin.level), admin.id.clone()));
+                        stop_area
+                            .codes
+                            .insert((admin_name_code_type(admin.level), admin.name.clone()));
+                    }
+                }
+            }
+        }
+        AdminOutput::AdminStations => {
+            let mut admin_stations: Vec<AdminStation> = collections
+                .admin_stations
+                .take()
+                .into_iter()
+                .filter(|admin_station| !admin_station.admin_id.starts_with("admin:osm:"))
+                .collect();
+            for (stop_area_id, admins) in stop_area_admins {
+                admin_stations.extend(admins.into_iter().map(|admin| AdminStation {
+                    admin_id: admin.id.clone(),
+                    admin_name: admin.name.clone(),
+                    stop_id: stop_area_id.clone(),
+                }));
+            }
+            collections.admin_stations = Collection::new(admin_stations);
+        }
+    }
+    Ok(())
+}
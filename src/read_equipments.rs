@@ -0,0 +1,208 @@
+//! Update of the equipments of the stop points from the tags of their OSM
+//! stops.
+use crate::equipments::{
+    equipment_property_mut, get_prefix, set_stop_point_equipment, stop_point_equipment,
+    APPROPRIATE_SIGNAGE, AUDIBLE_ANNOUNCEMENT, SHELTERED, VISUAL_ANNOUNCEMENT, WHEELCHAIR_BOARDING,
+};
+use crate::report::{self, Report};
+use crate::Result;
+use failure::{bail, format_err};
+use osm_transit_extractor::*;
+use osmpbfreader::Tags;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::str::FromStr;
+use transit_model::model::Collections;
+use transit_model::objects::Availability;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub enum ReportCategory {
+    ConflictingOsmStops,
+    ConflictWithNtfs,
+}
+
+impl report::ReportCategory for ReportCategory {}
+
+/// Which value is kept when the NTFS and OSM both know an equipment
+/// property and disagree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Precedence {
+    /// Keep the NTFS value, OSM only fills the unknown properties.
+    Ntfs,
+    /// Replace the NTFS value by the OSM one.
+    Osm,
+}
+
+impl Default for Precedence {
+    fn default() -> Self {
+        Precedence::Ntfs
+    }
+}
+
+impl FromStr for Precedence {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ntfs" => Ok(Precedence::Ntfs),
+            "osm" => Ok(Precedence::Osm),
+            _ => bail!("unknown precedence {:?}", s),
+        }
+    }
+}
+
+// OSM tags of the stops and the equipment properties they give, the first
+// tags taking precedence. There is no equipment property for the `bench` tag.
+const TAG_PROPERTIES: &[(&str, &str)] = &[
+    ("wheelchair", WHEELCHAIR_BOARDING),
+    ("shelter", SHELTERED),
+    ("tactile_paving", APPROPRIATE_SIGNAGE),
+    ("departures_board", VISUAL_ANNOUNCEMENT),
+    ("passenger_information_display", VISUAL_ANNOUNCEMENT),
+    (
+        "passenger_information_display:speech_output",
+        AUDIBLE_ANNOUNCEMENT,
+    ),
+];
+
+fn availability(tag_value: &str) -> Option<Availability> {
+    match tag_value {
+        "yes" | "designated" | "realtime" => Some(Availability::Available),
+        "no" | "none" => Some(Availability::NotAvailable),
+        _ => None,
+    }
+}
+
+/// The equipment properties given by the tags of an OSM stop.
+fn equipment_properties(tags: &Tags) -> BTreeMap<&'static str, Availability> {
+    let mut properties = BTreeMap::new();
+    for (key, property_name) in TAG_PROPERTIES {
+        if let Some(availability) = tags.get(*key).and_then(|value| availability(value)) {
+            properties.entry(*property_name).or_insert(availability);
+        }
+    }
+    properties
+}
+
+/// Updates the equipments of the stop points with `osm_stop_points_id`
+/// codes from the `wheelchair`, `shelter`, `tactile_paving`,
+/// `departures_board` and `passenger_information_display` tags of their OSM
+/// stops.
+///
+/// The properties on which the OSM stops of a stop point disagree are
+/// ignored, and the disagreements with the NTFS are settled by `precedence`.
+pub fn from_osm(
+    osm_pbf_path: &Path,
+    collections: &mut Collections,
+    precedence: Precedence,
+    report: &mut Report<ReportCategory>,
+) -> Result<()> {
+    let mut parsed_pbf = parse_osm_pbf(
+        osm_pbf_path
+            .to_str()
+            .ok_or_else(|| format_err!("osm pbf path is not valid"))?,
+    );
+    let osm_stop_points_map: HashMap<_, _> = get_stop_points_from_osm(&mut parsed_pbf)
+        .into_iter()
+        .map(|sp| (sp.id.clone(), sp))
+        .collect();
+    let prefix_conf = get_prefix(collections);
+    let stop_points_osm_properties: Vec<(String, BTreeMap<&str, Availability>)> = collections
+        .stop_points
+        .values()
+        .filter_map(|stop_point| {
+            let mut osm_properties = BTreeMap::new();
+            let mut conflicting_properties = Vec::new();
+            let osm_stops = stop_point
+                .codes
+                .iter()
+                .filter(|(code_type, _)| code_type == "osm_stop_points_id")
+                .filter_map(|(_, code_value)| osm_stop_points_map.get(code_value));
+            for osm_stop in osm_stops {
+                for (property_name, availability) in equipment_properties(&osm_stop.all_osm_tags) {
+                    let osm_availability =
+                        osm_properties.entry(property_name).or_insert(availability);
+                    if *osm_availability != availability {
+                        conflicting_properties.push(property_name);
+                    }
+                }
+            }
+            for property_name in conflicting_properties {
+                osm_properties.remove(property_name);
+                report.add_warning(
+                    format!(
+                        "the osm stops of stop point {:?} disagree on {}",
+                        stop_point.id, property_name
+                    ),
+                    ReportCategory::ConflictingOsmStops,
+                );
+            }
+            if osm_properties.is_empty() {
+                None
+            } else {
+                Some((stop_point.id.clone(), osm_properties))
+            }
+        })
+        .collect();
+
+    for (stop_point_id, osm_properties) in stop_points_osm_properties {
+        let mut equipment = stop_point_equipment(collections, &stop_point_id);
+        let mut updated = false;
+        for (property_name, osm_availability) in osm_properties {
+            let availability = match equipment_property_mut(&mut equipment, property_name) {
+                Some(availability) => availability,
+                None => continue,
+            };
+            if *availability == osm_availability {
+                continue;
+            }
+            if *availability == Availability::InformationNotAvailable
+                || precedence == Precedence::Osm
+            {
+                *availability = osm_availability;
+                updated = true;
+            } else {
+                report.add_warning(
+                    format!(
+                        "{} of stop point {:?} is {:?} in the ntfs and {:?} in osm",
+                        property_name, stop_point_id, availability, osm_availability
+                    ),
+                    ReportCategory::ConflictWithNtfs,
+                );
+            }
+        }
+        if updated {
+            set_stop_point_equipment(collections, &stop_point_id, equipment, &prefix_conf);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(v: &[(&str, &str)]) -> Tags {
+        v.iter().map(|&(k, v)| (k.into(), v.into())).collect()
+    }
+
+    #[test]
+    fn osm_tags_to_equipment_properties() {
+        let properties = equipment_properties(&tags(&[
+            ("wheelchair", "yes"),
+            ("shelter", "no"),
+            ("bench", "yes"),
+            ("departures_board", "timetable"),
+            ("passenger_information_display", "yes"),
+        ]));
+        let expected: BTreeMap<_, _> = vec![
+            (WHEELCHAIR_BOARDING, Availability::Available),
+            (SHELTERED, Availability::NotAvailable),
+            (VISUAL_ANNOUNCEMENT, Availability::Available),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(expected, properties);
+    }
+}
@@ -1,15 +1,100 @@
+mod map_matching;
+mod measures;
+mod stitching;
+
+pub use self::map_matching::{from_osm_graph, MapMatching, Profile};
+
+use self::stitching::{fit_to_stops, Gap};
+use crate::report::{self, Report};
 use crate::Result;
 use failure::bail;
 use failure::format_err;
+use geo::Point;
 use log::warn;
 use osm_transit_extractor::*;
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::Path;
+use std::str::FromStr;
 use transit_model::model::Collections;
 use transit_model::objects::{Codes, Geometry, Line as NtfsLine, Route as NtfsRoute};
 use typed_index_collection::CollectionWithId;
 use typed_index_collection::Id as NtfsId;
 
+#[derive(Debug, Serialize, PartialEq)]
+pub enum ReportCategory {
+    GapInShape,
+}
+
+impl report::ReportCategory for ReportCategory {}
+
+/// Options of the reading of the shapes of the OSM relations.
+#[derive(Debug, Clone, Default)]
+pub struct ShapeOptions {
+    /// Stitch the shapes of the routes into a single linestring, oriented
+    /// and cut to their first and last stops when these are closer than
+    /// this distance (in meters).
+    pub fit_routes_to_stops: Option<f64>,
+    /// What to do with the lines and routes already having a geometry.
+    pub geometry_mode: GeometryMode,
+}
+
+/// What to do when a line or a route already has a geometry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeometryMode {
+    /// Keep the existing geometry.
+    Keep,
+    /// Replace the existing geometry by the OSM one.
+    Replace,
+    /// Keep the longer of the existing and the OSM geometries.
+    KeepLonger,
+    /// Keep the geometry closer to the stops of the object.
+    KeepCloser,
+}
+
+impl Default for GeometryMode {
+    fn default() -> Self {
+        GeometryMode::Keep
+    }
+}
+
+impl FromStr for GeometryMode {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "keep" => Ok(GeometryMode::Keep),
+            "replace" => Ok(GeometryMode::Replace),
+            "keep-longer" => Ok(GeometryMode::KeepLonger),
+            "keep-closer" => Ok(GeometryMode::KeepCloser),
+            _ => bail!("unknown geometry mode {:?}", s),
+        }
+    }
+}
+
+impl GeometryMode {
+    fn keeps_existing(
+        self,
+        existing: &geo::Geometry<f64>,
+        new: &geo::Geometry<f64>,
+        stops: &[Point<f64>],
+    ) -> bool {
+        match self {
+            GeometryMode::Keep => true,
+            GeometryMode::Replace => false,
+            GeometryMode::KeepLonger => measures::length(existing) >= measures::length(new),
+            GeometryMode::KeepCloser => match (
+                measures::mean_distance(existing, stops),
+                measures::mean_distance(new, stops),
+            ) {
+                (Some(existing), Some(new)) => existing <= new,
+                (None, Some(_)) => false,
+                (_, None) => true,
+            },
+        }
+    }
+}
+
 pub trait WithGeometry {
     fn set_geometry_id(&mut self, geometry_id: String);
     fn geometry_id(&self) -> &Option<String>;
@@ -47,25 +132,37 @@ where
     }
 }
 
-fn populate_shapes_for_collection<N, O>(
+// The geometry ids are derived from the ids of the objects, so that they
+// are stable between runs. The gaps in a new geometry are only reported when
+// it is written.
+#[allow(clippy::too_many_arguments)]
+fn populate_shapes_for_collection<N, O, F>(
     geometries: &mut Vec<Geometry>,
     ntfs_objects: &mut CollectionWithId<N>,
     osm_objects: &HashMap<String, &O>,
     object_type: &str,
+    mode: GeometryMode,
+    object_stops: &ObjectStops,
+    report: &mut Report<ReportCategory>,
+    mut make_geometry: F,
 ) -> Result<CollectionWithId<N>>
 where
     O: Shape + Id<O>,
     N: Codes + NtfsId<N> + WithGeometry,
+    F: FnMut(&N, &O) -> (geo::Geometry<f64>, Vec<Gap>),
 {
+    let mut geometry_indexes: HashMap<String, usize> = geometries
+        .iter()
+        .enumerate()
+        .map(|(index, geometry)| (geometry.id.clone(), index))
+        .collect();
     let mut objects = ntfs_objects.take();
-    let mut generated_geo_obj_id = 0;
     for obj in objects.iter_mut() {
         if let Some((_, osm_obj_id)) = obj
             .codes()
             .iter()
             .find(|(key, _)| *key == format!("osm_{}_id", object_type))
         {
-            generated_geo_obj_id += 1;
             match osm_objects.get(osm_obj_id) {
                 Some(osm_object) => {
                     if osm_object.get_shape().is_empty() {
@@ -78,18 +175,48 @@ where
                         );
                         continue;
                     }
-                    let geo_id = format!("geo:{}:osm:{}", object_type, generated_geo_obj_id);
-                    match obj.geometry_id() {
-                        None => obj.set_geometry_id(geo_id.clone()),
-                        Some(_) => {
+                    if obj.geometry_id().is_some() && mode == GeometryMode::Keep {
+                        warn!("geometry already exists for {} {:?}", object_type, obj.id());
+                        continue;
+                    }
+                    let (geometry, gaps) = make_geometry(obj, *osm_object);
+                    let existing = obj
+                        .geometry_id()
+                        .as_ref()
+                        .and_then(|geometry_id| geometry_indexes.get(geometry_id))
+                        .map(|&index| &geometries[index].geometry);
+                    if let Some(existing) = existing {
+                        let stops = object_stops.get(obj.id()).map(Vec::as_slice).unwrap_or(&[]);
+                        if mode.keeps_existing(existing, &geometry, stops) {
                             warn!("geometry already exists for {} {:?}", object_type, obj.id());
                             continue;
                         }
                     }
-                    geometries.push(Geometry {
-                        id: geo_id,
-                        geometry: shape_to_multi_line_string(*osm_object).into(),
-                    });
+                    for gap in gaps {
+                        report.add_warning(
+                            format!(
+                                "gap of {:.0}m at ({}, {}) in the shape of {} {:?}",
+                                gap.length,
+                                gap.location.x(),
+                                gap.location.y(),
+                                object_type,
+                                obj.id()
+                            ),
+                            ReportCategory::GapInShape,
+                        );
+                    }
+                    let geo_id = format!("geo:{}:osm:{}", object_type, obj.id());
+                    obj.set_geometry_id(geo_id.clone());
+                    match geometry_indexes.get(&geo_id) {
+                        Some(&index) => geometries[index].geometry = geometry,
+                        None => {
+                            geometry_indexes.insert(geo_id.clone(), geometries.len());
+                            geometries.push(Geometry {
+                                id: geo_id,
+                                geometry,
+                            });
+                        }
+                    }
                 }
                 None => {
                     warn!(
@@ -104,7 +231,98 @@ where
     CollectionWithId::new(objects).map_err(|e| format_err!("{}", e))
 }
 
-pub fn from_osm(osm_pbf_path: &Path, collections: &mut Collections) -> Result<()> {
+// Coordinates of the stops of each object, by object id
+type ObjectStops = HashMap<String, Vec<Point<f64>>>;
+
+// Coordinates of the stops of each route and of each line
+fn object_stops(collections: &Collections) -> (ObjectStops, ObjectStops) {
+    let mut route_stops = HashMap::new();
+    for vj in collections.vehicle_journeys.values() {
+        route_stops
+            .entry(vj.route_id.as_str())
+            .or_insert_with(BTreeSet::new)
+            .extend(
+                vj.stop_times
+                    .iter()
+                    .map(|stop_time| stop_time.stop_point_idx),
+            );
+    }
+    let mut line_stops = HashMap::new();
+    for (route_id, stop_point_idxs) in &route_stops {
+        if let Some(route) = collections.routes.get(route_id) {
+            line_stops
+                .entry(route.line_id.as_str())
+                .or_insert_with(BTreeSet::new)
+                .extend(stop_point_idxs.iter().cloned());
+        }
+    }
+    let to_points = |stops: HashMap<&str, BTreeSet<_>>| -> ObjectStops {
+        stops
+            .into_iter()
+            .map(|(id, stop_point_idxs)| {
+                let points = stop_point_idxs
+                    .into_iter()
+                    .map(|idx| {
+                        let coord = collections.stop_points[idx].coord;
+                        Point::new(coord.lon, coord.lat)
+                    })
+                    .collect();
+                (id.to_string(), points)
+            })
+            .collect()
+    };
+    (to_points(line_stops), to_points(route_stops))
+}
+
+fn referenced_geometry_ids(collections: &Collections) -> HashSet<String> {
+    let lines = collections.lines.values().map(|o| &o.geometry_id);
+    let routes = collections.routes.values().map(|o| &o.geometry_id);
+    let vjs = collections
+        .vehicle_journeys
+        .values()
+        .map(|o| &o.geometry_id);
+    let stop_points = collections.stop_points.values().map(|o| &o.geometry_id);
+    let stop_areas = collections.stop_areas.values().map(|o| &o.geometry_id);
+    lines
+        .chain(routes)
+        .chain(vjs)
+        .chain(stop_points)
+        .chain(stop_areas)
+        .filter_map(Clone::clone)
+        .collect()
+}
+
+// First and last stops of the longest trip of each route
+fn route_endpoints(collections: &Collections) -> HashMap<&str, (Point<f64>, Point<f64>)> {
+    let mut longest_trips = HashMap::new();
+    for vj in collections.vehicle_journeys.values() {
+        let longest_trip = longest_trips.entry(vj.route_id.as_str()).or_insert(vj);
+        if vj.stop_times.len() > longest_trip.stop_times.len() {
+            *longest_trip = vj;
+        }
+    }
+    let point = |stop_time: &transit_model::objects::StopTime| {
+        let coord = collections.stop_points[stop_time.stop_point_idx].coord;
+        Point::new(coord.lon, coord.lat)
+    };
+    longest_trips
+        .into_iter()
+        .filter_map(|(route_id, vj)| {
+            let first = vj.stop_times.first()?;
+            let last = vj.stop_times.last()?;
+            Some((route_id, (point(first), point(last))))
+        })
+        .collect()
+}
+
+/// Adds the shapes of the OSM relations of the lines and routes with
+/// `osm_line_id` and `osm_route_id` codes.
+pub fn from_osm(
+    osm_pbf_path: &Path,
+    collections: &mut Collections,
+    options: &ShapeOptions,
+    report: &mut Report<ReportCategory>,
+) -> Result<()> {
     let mut parsed_pbf = parse_osm_pbf(
         osm_pbf_path
             .to_str()
@@ -113,19 +331,52 @@ pub fn from_osm(osm_pbf_path: &Path, collections: &mut Collections) -> Result<()
     let objects = get_osm_tcobjects(&mut parsed_pbf, false);
     let osm_lines_by_id = osm_objects_by_id(&objects.lines, "lines")?;
     let osm_routes_by_id = osm_objects_by_id(&objects.routes, "routes")?;
+    let route_endpoints: HashMap<String, _> = route_endpoints(collections)
+        .into_iter()
+        .map(|(route_id, endpoints)| (route_id.to_string(), endpoints))
+        .collect();
+    let (line_stops, route_stops) = if options.geometry_mode == GeometryMode::KeepCloser {
+        object_stops(collections)
+    } else {
+        Default::default()
+    };
+    let referenced_before = referenced_geometry_ids(collections);
     let mut geometries = collections.geometries.take();
     collections.lines = populate_shapes_for_collection(
         &mut geometries,
         &mut collections.lines,
         &osm_lines_by_id,
         "line",
+        options.geometry_mode,
+        &line_stops,
+        report,
+        |_, osm_line| (shape_to_multi_line_string(osm_line).into(), Vec::new()),
     )?;
     collections.routes = populate_shapes_for_collection(
         &mut geometries,
         &mut collections.routes,
         &osm_routes_by_id,
         "route",
+        options.geometry_mode,
+        &route_stops,
+        report,
+        |route, osm_route| {
+            let shape = shape_to_multi_line_string(osm_route);
+            match (options.fit_routes_to_stops, route_endpoints.get(&route.id)) {
+                (Some(max_stop_distance), Some((first_stop, last_stop))) => {
+                    let (line_string, gaps) =
+                        fit_to_stops(&shape, first_stop, last_stop, max_stop_distance);
+                    (line_string.into(), gaps)
+                }
+                _ => (shape.into(), Vec::new()),
+            }
+        },
     )?;
+    // The replaced geometries are removed when nothing references them anymore
+    let referenced_after = referenced_geometry_ids(collections);
+    geometries.retain(|geometry| {
+        !referenced_before.contains(&geometry.id) || referenced_after.contains(&geometry.id)
+    });
     collections.geometries = CollectionWithId::new(geometries)?;
 
     Ok(())
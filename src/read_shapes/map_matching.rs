@@ -0,0 +1,621 @@
+use super::measures::project;
+use crate::Result;
+use failure::ResultExt;
+use geo::algorithm::haversine_distance::HaversineDistance;
+use geo::{LineString, Point};
+use log::{info, warn};
+use osm_utils::grid_index::GridIndex;
+use osmpbfreader::{OsmId, OsmObj, OsmPbfReader, Tags};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap};
+use std::fs::File;
+use std::path::Path;
+use transit_model::model::Collections;
+use transit_model::objects::{Geometry, StopPoint, VehicleJourney};
+use typed_index_collection::{CollectionWithId, Idx};
+
+/// The OSM ways a mode of transport runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Profile {
+    Road,
+    Tram,
+    Subway,
+    Rail,
+}
+
+impl Profile {
+    /// Profile of an NTFS physical mode, if it runs on roads or rails.
+    pub fn from_physical_mode(physical_mode_id: &str) -> Option<Self> {
+        match physical_mode_id {
+            "Bus" | "BusRapidTransit" | "Coach" | "Shuttle" | "Taxi" | "Trolleybus" => {
+                Some(Profile::Road)
+            }
+            "Tramway" => Some(Profile::Tram),
+            "Metro" => Some(Profile::Subway),
+            "Train" | "LocalTrain" | "LongDistanceTrain" | "RapidTransit" | "RailShuttle" => {
+                Some(Profile::Rail)
+            }
+            _ => None,
+        }
+    }
+
+    fn accepts(self, tags: &Tags) -> bool {
+        let (key, values): (&str, &[&str]) = match self {
+            Profile::Road => (
+                "highway",
+                &[
+                    "motorway",
+                    "motorway_link",
+                    "trunk",
+                    "trunk_link",
+                    "primary",
+                    "primary_link",
+                    "secondary",
+                    "secondary_link",
+                    "tertiary",
+                    "tertiary_link",
+                    "unclassified",
+                    "residential",
+                    "living_street",
+                    "service",
+                    "road",
+                    "busway",
+                    "bus_guideway",
+                ],
+            ),
+            Profile::Tram => ("railway", &["tram", "light_rail"]),
+            Profile::Subway => ("railway", &["subway", "light_rail"]),
+            Profile::Rail => ("railway", &["rail", "light_rail", "narrow_gauge"]),
+        };
+        tags.get(key)
+            .map(|value| values.contains(&value.as_str()))
+            .unwrap_or(false)
+    }
+
+    /// Whether a way can be used forward and backward.
+    fn directions(self, tags: &Tags) -> (bool, bool) {
+        if self != Profile::Road
+            || tags.contains("oneway:bus", "no")
+            || tags.contains("oneway:psv", "no")
+        {
+            return (true, true);
+        }
+        match tags.get("oneway").map(|value| value.as_str()) {
+            Some("yes") | Some("true") | Some("1") => (true, false),
+            Some("-1") | Some("reverse") => (false, true),
+            Some("no") => (true, true),
+            _ => (true, !tags.contains("junction", "roundabout")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct State {
+    estimate: f64,
+    cost: f64,
+    node: usize,
+}
+
+impl Eq for State {}
+
+// Reversed on the estimate, for the BinaryHeap to be a min-heap
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .partial_cmp(&self.estimate)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A segment between 2 consecutive nodes of a way.
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    from: usize,
+    to: usize,
+    forward: bool,
+    backward: bool,
+}
+
+/// A point projected on a segment, `ratio` being its position from the
+/// start (0) to the end (1) of the segment.
+#[derive(Debug, Clone, Copy)]
+struct Snap {
+    segment: usize,
+    point: Point<f64>,
+    ratio: f64,
+}
+
+/// The graph of the OSM ways of a profile.
+///
+/// The segments of the ways are bucketed in a grid whose cells are about
+/// `snapping_distance` meters high, to find the nearest way of a stop point,
+/// the stop point being projected on it.
+#[derive(Debug)]
+pub(crate) struct Graph {
+    snapping_distance: f64,
+    points: Vec<Point<f64>>,
+    edges: Vec<Vec<(usize, f64)>>,
+    node_indexes: HashMap<i64, usize>,
+    segments: Vec<Segment>,
+    segment_index: GridIndex<usize>,
+}
+
+/// Reads the OSM ways of all the `profiles` at once, and builds the graph
+/// of each profile.
+fn graphs_from_pbf(
+    osm_pbf_path: &Path,
+    profiles: &BTreeSet<Profile>,
+    snapping_distance: f64,
+) -> Result<BTreeMap<Profile, Graph>> {
+    let file =
+        File::open(osm_pbf_path).with_context(|_| format!("Error reading {:?}", osm_pbf_path))?;
+    let mut pbf = OsmPbfReader::new(file);
+    let objects = pbf.get_objs_and_deps(|obj| {
+        obj.is_way() && profiles.iter().any(|profile| profile.accepts(obj.tags()))
+    })?;
+    Ok(profiles
+        .iter()
+        .map(|&profile| {
+            let graph = Graph::from_objects(&objects, profile, snapping_distance);
+            (profile, graph)
+        })
+        .collect())
+}
+
+impl Graph {
+    pub(crate) fn new(snapping_distance: f64) -> Self {
+        Graph {
+            snapping_distance,
+            points: Vec::new(),
+            edges: Vec::new(),
+            node_indexes: HashMap::new(),
+            segments: Vec::new(),
+            segment_index: GridIndex::new(snapping_distance),
+        }
+    }
+
+    fn from_objects(
+        objects: &BTreeMap<OsmId, OsmObj>,
+        profile: Profile,
+        snapping_distance: f64,
+    ) -> Self {
+        let mut graph = Graph::new(snapping_distance);
+        for way in objects
+            .values()
+            .filter_map(|obj| obj.way())
+            .filter(|way| profile.accepts(&way.tags))
+        {
+            let nodes: Vec<_> = way
+                .nodes
+                .iter()
+                .filter_map(|node_id| objects.get(&(*node_id).into()))
+                .filter_map(|obj| obj.node())
+                .map(|node| (node.id.0, Point::new(node.lon(), node.lat())))
+                .collect();
+            let (forward, backward) = profile.directions(&way.tags);
+            graph.add_way(&nodes, forward, backward);
+        }
+        graph
+    }
+
+    fn node(&mut self, osm_id: i64, point: Point<f64>) -> usize {
+        if let Some(&node) = self.node_indexes.get(&osm_id) {
+            return node;
+        }
+        let node = self.points.len();
+        self.points.push(point);
+        self.edges.push(Vec::new());
+        self.node_indexes.insert(osm_id, node);
+        node
+    }
+
+    /// Adds a way, given as its OSM nodes, usable forward and/or backward.
+    pub(crate) fn add_way(&mut self, nodes: &[(i64, Point<f64>)], forward: bool, backward: bool) {
+        for pair in nodes.windows(2) {
+            let from = self.node(pair[0].0, pair[0].1);
+            let to = self.node(pair[1].0, pair[1].1);
+            let length = pair[0].1.haversine_distance(&pair[1].1);
+            if forward {
+                self.edges[from].push((to, length));
+            }
+            if backward {
+                self.edges[to].push((from, length));
+            }
+            let (min, max) = (
+                Point::new(
+                    pair[0].1.x().min(pair[1].1.x()),
+                    pair[0].1.y().min(pair[1].1.y()),
+                ),
+                Point::new(
+                    pair[0].1.x().max(pair[1].1.x()),
+                    pair[0].1.y().max(pair[1].1.y()),
+                ),
+            );
+            self.segment_index
+                .insert_bbox(&min, &max, self.segments.len());
+            self.segments.push(Segment {
+                from,
+                to,
+                forward,
+                backward,
+            });
+        }
+    }
+
+    /// The projection of `point` on the nearest segment, within the
+    /// snapping distance.
+    fn snap(&self, point: &Point<f64>) -> Option<Snap> {
+        self.segment_index
+            .candidates(point, self.snapping_distance)
+            .map(|&segment_idx| {
+                let segment = &self.segments[segment_idx];
+                let (ratio, projected) =
+                    project(point, &self.points[segment.from], &self.points[segment.to]);
+                let snap = Snap {
+                    segment: segment_idx,
+                    point: projected,
+                    ratio,
+                };
+                (point.haversine_distance(&projected), snap)
+            })
+            .filter(|(distance, _)| *distance <= self.snapping_distance)
+            .min_by(|(lhs, _), (rhs, _)| lhs.partial_cmp(rhs).unwrap_or(Ordering::Equal))
+            .map(|(_, snap)| snap)
+    }
+
+    /// The shortest path from one of the `sources` to one of the `targets`
+    /// (A* with the distance as the crow flies to `target` as heuristic),
+    /// each source and target being given with the cost to reach it from
+    /// the start, or to reach the end from it.
+    pub(crate) fn shortest_path(
+        &self,
+        sources: &[(usize, f64)],
+        targets: &[(usize, f64)],
+        target: &Point<f64>,
+    ) -> Option<Vec<usize>> {
+        // A virtual node after all the targets
+        let end = self.points.len();
+        let mut costs = HashMap::new();
+        let mut previous = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        for &(node, cost) in sources {
+            if cost < *costs.get(&node).unwrap_or(&f64::INFINITY) {
+                costs.insert(node, cost);
+                heap.push(State {
+                    estimate: cost + self.points[node].haversine_distance(target),
+                    cost,
+                    node,
+                });
+            }
+        }
+        while let Some(State { cost, node, .. }) = heap.pop() {
+            if node == end {
+                let mut path = Vec::new();
+                let mut current = end;
+                while let Some(&previous_node) = previous.get(&current) {
+                    path.push(previous_node);
+                    current = previous_node;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if cost > *costs.get(&node).unwrap_or(&f64::INFINITY) {
+                continue;
+            }
+            let to_end = targets
+                .iter()
+                .filter(|(target_node, _)| *target_node == node)
+                .map(|&(_, cost_to_end)| (end, cost_to_end));
+            for (next, length) in self.edges[node].iter().cloned().chain(to_end) {
+                let next_cost = cost + length;
+                if next_cost < *costs.get(&next).unwrap_or(&f64::INFINITY) {
+                    costs.insert(next, next_cost);
+                    previous.insert(next, node);
+                    let estimate = if next == end {
+                        next_cost
+                    } else {
+                        next_cost + self.points[next].haversine_distance(target)
+                    };
+                    heap.push(State {
+                        estimate,
+                        cost: next_cost,
+                        node: next,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// The path from a snapped stop to the next one.
+    fn path_between(&self, from: &Snap, to: &Snap) -> Option<Vec<Point<f64>>> {
+        let from_segment = &self.segments[from.segment];
+        let to_segment = &self.segments[to.segment];
+        if from.segment == to.segment
+            && ((from_segment.forward && from.ratio <= to.ratio)
+                || (from_segment.backward && from.ratio >= to.ratio))
+        {
+            return Some(vec![from.point, to.point]);
+        }
+        let mut sources = Vec::new();
+        if from_segment.forward {
+            let node = from_segment.to;
+            sources.push((node, from.point.haversine_distance(&self.points[node])));
+        }
+        if from_segment.backward {
+            let node = from_segment.from;
+            sources.push((node, from.point.haversine_distance(&self.points[node])));
+        }
+        let mut targets = Vec::new();
+        if to_segment.forward {
+            let node = to_segment.from;
+            targets.push((node, self.points[node].haversine_distance(&to.point)));
+        }
+        if to_segment.backward {
+            let node = to_segment.to;
+            targets.push((node, self.points[node].haversine_distance(&to.point)));
+        }
+        let nodes = self.shortest_path(&sources, &targets, &to.point)?;
+        let mut path = vec![from.point];
+        path.extend(nodes.into_iter().map(|node| self.points[node]));
+        path.push(to.point);
+        Some(path)
+    }
+
+    /// The path going through all the stops, if each stop can be snapped
+    /// to a way and each stop can be reached from the previous one.
+    pub(crate) fn path_through(&self, stops: &[Point<f64>]) -> Option<Vec<Point<f64>>> {
+        let snaps = stops
+            .iter()
+            .map(|stop| self.snap(stop))
+            .collect::<Option<Vec<_>>>()?;
+        let mut path = vec![snaps.first()?.point];
+        for pair in snaps.windows(2) {
+            path.extend(self.path_between(&pair[0], &pair[1])?.into_iter().skip(1));
+        }
+        // A stop snapped on a node
+        path.dedup();
+        if path.len() < 2 {
+            return None;
+        }
+        Some(path)
+    }
+}
+
+/// Options of the generation of shapes on the OSM ways.
+#[derive(Debug, Clone)]
+pub struct MapMatching {
+    /// Maximum distance (in meters) between a stop point and the OSM way it
+    /// is snapped to.
+    pub max_snapping_distance: f64,
+}
+
+type Patterns = BTreeMap<Vec<Idx<StopPoint>>, Vec<Idx<VehicleJourney>>>;
+
+/// Generates the shapes of the trips without geometry, by routing between
+/// their stops on the OSM ways of their physical mode.
+///
+/// Each route without geometry gets the shape of its journey pattern with
+/// the most stops.
+pub fn from_osm_graph(
+    osm_pbf_path: &Path,
+    collections: &mut Collections,
+    map_matching: &MapMatching,
+) -> Result<()> {
+    let mut patterns_by_profile: BTreeMap<Profile, Patterns> = BTreeMap::new();
+    for (vj_idx, vj) in collections
+        .vehicle_journeys
+        .iter()
+        .filter(|(_, vj)| vj.geometry_id.is_none())
+    {
+        let profile = match Profile::from_physical_mode(&vj.physical_mode_id) {
+            Some(profile) => profile,
+            None => continue,
+        };
+        patterns_by_profile
+            .entry(profile)
+            .or_insert_with(BTreeMap::new)
+            .entry(vj.stop_times.iter().map(|st| st.stop_point_idx).collect())
+            .or_insert_with(Vec::new)
+            .push(vj_idx);
+    }
+    if patterns_by_profile.is_empty() {
+        return Ok(());
+    }
+    let profiles = patterns_by_profile.keys().cloned().collect();
+    let graphs = graphs_from_pbf(osm_pbf_path, &profiles, map_matching.max_snapping_distance)?;
+    let mut geometries = collections.geometries.take();
+    let mut vj_geometries = Vec::new();
+    // route id => (number of stops, geometry id)
+    let mut route_geometries: BTreeMap<String, (usize, String)> = BTreeMap::new();
+    for (profile, patterns) in patterns_by_profile {
+        let graph = &graphs[&profile];
+        info!(
+            "{} osm nodes found for the profile {:?}",
+            graph.points.len(),
+            profile
+        );
+        for (stop_points, vj_idxs) in patterns {
+            // The smallest trip id names the geometry, whatever the order
+            // of the trips in the input
+            let first_vj = vj_idxs
+                .iter()
+                .map(|&vj_idx| &collections.vehicle_journeys[vj_idx])
+                .min_by(|vj1, vj2| vj1.id.cmp(&vj2.id))
+                .expect("a journey pattern has at least one trip");
+            let stops: Vec<_> = stop_points
+                .iter()
+                .map(|&idx| {
+                    let coord = collections.stop_points[idx].coord;
+                    Point::new(coord.lon, coord.lat)
+                })
+                .collect();
+            let path = match graph.path_through(&stops) {
+                Some(path) => path,
+                None => {
+                    warn!(
+                        "no path found on the osm ways for the journey pattern of trip {:?}",
+                        first_vj.id
+                    );
+                    continue;
+                }
+            };
+            let geo_id = format!("geo:trip:osm_graph:{}", first_vj.id);
+            geometries.push(Geometry {
+                id: geo_id.clone(),
+                geometry: LineString::from(path).into(),
+            });
+            for &vj_idx in &vj_idxs {
+                let route_id = &collections.vehicle_journeys[vj_idx].route_id;
+                let route_geometry = route_geometries
+                    .entry(route_id.clone())
+                    .or_insert((0, String::new()));
+                if stop_points.len() > route_geometry.0 {
+                    *route_geometry = (stop_points.len(), geo_id.clone());
+                }
+            }
+            vj_geometries.extend(vj_idxs.into_iter().map(|vj_idx| (vj_idx, geo_id.clone())));
+        }
+    }
+    for (vj_idx, geo_id) in vj_geometries {
+        collections.vehicle_journeys.index_mut(vj_idx).geometry_id = Some(geo_id);
+    }
+    for (route_id, (_, geo_id)) in route_geometries {
+        if let Some(mut route) = collections.routes.get_mut(&route_id) {
+            if route.geometry_id.is_none() {
+                route.geometry_id = Some(geo_id);
+            }
+        }
+    }
+    collections.geometries = CollectionWithId::new(geometries)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A square of 4 nodes about 100m apart, with a diagonal way usable
+    // only from node 1 to node 3
+    fn graph() -> Graph {
+        let nodes = [
+            (1, Point::new(2.35, 48.85)),
+            (2, Point::new(2.3514, 48.85)),
+            (3, Point::new(2.3514, 48.851)),
+            (4, Point::new(2.35, 48.851)),
+        ];
+        let mut graph = Graph::new(30.);
+        graph.add_way(
+            &[nodes[0], nodes[1], nodes[2], nodes[3], nodes[0]],
+            true,
+            true,
+        );
+        graph.add_way(&[nodes[0], nodes[2]], true, false);
+        graph
+    }
+
+    fn assert_path_eq(expected: &[Point<f64>], path: Option<Vec<Point<f64>>>) {
+        let path = path.unwrap();
+        assert_eq!(expected.len(), path.len(), "{:?}", path);
+        for (expected, point) in expected.iter().zip(path) {
+            assert!(
+                (expected.x() - point.x()).abs() < 1e-9 && (expected.y() - point.y()).abs() < 1e-9,
+                "{:?} != {:?}",
+                expected,
+                point
+            );
+        }
+    }
+
+    #[test]
+    fn shortest_path_follows_oneways() {
+        let graph = graph();
+        let node = |osm_id| graph.node_indexes[&osm_id];
+        let point = |osm_id| graph.points[node(osm_id)];
+        assert_eq!(
+            Some(vec![node(1), node(3)]),
+            graph.shortest_path(&[(node(1), 0.)], &[(node(3), 0.)], &point(3))
+        );
+        let path = graph
+            .shortest_path(&[(node(3), 0.)], &[(node(1), 0.)], &point(1))
+            .unwrap();
+        assert_eq!(3, path.len());
+        assert_eq!(node(3), path[0]);
+        assert_eq!(node(1), path[2]);
+    }
+
+    #[test]
+    fn stops_projected_on_the_ways() {
+        let graph = graph();
+        // ~10m north of the way between nodes 1 and 2
+        let snap = graph.snap(&Point::new(2.3507, 48.85009)).unwrap();
+        assert!((snap.point.x() - 2.3507).abs() < 1e-9);
+        assert!((snap.point.y() - 48.85).abs() < 1e-9);
+        assert!((snap.ratio - 0.5).abs() < 1e-6);
+        // ~55m south of the ways
+        assert!(graph.snap(&Point::new(2.3507, 48.8495)).is_none());
+    }
+
+    #[test]
+    fn path_through_stops() {
+        let graph = graph();
+        // on the same way, ~10m from node 1 and from node 2
+        let stops = [Point::new(2.3501, 48.85), Point::new(2.3513, 48.85)];
+        assert_path_eq(&stops, graph.path_through(&stops));
+        // ~10m north of the way between nodes 1 and 2, then on the way
+        // between nodes 2 and 3
+        let stops = [Point::new(2.3507, 48.85009), Point::new(2.3514, 48.8505)];
+        assert_path_eq(
+            &[
+                Point::new(2.3507, 48.85),
+                Point::new(2.3514, 48.85),
+                Point::new(2.3514, 48.8505),
+            ],
+            graph.path_through(&stops),
+        );
+        // in the middle of the oneway diagonal, to near node 1
+        let stops = [Point::new(2.3507, 48.8505), Point::new(2.3501, 48.85)];
+        assert_path_eq(
+            &[
+                Point::new(2.3507, 48.8505),
+                Point::new(2.3514, 48.851),
+                Point::new(2.3514, 48.85),
+                Point::new(2.3501, 48.85),
+            ],
+            graph.path_through(&stops),
+        );
+        // too far from the ways
+        let stops = [Point::new(2.3507, 48.8495), Point::new(2.3513, 48.85)];
+        assert_eq!(None, graph.path_through(&stops));
+    }
+
+    #[test]
+    fn profiles() {
+        let tags =
+            |v: &[(&str, &str)]| -> Tags { v.iter().map(|&(k, v)| (k.into(), v.into())).collect() };
+        assert!(Profile::Road.accepts(&tags(&[("highway", "primary")])));
+        assert!(!Profile::Road.accepts(&tags(&[("highway", "footway")])));
+        assert!(Profile::Tram.accepts(&tags(&[("railway", "tram")])));
+        assert_eq!(
+            (true, false),
+            Profile::Road.directions(&tags(&[("oneway", "yes")]))
+        );
+        assert_eq!(
+            (true, true),
+            Profile::Road.directions(&tags(&[("oneway", "yes"), ("oneway:bus", "no")]))
+        );
+        assert_eq!(
+            (true, true),
+            Profile::Rail.directions(&tags(&[("oneway", "yes")]))
+        );
+        assert_eq!(Some(Profile::Road), Profile::from_physical_mode("Bus"));
+        assert_eq!(None, Profile::from_physical_mode("Ferry"));
+    }
+}
@@ -0,0 +1,114 @@
+use geo::algorithm::haversine_distance::HaversineDistance;
+use geo::{Geometry, Point};
+use std::cmp::Ordering;
+
+fn paths(geometry: &Geometry<f64>) -> Vec<Vec<Point<f64>>> {
+    match geometry {
+        Geometry::LineString(line_string) => vec![line_string.points_iter().collect()],
+        Geometry::MultiLineString(multi_line_string) => multi_line_string
+            .0
+            .iter()
+            .map(|line_string| line_string.points_iter().collect())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Length (in meters) of a linestring or a multilinestring.
+pub(crate) fn length(geometry: &Geometry<f64>) -> f64 {
+    paths(geometry)
+        .iter()
+        .flat_map(|path| path.windows(2))
+        .map(|segment| segment[0].haversine_distance(&segment[1]))
+        .sum()
+}
+
+/// Projection of a point on a segment: the ratio of the projection along
+/// the segment, and the projected point.
+///
+/// The longitudes are scaled to project on a locally orthonormal plane.
+pub(crate) fn project(point: &Point<f64>, from: &Point<f64>, to: &Point<f64>) -> (f64, Point<f64>) {
+    let scale = point.y().to_radians().cos();
+    let (ax, ay) = ((from.x() - point.x()) * scale, from.y() - point.y());
+    let (bx, by) = ((to.x() - point.x()) * scale, to.y() - point.y());
+    let (dx, dy) = (bx - ax, by - ay);
+    let squared_length = dx * dx + dy * dy;
+    let ratio = if squared_length == 0. {
+        0.
+    } else {
+        (-(ax * dx + ay * dy) / squared_length).clamp(0., 1.)
+    };
+    let projected = Point::new(
+        from.x() + (to.x() - from.x()) * ratio,
+        from.y() + (to.y() - from.y()) * ratio,
+    );
+    (ratio, projected)
+}
+
+fn distance(point: &Point<f64>, paths: &[Vec<Point<f64>>]) -> Option<f64> {
+    paths
+        .iter()
+        .flat_map(|path| {
+            let single_point = path.first().filter(|_| path.len() == 1);
+            single_point
+                .map(|single_point| point.haversine_distance(single_point))
+                .into_iter()
+                .chain(path.windows(2).map(move |segment| {
+                    point.haversine_distance(&project(point, &segment[0], &segment[1]).1)
+                }))
+        })
+        .min_by(|lhs, rhs| lhs.partial_cmp(rhs).unwrap_or(Ordering::Equal))
+}
+
+/// Mean distance (in meters) between the points and a linestring or a
+/// multilinestring.
+pub(crate) fn mean_distance(geometry: &Geometry<f64>, points: &[Point<f64>]) -> Option<f64> {
+    let paths = paths(geometry);
+    let distances = points
+        .iter()
+        .map(|point| distance(point, &paths))
+        .collect::<Option<Vec<_>>>()?;
+    if distances.is_empty() {
+        return None;
+    }
+    Some(distances.iter().sum::<f64>() / distances.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::{LineString, MultiLineString};
+
+    fn line_string(points: &[(f64, f64)]) -> LineString<f64> {
+        points
+            .iter()
+            .map(|&(x, y)| Point::new(x, y))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    #[test]
+    fn geometry_length() {
+        // 2 pieces of ~111m
+        let geometry = Geometry::MultiLineString(MultiLineString(vec![
+            line_string(&[(0., 0.), (0.001, 0.)]),
+            line_string(&[(0., 0.001), (0., 0.002)]),
+        ]));
+        assert!((length(&geometry) - 222.).abs() < 1.);
+        assert_eq!(0., length(&Geometry::Point(Point::new(0., 0.))));
+    }
+
+    #[test]
+    fn geometry_mean_distance() {
+        let geometry = Geometry::LineString(line_string(&[(0., 0.), (0.002, 0.)]));
+        // ~0m and ~111m
+        let points = [Point::new(0.001, 0.), Point::new(0.001, 0.001)];
+        let mean = mean_distance(&geometry, &points).unwrap();
+        assert!((mean - 55.6).abs() < 1.);
+        assert_eq!(None, mean_distance(&geometry, &[]));
+        assert_eq!(
+            None,
+            mean_distance(&Geometry::Point(Point::new(0., 0.)), &points)
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use super::measures::project;
+use geo::algorithm::haversine_distance::HaversineDistance;
+use geo::{LineString, MultiLineString, Point};
+use std::cmp::Ordering;
+
+// Pieces of a shape closer than this distance (in meters) are considered
+// connected
+const MAX_CONNECTION_DISTANCE: f64 = 1.;
+
+/// A gap between 2 consecutive pieces of a stitched shape.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Gap {
+    pub(crate) location: Point<f64>,
+    pub(crate) length: f64,
+}
+
+/// Chains the pieces of a shape into a single path, each time joining the
+/// nearest piece to one of the ends of the path.
+pub(crate) fn stitch(shape: &MultiLineString<f64>) -> (Vec<Point<f64>>, Vec<Gap>) {
+    let mut pieces: Vec<Vec<Point<f64>>> = shape
+        .0
+        .iter()
+        .map(|line_string| line_string.points_iter().collect::<Vec<_>>())
+        .filter(|piece| !piece.is_empty())
+        .collect();
+    let mut gaps = Vec::new();
+    if pieces.is_empty() {
+        return (Vec::new(), gaps);
+    }
+    let mut path = pieces.remove(0);
+    while !pieces.is_empty() {
+        let start = path[0];
+        let end = path[path.len() - 1];
+        // (distance, index of the piece, piece to reverse, join at the end of the path)
+        let (distance, index, reverse, at_end) = pieces
+            .iter()
+            .enumerate()
+            .flat_map(|(index, piece)| {
+                let first = piece[0];
+                let last = piece[piece.len() - 1];
+                vec![
+                    (end.haversine_distance(&first), index, false, true),
+                    (end.haversine_distance(&last), index, true, true),
+                    (start.haversine_distance(&last), index, false, false),
+                    (start.haversine_distance(&first), index, true, false),
+                ]
+            })
+            .min_by(|lhs, rhs| lhs.0.partial_cmp(&rhs.0).unwrap_or(Ordering::Equal))
+            .unwrap();
+        let mut piece = pieces.remove(index);
+        if reverse {
+            piece.reverse();
+        }
+        if distance > MAX_CONNECTION_DISTANCE {
+            gaps.push(Gap {
+                location: if at_end { end } else { start },
+                length: distance,
+            });
+        } else if at_end {
+            piece.remove(0);
+        } else {
+            piece.pop();
+        }
+        if at_end {
+            path.extend(piece);
+        } else {
+            piece.extend(path);
+            path = piece;
+        }
+    }
+    (path, gaps)
+}
+
+// Projection of a point on a path: index of the segment, ratio along this
+// segment and distance (in meters) to the path
+type Location = (usize, f64, f64);
+
+fn locate(path: &[Point<f64>], point: &Point<f64>) -> Option<Location> {
+    if path.len() == 1 {
+        return Some((0, 0., point.haversine_distance(&path[0])));
+    }
+    path.windows(2)
+        .enumerate()
+        .map(|(index, segment)| {
+            let (ratio, projected) = project(point, &segment[0], &segment[1]);
+            (index, ratio, point.haversine_distance(&projected))
+        })
+        .min_by(|lhs, rhs| lhs.2.partial_cmp(&rhs.2).unwrap_or(Ordering::Equal))
+}
+
+fn interpolate(path: &[Point<f64>], (index, ratio): (usize, f64)) -> Point<f64> {
+    let from = path[index];
+    match path.get(index + 1) {
+        Some(to) => Point::new(
+            from.x() + (to.x() - from.x()) * ratio,
+            from.y() + (to.y() - from.y()) * ratio,
+        ),
+        None => from,
+    }
+}
+
+fn is_before(lhs: &Location, rhs: &Location) -> bool {
+    (lhs.0, lhs.1) < (rhs.0, rhs.1)
+}
+
+// Part of the path between 2 locations, `from` being before `to`
+fn substring(path: &[Point<f64>], from: &Location, to: &Location) -> Vec<Point<f64>> {
+    let mut points = vec![interpolate(path, (from.0, from.1))];
+    points.extend(path[from.0 + 1..=to.0].iter().cloned());
+    points.push(interpolate(path, (to.0, to.1)));
+    points.dedup();
+    points
+}
+
+/// Stitches a shape into a single linestring, oriented from the first stop
+/// to the last one and cut at their projections.
+///
+/// The linestring is neither oriented nor cut if one of the stops is
+/// farther than `max_stop_distance` (in meters) from the shape.
+pub(crate) fn fit_to_stops(
+    shape: &MultiLineString<f64>,
+    first_stop: &Point<f64>,
+    last_stop: &Point<f64>,
+    max_stop_distance: f64,
+) -> (LineString<f64>, Vec<Gap>) {
+    let (path, gaps) = stitch(shape);
+    let locations = (locate(&path, first_stop), locate(&path, last_stop));
+    let path = match locations {
+        (Some(first), Some(last))
+            if first.2 <= max_stop_distance && last.2 <= max_stop_distance =>
+        {
+            if is_before(&first, &last) {
+                substring(&path, &first, &last)
+            } else if is_before(&last, &first) {
+                let mut reversed = substring(&path, &last, &first);
+                reversed.reverse();
+                reversed
+            } else {
+                path
+            }
+        }
+        _ => path,
+    };
+    (path.into(), gaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_string(points: &[(f64, f64)]) -> LineString<f64> {
+        points
+            .iter()
+            .map(|&(x, y)| Point::new(x, y))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    #[test]
+    fn stitch_unordered_pieces() {
+        let shape = MultiLineString(vec![
+            line_string(&[(2., 0.), (3., 0.)]),
+            line_string(&[(1., 0.), (0., 0.)]),
+            line_string(&[(1., 0.), (2., 0.)]),
+        ]);
+        let (path, gaps) = stitch(&shape);
+        assert!(gaps.is_empty());
+        assert_eq!(
+            vec![
+                Point::new(0., 0.),
+                Point::new(1., 0.),
+                Point::new(2., 0.),
+                Point::new(3., 0.)
+            ],
+            path
+        );
+    }
+
+    #[test]
+    fn stitch_with_gap() {
+        let shape = MultiLineString(vec![
+            line_string(&[(0., 0.), (0.001, 0.)]),
+            line_string(&[(0.002, 0.), (0.003, 0.)]),
+        ]);
+        let (path, gaps) = stitch(&shape);
+        assert_eq!(4, path.len());
+        assert_eq!(1, gaps.len());
+        assert_eq!(Point::new(0.001, 0.), gaps[0].location);
+        // ~111m
+        assert!((gaps[0].length - 111.).abs() < 1.);
+    }
+
+    #[test]
+    fn fit_to_stops_orients_and_trims() {
+        let shape = MultiLineString(vec![line_string(&[
+            (0., 0.),
+            (0.001, 0.),
+            (0.002, 0.),
+            (0.003, 0.),
+        ])]);
+        // Stops next to the shape, travelling westward
+        let (line, gaps) = fit_to_stops(
+            &shape,
+            &Point::new(0.0025, 0.0001),
+            &Point::new(0.0005, 0.0001),
+            50.,
+        );
+        assert!(gaps.is_empty());
+        let points: Vec<_> = line.points_iter().collect();
+        assert_eq!(4, points.len());
+        assert!((points[0].x() - 0.0025).abs() < 1e-9);
+        assert!((points[1].x() - 0.002).abs() < 1e-9);
+        assert!((points[3].x() - 0.0005).abs() < 1e-9);
+
+        // Stops too far from the shape
+        let (line, _) = fit_to_stops(
+            &shape,
+            &Point::new(0.0025, 0.01),
+            &Point::new(0.0005, 0.01),
+            50.,
+        );
+        assert_eq!(4, line.points_iter().count());
+        assert_eq!(Some(Point::new(0., 0.)), line.points_iter().next());
+    }
+}
@@ -40,6 +40,15 @@ impl<R: ReportCategory> Report<R> {
             self.warnings.push(report_row);
         }
     }
+    /// Add a warning report record, without checking if the same record has
+    /// already been added. For the callers already keeping the added records
+    /// in a set, the check being linear in the number of warnings.
+    pub fn add_unchecked_warning(&mut self, warning: String, warning_type: R) {
+        self.warnings.push(ReportRow {
+            category: warning_type,
+            message: warning,
+        });
+    }
     /// Add an error report record.
     pub fn add_error(&mut self, error: String, error_type: R) {
         let report_row = ReportRow {
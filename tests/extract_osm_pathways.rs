@@ -0,0 +1,79 @@
+use std::path::Path;
+use tartare_tools::pathways::{PathwayOptions, ReportCategory};
+use tartare_tools::report::Report;
+use transit_model::ntfs;
+use transit_model::objects::{PathwayMode, StopType};
+use transit_model::Model;
+
+#[test]
+fn test_extract_osm_pathways() {
+    let input_dir = "./tests/fixtures/extract-osm-pathways/input";
+    let mut collections = ntfs::read(input_dir).unwrap().into_collections();
+    let mut report: Report<ReportCategory> = Report::default();
+    tartare_tools::pathways::from_osm(
+        Path::new("./tests/fixtures/extract-osm-pathways/stations.osm.pbf"),
+        &mut collections,
+        &PathwayOptions::default(),
+        &mut report,
+    )
+    .unwrap();
+
+    // 2 entrances, a junction and an elevator on 2 levels
+    let mut stop_location_ids: Vec<_> = collections
+        .stop_locations
+        .values()
+        .map(|stop_location| stop_location.id.as_str())
+        .collect();
+    stop_location_ids.sort();
+    assert_eq!(
+        vec![
+            "sa:1:osm:node:1",
+            "sa:1:osm:node:2",
+            "sa:1:osm:node:5:-1",
+            "sa:1:osm:node:5:0",
+            "sa:1:osm:node:6",
+        ],
+        stop_location_ids
+    );
+    let entrance = collections.stop_locations.get("sa:1:osm:node:1").unwrap();
+    assert_eq!(StopType::StopEntrance, entrance.stop_type);
+    assert_eq!("Sortie 1", entrance.name);
+    assert_eq!(Some("sa:1"), entrance.parent_id.as_deref());
+    assert_eq!(Some("level:0"), entrance.level_id.as_deref());
+
+    // The sidewalk without level is ignored
+    assert_eq!(5, collections.pathways.len());
+    let stairs = collections.pathways.get("sa:1:osm:way:10:0").unwrap();
+    assert_eq!(PathwayMode::Stairs, stairs.pathway_mode);
+    assert_eq!(Some(20), stairs.stair_count);
+    // ~22m at 0.5m/s
+    assert_eq!(Some(45), stairs.traversal_time);
+    let walkway = collections.pathways.get("sa:1:osm:way:11:0").unwrap();
+    assert_eq!("sa:1:osm:node:2", walkway.from_stop_id);
+    assert_eq!("sp:1", walkway.to_stop_id);
+    let elevator = collections.pathways.get("sa:1:osm:node:5:-1:0").unwrap();
+    assert_eq!(PathwayMode::Elevator, elevator.pathway_mode);
+    assert_eq!(Some(60), elevator.traversal_time);
+
+    let mut level_ids: Vec<_> = collections
+        .levels
+        .values()
+        .map(|level| level.id.as_str())
+        .collect();
+    level_ids.sort();
+    assert_eq!(vec!["level:-1", "level:0"], level_ids);
+
+    let serialized_report = serde_json::to_value(&report).unwrap();
+    let categories: Vec<_> = serialized_report["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|warning| warning["category"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        vec!["StopAreaWithoutEntrance", "OsmStopAreaNotMatched"],
+        categories
+    );
+
+    Model::new(collections).unwrap();
+}
@@ -1,7 +1,8 @@
+use navitia_poi_model::{Coord, Model, Poi, PoiReader, PoiType, PoiWriter};
 use osm_utils::poi::PoiConfig;
 use std::fs::File;
 use std::io;
-use tartare_tools::poi::osm::extract_pois;
+use tartare_tools::poi::osm::{extract_pois, extract_pois_to_path};
 use transit_model::test_utils::*;
 
 #[test]
@@ -37,3 +38,177 @@ fn test_export_pois() {
         );
     });
 }
+
+#[test]
+fn test_stream_pois() {
+    test_in_tmp_dir(|path| {
+        let osm_pbf = "./tests/fixtures/extract_osm_pois/input/osm_fixture.osm.pbf";
+        let pois_config = "./tests/fixtures/extract_osm_pois/input/pois_config.json";
+
+        let r = File::open(pois_config).unwrap();
+        let matcher = PoiConfig::from_reader(r).unwrap();
+
+        extract_pois_to_path(osm_pbf, matcher, path.join("pois.zip")).unwrap();
+
+        let output_file = path.join("pois.poi");
+        assert!(output_file.is_file());
+        // the temporary properties file should have been cleaned up
+        assert!(!path.join("pois.poi_properties.tmp").exists());
+
+        let mut reader = PoiReader::from_path(&output_file).unwrap();
+        let pois: Vec<_> = reader.pois().unwrap().collect::<Result<_, _>>().unwrap();
+        let model = Model::try_from_path(&output_file).unwrap();
+        assert_eq!(model.pois.len(), pois.len());
+        for poi in pois {
+            let expected = &model.pois[&poi.id];
+            assert_eq!(expected.properties.len(), poi.properties.len());
+        }
+
+        let file = File::open(&output_file).unwrap();
+        let mut archive = zip::ZipArchive::new(file).unwrap();
+
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).unwrap();
+            let outpath = file.sanitized_name();
+            let mut outfile = File::create(&path.join(outpath)).unwrap();
+            io::copy(&mut file, &mut outfile).unwrap();
+        }
+
+        compare_output_dir_with_expected(
+            &path,
+            Some(vec!["poi.txt", "poi_properties.txt", "poi_type.txt"]),
+            "./tests/fixtures/extract_osm_pois/output",
+        );
+    });
+}
+
+#[test]
+fn test_poi_writer_cleanup() {
+    test_in_tmp_dir(|path| {
+        let writer = PoiWriter::from_path(path.join("pois.zip")).unwrap();
+        assert!(path.join("pois.poi_properties.tmp").exists());
+        // dropped without being finished, like after an error
+        drop(writer);
+        assert!(!path.join("pois.poi_properties.tmp").exists());
+        assert!(!path.join("pois.poi_translations.tmp").exists());
+        assert!(!path.join("pois.poi_addresses.tmp").exists());
+    });
+}
+
+// Writes a `.poi` archive with the files of `files`, returning its path
+fn write_archive(path: &std::path::Path, files: &[(&str, &str)]) -> std::path::PathBuf {
+    let output_file = path.join("pois.poi");
+    let mut zip = zip::ZipWriter::new(File::create(&output_file).unwrap());
+    for (name, content) in files.iter() {
+        zip.start_file(*name, zip::write::FileOptions::default())
+            .unwrap();
+        io::Write::write_all(&mut zip, content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+    output_file
+}
+
+const POI_TYPES: &str = "poi_type_id;poi_type_name\ntype1;Type 1\n";
+
+#[test]
+fn test_read_unordered_properties() {
+    test_in_tmp_dir(|path| {
+        let output_file = write_archive(
+            path,
+            &[
+                (
+                    "poi.txt",
+                    "poi_id;poi_type_id;poi_name;poi_lat;poi_lon;poi_weight;poi_visible\n\
+                     poi1;type1;POI 1;48.85;2.35;0;1\n\
+                     poi2;type1;POI 2;48.86;2.36;0;1\n\
+                     poi3;type1;POI 3;48.87;2.37;0;1\n",
+                ),
+                ("poi_type.txt", POI_TYPES),
+                (
+                    "poi_properties.txt",
+                    "poi_id;key;value\npoi2;amenity;bar\npoi1;amenity;cafe\npoi3;amenity;pub\n",
+                ),
+            ],
+        );
+
+        let mut reader = PoiReader::from_path(&output_file).unwrap();
+        let pois: Vec<_> = reader.pois().unwrap().collect();
+        // The property of poi1 is found after poi2, which fails instead of
+        // ignoring it
+        assert!(pois[0].is_ok());
+        assert!(pois[1].is_err());
+    });
+}
+
+#[test]
+fn test_read_unordered_pois() {
+    test_in_tmp_dir(|path| {
+        let output_file = write_archive(
+            path,
+            &[
+                (
+                    "poi.txt",
+                    "poi_id;poi_type_id;poi_name;poi_lat;poi_lon;poi_weight;poi_visible\n\
+                     poi1;type1;POI 1;48.85;2.35;0;1\n\
+                     poi3;type1;POI 3;48.87;2.37;0;1\n\
+                     poi2;type1;POI 2;48.86;2.36;0;1\n",
+                ),
+                ("poi_type.txt", POI_TYPES),
+            ],
+        );
+
+        let mut reader = PoiReader::from_path(&output_file).unwrap();
+        let pois: Vec<_> = reader.pois().unwrap().collect();
+        assert!(pois[0].is_ok());
+        assert!(pois[1].is_ok());
+        assert!(pois[2].is_err());
+    });
+}
+
+#[test]
+fn test_translations_round_trip() {
+    test_in_tmp_dir(|path| {
+        let poi = |id: &str, translated_names: &[(&str, &str)]| Poi {
+            id: id.to_string(),
+            name: format!("POI {}", id),
+            coord: Coord::try_new(2.35, 48.85).unwrap(),
+            poi_type_id: "amenity:cafe".to_string(),
+            properties: Vec::new(),
+            translated_names: translated_names
+                .iter()
+                .map(|&(lang, name)| (lang.to_string(), name.to_string()))
+                .collect(),
+            address: None,
+            visible: true,
+            weight: 0,
+        };
+        let pois = vec![
+            poi("poi1", &[("en", "Coffee shop"), ("fr", "Café")]),
+            poi("poi2", &[]),
+            poi("poi3", &[("de", "Kneipe")]),
+        ];
+        let mut writer = PoiWriter::from_path(path.join("pois.zip")).unwrap();
+        writer.add_poi_type(PoiType {
+            id: "amenity:cafe".to_string(),
+            name: "Café".to_string(),
+        });
+        for poi in &pois {
+            writer.write_poi(poi).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let output_file = path.join("pois.poi");
+        let mut reader = PoiReader::from_path(&output_file).unwrap();
+        let read_pois: Vec<Poi> = reader.pois().unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(pois.len(), read_pois.len());
+        for (poi, read_poi) in pois.iter().zip(&read_pois) {
+            assert_eq!(poi.id, read_poi.id);
+            assert_eq!(poi.translated_names, read_poi.translated_names);
+        }
+
+        let model = Model::try_from_path(&output_file).unwrap();
+        for poi in &pois {
+            assert_eq!(poi.translated_names, model.pois[&poi.id].translated_names);
+        }
+    });
+}
@@ -1,4 +1,6 @@
 use std::path::Path;
+use tartare_tools::improve_stop_positions::{PositionPolicy, ReportCategory};
+use tartare_tools::report::Report;
 use transit_model::ntfs;
 use transit_model::test_utils::*;
 use transit_model::Model;
@@ -12,7 +14,8 @@ fn test_global() {
         tartare_tools::improve_stop_positions::improve_with_pbf(
             Path::new("./tests/fixtures/improve-stop-positions/lemans-nodes.osm.pbf"),
             &mut collections,
-            100.00,
+            &PositionPolicy::new(100.00),
+            &mut Report::default(),
         )
         .unwrap();
         let model = Model::new(collections).unwrap();
@@ -24,3 +27,78 @@ fn test_global() {
         );
     });
 }
+
+#[test]
+fn test_max_displacement() {
+    let input_dir = "./tests/fixtures/improve-stop-positions/input";
+    let model = ntfs::read(input_dir).unwrap();
+    let mut collections = ntfs::read(input_dir).unwrap().into_collections();
+    let mut report: Report<ReportCategory> = Report::default();
+    tartare_tools::improve_stop_positions::improve_with_pbf(
+        Path::new("./tests/fixtures/improve-stop-positions/lemans-nodes.osm.pbf"),
+        &mut collections,
+        &PositionPolicy {
+            max_displacement: Some(1_000.),
+            ..PositionPolicy::new(100.)
+        },
+        &mut report,
+    )
+    .unwrap();
+    // The osm stops of sp:04 are about 250m away, the ones of sp:01, sp:02
+    // and sp:07 more than 1km away
+    let moved_stop_point = "sp:04";
+    let refused_stop_points = ["sp:01", "sp:02", "sp:07"];
+    for stop_point in collections.stop_points.values() {
+        let initial_coord = model.stop_points.get(&stop_point.id).unwrap().coord;
+        if stop_point.id == moved_stop_point {
+            assert_ne!(initial_coord, stop_point.coord);
+        } else {
+            assert_eq!(initial_coord, stop_point.coord);
+        }
+    }
+    let serialized_report = serde_json::to_value(&report).unwrap();
+    let warnings = serialized_report["warnings"].as_array().unwrap();
+    assert_eq!(refused_stop_points.len(), warnings.len());
+    for stop_point_id in &refused_stop_points {
+        assert!(warnings.iter().any(|warning| {
+            warning["category"] == "DisplacementTooLong"
+                && warning["message"]
+                    .as_str()
+                    .unwrap()
+                    .contains(&format!("{:?}", stop_point_id))
+        }));
+    }
+}
+
+#[test]
+fn test_recompute_all_stop_areas() {
+    let input_dir = "./tests/fixtures/improve-stop-positions/input";
+    let mut collections = ntfs::read(input_dir).unwrap().into_collections();
+    tartare_tools::improve_stop_positions::improve_with_pbf(
+        Path::new("./tests/fixtures/improve-stop-positions/lemans-nodes.osm.pbf"),
+        &mut collections,
+        &PositionPolicy {
+            recompute_all_stop_areas: true,
+            // No stop point is moved
+            ..PositionPolicy::new(1_000_000.)
+        },
+        &mut Report::default(),
+    )
+    .unwrap();
+    for stop_area in collections.stop_areas.values() {
+        let coords: Vec<_> = collections
+            .stop_points
+            .values()
+            .filter(|stop_point| stop_point.stop_area_id == stop_area.id)
+            .map(|stop_point| stop_point.coord)
+            .collect();
+        if coords.is_empty() {
+            continue;
+        }
+        let nb_coords = coords.len() as f64;
+        let lon = coords.iter().map(|coord| coord.lon).sum::<f64>() / nb_coords;
+        let lat = coords.iter().map(|coord| coord.lat).sum::<f64>() / nb_coords;
+        assert!((stop_area.coord.lon - lon).abs() < 1e-9);
+        assert!((stop_area.coord.lat - lat).abs() < 1e-9);
+    }
+}
@@ -1,7 +1,10 @@
 use log::Level as LogLevel;
 use std::collections::HashMap;
 use std::path::Path;
-use tartare_tools::improve_stop_positions;
+use tartare_tools::improve_stop_positions::{
+    self, CuratedMapping, GeoMatching, MappingRecord, MatchingMethod, MatchingOptions,
+    MatchingReport, NetworkMapping, NtfsObjectType, SequenceAlignment,
+};
 use transit_model::ntfs;
 use transit_model::test_utils::*;
 
@@ -16,7 +19,8 @@ fn test_map_no_force() {
             Path::new("./tests/fixtures/map-ntfs-with-osm/marseille-lite.osm.pbf"),
             model,
             ntfs_network_to_osm,
-            false,
+            &MatchingOptions::default(),
+            &mut MatchingReport::default(),
         )
         .unwrap();
         transit_model::ntfs::write(&enriched_model, path, get_test_datetime()).unwrap();
@@ -39,7 +43,11 @@ fn test_map_force() {
             Path::new("./tests/fixtures/map-ntfs-with-osm/marseille-lite.osm.pbf"),
             model,
             ntfs_network_to_osm,
-            true,
+            &MatchingOptions {
+                force_double_stop_point_matching: true,
+                ..Default::default()
+            },
+            &mut MatchingReport::default(),
         )
         .unwrap();
         transit_model::ntfs::write(&enriched_model, path, get_test_datetime()).unwrap();
@@ -64,7 +72,8 @@ fn test_unknown_network() {
             Path::new("./tests/fixtures/map-ntfs-with-osm/marseille-lite.osm.pbf"),
             model,
             ntfs_network_to_osm,
-            false,
+            &MatchingOptions::default(),
+            &mut MatchingReport::default(),
         )
         .unwrap();
         transit_model::ntfs::write(&enriched_model, path, get_test_datetime()).unwrap();
@@ -97,7 +106,8 @@ fn test_osm_without_route() {
             osm_pbf_path,
             model,
             ntfs_network_to_osm,
-            false,
+            &MatchingOptions::default(),
+            &mut MatchingReport::default(),
         )
         .unwrap();
         transit_model::ntfs::write(&enriched_model, path, get_test_datetime()).unwrap();
@@ -113,3 +123,271 @@ fn test_osm_without_route() {
         });
     });
 }
+
+#[test]
+fn test_matching_report() {
+    let mut ntfs_network_to_osm = HashMap::new();
+    ntfs_network_to_osm.insert("UNKNOWN", "UNKNOWN");
+    ntfs_network_to_osm.insert("RTM", "RTM");
+    let input_dir = "./tests/fixtures/map-ntfs-with-osm/input";
+    let model = ntfs::read(input_dir).unwrap();
+    let mut report = MatchingReport::default();
+    improve_stop_positions::enrich_object_codes(
+        Path::new("./tests/fixtures/map-ntfs-with-osm/marseille-lite.osm.pbf"),
+        model,
+        ntfs_network_to_osm,
+        &MatchingOptions::default(),
+        &mut report,
+    )
+    .unwrap();
+    assert!(report.coverage("UNKNOWN").is_none());
+    let coverage = report.coverage("RTM").unwrap();
+    assert!(coverage.lines.matched > 0);
+    assert!(coverage.lines.matched <= coverage.lines.total);
+    assert!(coverage.stop_points.matched > 0);
+    assert!(coverage.stop_points.matched <= coverage.stop_points.total);
+
+    let serialized_report = serde_json::to_value(&report).unwrap();
+    assert!(serialized_report["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|warning| warning["category"] == "UnknownNetwork"));
+    assert_eq!(
+        coverage.stop_points.matched,
+        serialized_report["matched_stop_points"]
+            .as_object()
+            .unwrap()
+            .len()
+    );
+}
+
+#[test]
+fn test_curated_mapping() {
+    let input_dir = "./tests/fixtures/map-ntfs-with-osm/input";
+    let osm_pbf_path = Path::new("./tests/fixtures/map-ntfs-with-osm/marseille-lite.osm.pbf");
+    let mut ntfs_network_to_osm = HashMap::new();
+    ntfs_network_to_osm.insert("RTM", "RTM");
+    let mut report = MatchingReport::default();
+    improve_stop_positions::enrich_object_codes(
+        osm_pbf_path,
+        ntfs::read(input_dir).unwrap(),
+        ntfs_network_to_osm.clone(),
+        &MatchingOptions::default(),
+        &mut report,
+    )
+    .unwrap();
+    let mapping = report.mapping();
+    assert!(!mapping.is_empty());
+
+    // Re-importing the mapping keeps all its matchings
+    let mut curated_report = MatchingReport::default();
+    improve_stop_positions::enrich_object_codes(
+        osm_pbf_path,
+        ntfs::read(input_dir).unwrap(),
+        ntfs_network_to_osm,
+        &MatchingOptions {
+            curated_mapping: CuratedMapping::from_records(mapping.clone()),
+            ..Default::default()
+        },
+        &mut curated_report,
+    )
+    .unwrap();
+    let curated_mapping = curated_report.mapping();
+    for record in mapping {
+        assert!(curated_mapping.iter().any(|curated_record| {
+            curated_record.ntfs_object_type == record.ntfs_object_type
+                && curated_record.ntfs_id == record.ntfs_id
+                && curated_record.osm_id == record.osm_id
+                && curated_record.method == MatchingMethod::Curated
+        }));
+    }
+}
+
+#[test]
+fn test_curated_mapping_without_osm_lines() {
+    // The osm file has no lines, the curated stop points are still mapped
+    let mut ntfs_network_to_osm = HashMap::new();
+    ntfs_network_to_osm.insert("RTM", "RTM");
+    let enriched_model = improve_stop_positions::enrich_object_codes(
+        Path::new("./tests/fixtures/map-ntfs-with-osm/geo_matching.osm.pbf"),
+        ntfs::read("./tests/fixtures/map-ntfs-with-osm/input").unwrap(),
+        ntfs_network_to_osm,
+        &MatchingOptions {
+            curated_mapping: CuratedMapping::from_records(vec![MappingRecord {
+                ntfs_object_type: NtfsObjectType::StopPoint,
+                ntfs_id: "StopPoint:OM11750".to_string(),
+                osm_type: "node".to_string(),
+                osm_id: "104".to_string(),
+                score: None,
+                method: MatchingMethod::Curated,
+            }]),
+            ..Default::default()
+        },
+        &mut MatchingReport::default(),
+    )
+    .unwrap();
+    let stop_point = enriched_model.stop_points.get("StopPoint:OM11750").unwrap();
+    assert!(stop_point
+        .codes
+        .contains(&("osm_stop_points_id".to_string(), "node:104".to_string())));
+}
+
+#[test]
+fn test_networks_config() {
+    let input_dir = "./tests/fixtures/map-ntfs-with-osm/input";
+    let osm_pbf_path = Path::new("./tests/fixtures/map-ntfs-with-osm/marseille-lite.osm.pbf");
+    let mut ntfs_network_to_osm = HashMap::new();
+    ntfs_network_to_osm.insert("RTM", "RTM");
+    let mut label_report = MatchingReport::default();
+    improve_stop_positions::enrich_object_codes(
+        osm_pbf_path,
+        ntfs::read(input_dir).unwrap(),
+        ntfs_network_to_osm,
+        &MatchingOptions::default(),
+        &mut label_report,
+    )
+    .unwrap();
+
+    // The regex of the config matches the same osm network label
+    let network_mapping =
+        NetworkMapping::from_path("./tests/fixtures/map-ntfs-with-osm/networks.json").unwrap();
+    let mut config_report = MatchingReport::default();
+    improve_stop_positions::enrich_object_codes(
+        osm_pbf_path,
+        ntfs::read(input_dir).unwrap(),
+        network_mapping,
+        &MatchingOptions::default(),
+        &mut config_report,
+    )
+    .unwrap();
+    let label_coverage = label_report.coverage("RTM").unwrap();
+    let config_coverage = config_report.coverage("RTM").unwrap();
+    assert!(config_coverage.lines.matched > 0);
+    assert_eq!(label_coverage.lines.matched, config_coverage.lines.matched);
+    assert_eq!(
+        label_coverage.stop_points.matched,
+        config_coverage.stop_points.matched
+    );
+}
+
+// The osm stops matched by proximity to the stop points of the RTM network,
+// indexed by stop point
+fn geo_matched_osm_stops(max_distance: f64) -> HashMap<String, String> {
+    let input_dir = "./tests/fixtures/map-ntfs-with-osm/input";
+    let model = ntfs::read(input_dir).unwrap();
+    let mut ntfs_network_to_osm = HashMap::new();
+    ntfs_network_to_osm.insert("RTM", "RTM");
+    let enriched_model = improve_stop_positions::enrich_object_codes(
+        Path::new("./tests/fixtures/map-ntfs-with-osm/geo_matching.osm.pbf"),
+        model,
+        ntfs_network_to_osm,
+        &MatchingOptions {
+            geo_matching: Some(GeoMatching {
+                max_distance,
+                min_name_similarity: 0.8,
+            }),
+            ..Default::default()
+        },
+        &mut MatchingReport::default(),
+    )
+    .unwrap();
+    enriched_model
+        .stop_points
+        .values()
+        .flat_map(|stop_point| {
+            stop_point
+                .codes
+                .iter()
+                .filter(|(system, _)| system == "osm_stop_points_id")
+                .map(move |(_, osm_id)| (stop_point.id.clone(), osm_id.clone()))
+        })
+        .collect()
+}
+
+#[test]
+fn test_geo_matching_distance() {
+    // "Michelet Huveaune" is 8m from the osm stop of the same name, and the
+    // stop point of the opposite direction is 30m away; "Michelet Ganay" is
+    // 6m from an osm stop with another name
+    let osm_stops = geo_matched_osm_stops(20.);
+    assert_eq!(
+        Some(&"node:101".to_string()),
+        osm_stops.get("StopPoint:OM11748")
+    );
+    assert_eq!(None, osm_stops.get("StopPoint:OM11790"));
+    assert_eq!(None, osm_stops.get("StopPoint:OM11750"));
+
+    let osm_stops = geo_matched_osm_stops(5.);
+    assert_eq!(None, osm_stops.get("StopPoint:OM11748"));
+}
+
+#[test]
+fn test_platform_only_osm_stops() {
+    // By default, the osm nodes with only a public_transport=platform tag
+    // are mapped, unlike the ones with only public_transport=stop_position
+    let osm_stops = geo_matched_osm_stops(20.);
+    assert_eq!(
+        Some(&"node:102".to_string()),
+        osm_stops.get("StopPoint:OM11749")
+    );
+    assert_eq!(None, osm_stops.get("StopPoint:OM11789"));
+}
+
+// The routes of the RTM network matched with the osm route of the bus 10,
+// which has one more stop than its journey pattern, with their alignment
+// score
+fn aligned_routes(options: &MatchingOptions) -> Vec<(String, Option<f64>)> {
+    let input_dir = "./tests/fixtures/map-ntfs-with-osm/input";
+    let mut ntfs_network_to_osm = HashMap::new();
+    ntfs_network_to_osm.insert("RTM", "RTM");
+    let mut report = MatchingReport::default();
+    improve_stop_positions::enrich_object_codes(
+        Path::new("./tests/fixtures/map-ntfs-with-osm/alignment.osm.pbf"),
+        ntfs::read(input_dir).unwrap(),
+        ntfs_network_to_osm,
+        options,
+        &mut report,
+    )
+    .unwrap();
+    report
+        .mapping()
+        .into_iter()
+        .filter(|record| record.ntfs_object_type == NtfsObjectType::Route)
+        .map(|record| (record.ntfs_id, record.score))
+        .collect()
+}
+
+#[test]
+fn test_sequence_alignment() {
+    // Without alignment, the journey pattern needs as many stops as the osm
+    // route
+    assert!(aligned_routes(&Default::default()).is_empty());
+
+    let options = MatchingOptions {
+        sequence_alignment: Some(SequenceAlignment {
+            min_score: 0.9,
+            max_distance: 100.,
+            min_name_similarity: 0.8,
+        }),
+        ..Default::default()
+    };
+    let routes = aligned_routes(&options);
+    assert_eq!(1, routes.len());
+    let (route_id, score) = &routes[0];
+    assert_eq!("OM110OM179", route_id);
+    // 20 aligned stops and a missing one, out of 21 stops
+    let score = score.unwrap();
+    assert!((score - 19.5 / 21.).abs() < 1e-6, "{}", score);
+
+    // Below the minimum score, the route is not matched
+    let options = MatchingOptions {
+        sequence_alignment: Some(SequenceAlignment {
+            min_score: 0.95,
+            max_distance: 100.,
+            min_name_similarity: 0.8,
+        }),
+        ..Default::default()
+    };
+    assert!(aligned_routes(&options).is_empty());
+}
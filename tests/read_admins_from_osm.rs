@@ -0,0 +1,93 @@
+use std::path::Path;
+use tartare_tools::read_admins::{AdminOptions, AdminOutput, ReportCategory};
+use tartare_tools::report::Report;
+use transit_model::model::Collections;
+use transit_model::ntfs;
+
+fn read_admins(options: &AdminOptions) -> (Collections, Report<ReportCategory>) {
+    let input_dir = "./tests/fixtures/read-admins-from-osm/input";
+    let mut collections = ntfs::read(input_dir).unwrap().into_collections();
+    let mut report = Report::default();
+    tartare_tools::read_admins::from_osm(
+        Path::new("./tests/fixtures/read-admins-from-osm/admins.osm.pbf"),
+        &mut collections,
+        options,
+        &mut report,
+    )
+    .unwrap();
+    (collections, report)
+}
+
+fn code<'a>(collections: &'a Collections, stop_area_id: &str, code_type: &str) -> Option<&'a str> {
+    collections
+        .stop_areas
+        .get(stop_area_id)
+        .unwrap()
+        .codes
+        .iter()
+        .find(|(t, _)| t == code_type)
+        .map(|(_, value)| value.as_str())
+}
+
+#[test]
+fn test_admin_codes() {
+    let options = AdminOptions {
+        levels: vec![6, 8].into_iter().collect(),
+        ..Default::default()
+    };
+    let (collections, report) = read_admins(&options);
+    assert_eq!(
+        Some("admin:osm:relation:200"),
+        code(&collections, "sa:1", "osm_admin_8_id")
+    );
+    assert_eq!(
+        Some("Ville Ouest"),
+        code(&collections, "sa:1", "osm_admin_8_name")
+    );
+    assert_eq!(
+        Some("admin:osm:relation:300"),
+        code(&collections, "sa:2", "osm_admin_8_id")
+    );
+    for stop_area_id in &["sa:1", "sa:2", "sa:3"] {
+        assert_eq!(
+            Some("Seine"),
+            code(&collections, stop_area_id, "osm_admin_6_name")
+        );
+    }
+    // Outside of the cities
+    assert_eq!(None, code(&collections, "sa:3", "osm_admin_8_id"));
+
+    let serialized_report = serde_json::to_value(&report).unwrap();
+    let warnings = serialized_report["warnings"].as_array().unwrap();
+    assert_eq!(1, warnings.len());
+    assert_eq!("StopAreaWithoutAdmin", warnings[0]["category"]);
+}
+
+#[test]
+fn test_admin_stations() {
+    let options = AdminOptions {
+        output: AdminOutput::AdminStations,
+        ..Default::default()
+    };
+    let (collections, _) = read_admins(&options);
+    let mut admin_stations: Vec<_> = collections
+        .admin_stations
+        .values()
+        .map(|admin_station| {
+            (
+                admin_station.stop_id.as_str(),
+                admin_station.admin_id.as_str(),
+                admin_station.admin_name.as_str(),
+            )
+        })
+        .collect();
+    admin_stations.sort();
+    assert_eq!(
+        vec![
+            ("sa:1", "admin:osm:relation:200", "Ville Ouest"),
+            ("sa:2", "admin:osm:relation:300", "Ville Est"),
+        ],
+        admin_stations
+    );
+    assert_eq!(None, code(&collections, "sa:1", "osm_admin_8_id"));
+}
@@ -0,0 +1,76 @@
+use std::path::Path;
+use tartare_tools::read_equipments::{Precedence, ReportCategory};
+use tartare_tools::report::Report;
+use transit_model::model::Collections;
+use transit_model::ntfs;
+use transit_model::objects::{Availability, Equipment};
+
+fn read_equipments(precedence: Precedence) -> (Collections, Report<ReportCategory>) {
+    let input_dir = "./tests/fixtures/read-equipments-from-osm/input";
+    let mut collections = ntfs::read(input_dir).unwrap().into_collections();
+    let mut report = Report::default();
+    tartare_tools::read_equipments::from_osm(
+        Path::new("./tests/fixtures/read-equipments-from-osm/stops.osm.pbf"),
+        &mut collections,
+        precedence,
+        &mut report,
+    )
+    .unwrap();
+    (collections, report)
+}
+
+fn stop_point_equipment<'a>(collections: &'a Collections, stop_point_id: &str) -> &'a Equipment {
+    let stop_point = collections.stop_points.get(stop_point_id).unwrap();
+    let equipment_id = stop_point.equipment_id.as_ref().unwrap();
+    collections.equipments.get(equipment_id).unwrap()
+}
+
+#[test]
+fn test_ntfs_precedence() {
+    let (collections, report) = read_equipments(Precedence::Ntfs);
+    let equipment = stop_point_equipment(&collections, "sp:1");
+    // Known in the ntfs, so kept
+    assert_eq!(Availability::NotAvailable, equipment.wheelchair_boarding);
+    assert_eq!(Availability::Available, equipment.sheltered);
+    assert_eq!(Availability::NotAvailable, equipment.appropriate_signage);
+    // The original equipment is not modified
+    let original_equipment = collections.equipments.get("eq:1").unwrap();
+    assert_eq!(
+        Availability::InformationNotAvailable,
+        original_equipment.sheltered
+    );
+
+    // The 2 osm stops disagree on the shelter
+    let equipment = stop_point_equipment(&collections, "sp:2");
+    assert_eq!(Availability::InformationNotAvailable, equipment.sheltered);
+    assert_eq!(Availability::Available, equipment.visual_announcement);
+
+    assert!(collections
+        .stop_points
+        .get("sp:3")
+        .unwrap()
+        .equipment_id
+        .is_none());
+
+    let serialized_report = serde_json::to_value(&report).unwrap();
+    let categories: Vec<_> = serialized_report["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|warning| warning["category"].as_str().unwrap())
+        .collect();
+    assert_eq!(vec!["ConflictingOsmStops", "ConflictWithNtfs"], categories);
+}
+
+#[test]
+fn test_osm_precedence() {
+    let (collections, report) = read_equipments(Precedence::Osm);
+    let equipment = stop_point_equipment(&collections, "sp:1");
+    assert_eq!(Availability::Available, equipment.wheelchair_boarding);
+    let serialized_report = serde_json::to_value(&report).unwrap();
+    assert!(serialized_report["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|warning| warning["category"] != "ConflictWithNtfs"));
+}
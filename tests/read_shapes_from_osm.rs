@@ -1,5 +1,8 @@
 use std::path::Path;
+use tartare_tools::read_shapes::{GeometryMode, MapMatching, ReportCategory, ShapeOptions};
+use tartare_tools::report::Report;
 use transit_model::ntfs;
+use transit_model::objects::Geometry;
 use transit_model::test_utils::*;
 use transit_model::Model;
 
@@ -12,6 +15,8 @@ fn test_read_shapes_from_osm() {
         tartare_tools::read_shapes::from_osm(
             Path::new("./tests/fixtures/read-shapes-from-osm/sample-lite.osm.pbf"),
             &mut collections,
+            &ShapeOptions::default(),
+            &mut Report::default(),
         )
         .unwrap();
         let model = Model::new(collections).unwrap();
@@ -32,8 +37,194 @@ fn test_read_shapes_relation_not_found() {
     tartare_tools::read_shapes::from_osm(
         Path::new("./tests/fixtures/read-shapes-from-osm/sample-lite.osm.pbf"),
         &mut collections,
+        &ShapeOptions::default(),
+        &mut Report::default(),
     )
     .unwrap();
     // No new geometry created since the relation is incorrect
     assert_eq!(0, collections.geometries.len());
 }
+
+#[test]
+fn test_fit_routes_to_stops() {
+    let input_dir = "./tests/fixtures/read-shapes-from-osm/input/ok";
+    let model = ntfs::read(input_dir).unwrap();
+    let mut collections = model.into_collections();
+    let mut report: Report<ReportCategory> = Report::default();
+    tartare_tools::read_shapes::from_osm(
+        Path::new("./tests/fixtures/read-shapes-from-osm/sample-lite.osm.pbf"),
+        &mut collections,
+        &ShapeOptions {
+            fit_routes_to_stops: Some(100.),
+            ..Default::default()
+        },
+        &mut report,
+    )
+    .unwrap();
+    // The routes shapes are stitched into a single linestring
+    for route_id in &["route:1", "route:2"] {
+        let route = collections.routes.get(route_id).unwrap();
+        let geometry_id = route.geometry_id.as_ref().unwrap();
+        let geometry = &collections.geometries.get(geometry_id).unwrap().geometry;
+        match geometry {
+            geo::Geometry::LineString(_) => {}
+            _ => panic!("the shape of {} is not a linestring", route_id),
+        }
+    }
+    // The lines shapes are not modified
+    let line = collections.lines.get("line:1").unwrap();
+    let geometry_id = line.geometry_id.as_ref().unwrap();
+    match &collections.geometries.get(geometry_id).unwrap().geometry {
+        geo::Geometry::MultiLineString(_) => {}
+        _ => panic!("the shape of line:1 is not a multilinestring"),
+    }
+    // The pieces of the relations are not connected
+    let serialized_report = serde_json::to_value(&report).unwrap();
+    assert!(serialized_report["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|warning| warning["category"] == "GapInShape"));
+}
+
+#[test]
+fn test_no_gaps_reported_for_kept_geometries() {
+    let input_dir = "./tests/fixtures/read-shapes-from-osm/input/ok";
+    let model = ntfs::read(input_dir).unwrap();
+    let mut collections = model.into_collections();
+    // A geometry going through all the stops, closer than the osm ones
+    collections
+        .geometries
+        .push(Geometry {
+            id: "geo:stops".to_string(),
+            geometry: geo::LineString::from(vec![(5.392, 43.271531), (5.393, 43.271531)]).into(),
+        })
+        .unwrap();
+    let route_ids: Vec<String> = collections.routes.values().map(|r| r.id.clone()).collect();
+    for route_id in &route_ids {
+        collections.routes.get_mut(route_id).unwrap().geometry_id = Some("geo:stops".to_string());
+    }
+    let mut report: Report<ReportCategory> = Report::default();
+    tartare_tools::read_shapes::from_osm(
+        Path::new("./tests/fixtures/read-shapes-from-osm/sample-lite.osm.pbf"),
+        &mut collections,
+        &ShapeOptions {
+            fit_routes_to_stops: Some(100.),
+            geometry_mode: GeometryMode::KeepCloser,
+        },
+        &mut report,
+    )
+    .unwrap();
+    for route in collections.routes.values() {
+        assert_eq!(Some("geo:stops"), route.geometry_id.as_deref());
+    }
+    // The gaps of the osm shapes which are not used are not reported
+    let serialized_report = serde_json::to_value(&report).unwrap();
+    assert!(serialized_report["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|warning| warning["category"] != "GapInShape"));
+}
+
+#[test]
+fn test_replace_existing_geometries() {
+    let input_dir = "./tests/fixtures/read-shapes-from-osm/input/ok";
+    let model = ntfs::read(input_dir).unwrap();
+    let mut collections = model.into_collections();
+    tartare_tools::read_shapes::from_osm(
+        Path::new("./tests/fixtures/read-shapes-from-osm/sample-lite.osm.pbf"),
+        &mut collections,
+        &ShapeOptions {
+            geometry_mode: GeometryMode::Replace,
+            ..Default::default()
+        },
+        &mut Report::default(),
+    )
+    .unwrap();
+    let route = collections.routes.get("route:3").unwrap();
+    assert_eq!(Some("geo:route:osm:route:3"), route.geometry_id.as_deref());
+    // The replaced geometry is not used anymore
+    assert!(collections.geometries.get("geo:existing:1").is_none());
+
+    // Geometry ids are stable: a second run replaces the geometries in place
+    let nb_geometries = collections.geometries.len();
+    tartare_tools::read_shapes::from_osm(
+        Path::new("./tests/fixtures/read-shapes-from-osm/sample-lite.osm.pbf"),
+        &mut collections,
+        &ShapeOptions {
+            geometry_mode: GeometryMode::Replace,
+            ..Default::default()
+        },
+        &mut Report::default(),
+    )
+    .unwrap();
+    assert_eq!(nb_geometries, collections.geometries.len());
+}
+
+#[test]
+fn test_map_matching() {
+    let input_dir = "./tests/fixtures/read-shapes-from-osm/input/map_matching";
+    let model = ntfs::read(input_dir).unwrap();
+    let mut collections = model.into_collections();
+    tartare_tools::read_shapes::from_osm_graph(
+        Path::new("./tests/fixtures/read-shapes-from-osm/map_matching.osm.pbf"),
+        &mut collections,
+        &MapMatching {
+            max_snapping_distance: 20.,
+        },
+    )
+    .unwrap();
+    let geometry_of = |geometry_id: &str| -> Vec<(f64, f64)> {
+        match &collections.geometries.get(geometry_id).unwrap().geometry {
+            geo::Geometry::LineString(line_string) => line_string
+                .0
+                .iter()
+                .map(|coord| (coord.x, coord.y))
+                .collect(),
+            _ => panic!("the geometry {} is not a linestring", geometry_id),
+        }
+    };
+    let assert_coords_eq = |expected: &[(f64, f64)], coords: Vec<(f64, f64)>| {
+        assert_eq!(expected.len(), coords.len(), "{:?}", coords);
+        for (expected, coord) in expected.iter().zip(coords) {
+            assert!((expected.0 - coord.0).abs() < 1e-6, "{:?}", coord);
+            assert!((expected.1 - coord.1).abs() < 1e-6, "{:?}", coord);
+        }
+    };
+
+    // The stops are projected on the roads, the bus follows the oneway road
+    // and not the footway
+    let bus_geometry_id = "geo:trip:osm_graph:trip:1";
+    assert_coords_eq(
+        &[
+            (2.351, 48.85),
+            (2.352, 48.85),
+            (2.354, 48.85),
+            (2.354, 48.852),
+            (2.352, 48.852),
+        ],
+        geometry_of(bus_geometry_id),
+    );
+    // The trips and routes of a journey pattern all get its geometry
+    for trip_id in &["trip:1", "trip:2"] {
+        let trip = collections.vehicle_journeys.get(trip_id).unwrap();
+        assert_eq!(Some(bus_geometry_id), trip.geometry_id.as_deref());
+    }
+    for route_id in &["route:1", "route:2"] {
+        let route = collections.routes.get(route_id).unwrap();
+        assert_eq!(Some(bus_geometry_id), route.geometry_id.as_deref());
+    }
+    // No way back against the oneway road
+    let trip = collections.vehicle_journeys.get("trip:4").unwrap();
+    assert_eq!(None, trip.geometry_id);
+
+    // The tram trip is routed on the rails, read from the same pbf
+    let tram_geometry_id = "geo:trip:osm_graph:trip:3";
+    assert_coords_eq(
+        &[(2.3505, 48.851), (2.3535, 48.851)],
+        geometry_of(tram_geometry_id),
+    );
+    let route = collections.routes.get("route:3").unwrap();
+    assert_eq!(Some(tram_geometry_id), route.geometry_id.as_deref());
+}
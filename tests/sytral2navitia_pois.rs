@@ -36,7 +36,12 @@ fn test_export_sytral_pois_ok() {
 
         compare_output_dir_with_expected(
             &path,
-            Some(vec!["poi.txt", "poi_properties.txt", "poi_type.txt"]),
+            Some(vec![
+                "poi.txt",
+                "poi_properties.txt",
+                "poi_type.txt",
+                "poi_addresses.txt",
+            ]),
             "./tests/fixtures/sytral2navitia-pois/output",
         );
     });
@@ -132,3 +137,16 @@ fn test_export_sytral_poi_id_double() {
         };
     }
 }
+
+#[test]
+fn test_export_sytral_pois_ko_coord_invalide() {
+    let input_path = "./tests/fixtures/sytral2navitia-pois/input/sytral_poi_echec5_coord_invalide";
+    let poi_model = extract_pois(input_path);
+    match poi_model {
+        Ok(_) => panic!(),
+        Err(e) => assert_eq!(e.iter_chain().map(|err| format!("{}", err)).collect::<Vec<String>>(),
+                             vec!["Error reading \"./tests/fixtures/sytral2navitia-pois/input/sytral_poi_echec5_coord_invalide/POI_TCL.csv\"",
+                                  "poi 'TCL:AGE:1457' rejected",
+                                  "invalid coordinates (lon: 4.772876385468, lat: 145.582740599557), expected -180 <= lon <= 180 and -90 <= lat <= 90"]),
+    };
+}